@@ -0,0 +1,652 @@
+use std::{collections::HashMap, path::PathBuf, str::FromStr};
+
+use crate::{error::AppError, mesh::VertexLayout};
+
+/// Command-line flags for the demo.
+/// Deliberately hand-rolled: there's only a few flags, so it's not worth pulling in clap.
+#[derive(Debug, Default, Clone)]
+pub struct Args {
+    /// Don't spin the pentagon, leave it sitting still like it used to.
+    pub no_spin: bool,
+    /// Draw every object and instance, even the ones the camera can't see, for comparing how
+    /// fast it is with and without frustum culling.
+    pub no_cull: bool,
+    /// Maximum number of frames the CPU can queue up before waiting for the GPU to catch up.
+    /// None means use wgpu's default.
+    pub frame_latency: Option<u32>,
+    /// Run the particle demo with this many particles.
+    pub particles: Option<u32>,
+    /// How many samples per pixel for multisample antialiasing. 1 means no MSAA.
+    pub msaa: Option<u32>,
+    /// Use wgpu's software/fallback adapter, even if there's a real graphics card.
+    pub force_fallback: bool,
+    /// Draw this many copies of the pentagon, scattered randomly.
+    pub scatter: Option<usize>,
+    /// Seed for the random number generator used to place things, e.g. the scattered pentagons.
+    pub seed: Option<u64>,
+    /// Draw a quad tiled 2x2 with the tree texture instead of the pentagon, to show off the
+    /// sampler's address modes.
+    pub tiling: bool,
+    /// Give each of the pentagon's corners its own colour, blended with the texture.
+    pub vertex_colors: bool,
+    /// Add two overlapping see-through quads, to show off sorting transparent objects.
+    pub transparent_quads: bool,
+    /// Add a floor at y = 0.
+    pub ground: bool,
+    /// Stand the --gltf model on the floor, rather than centring it on the origin. Turns on
+    /// --ground.
+    pub on_floor: bool,
+    /// Draw a shadow map from the light, so objects cast shadows, and add a cube on the ground to
+    /// show them off.
+    pub shadows: bool,
+    /// Open the window on this monitor (counting from 0) instead of wherever the OS puts it.
+    pub monitor: Option<usize>,
+    /// Go exclusive-fullscreen, on --monitor if given, otherwise the primary monitor.
+    pub fullscreen: bool,
+    /// Use this WGSL file instead of the built-in shader.wgsl. If it doesn't load, the built-in
+    /// one gets used anyway.
+    pub shader: Option<PathBuf>,
+    /// Also load every .wgsl file in this directory, as extra pipelines to cycle through.
+    pub shader_dir: Option<PathBuf>,
+    /// Values for `{{NAME}}` placeholders in the shaders, from `--define NAME=VALUE`, which can be
+    /// given more than once.
+    pub defines: HashMap<String, String>,
+    /// Log each scene shader's size and entry points, and validate it with naga before wgpu sees
+    /// it. Validation errors are fatal.
+    pub dump_shader_info: bool,
+    /// Start with the gradient background instead of the flat clear colour. B toggles it.
+    pub gradient: bool,
+    /// Colours for the gradient background, as sRGB hex like `ff8800`.
+    pub gradient_top: Option<[f32; 3]>,
+    pub gradient_bottom: Option<[f32; 3]>,
+    /// Start with the skybox showing instead of the background. Y toggles it.
+    pub skybox: bool,
+    /// Use the skybox faces in this directory, px.png, nx.png, py.png, ny.png, pz.png and nz.png,
+    /// instead of the built-in test faces. Turns on --skybox.
+    pub skybox_dir: Option<PathBuf>,
+    /// Keep the scene at this width/height ratio, with black bars filling the rest of the window.
+    /// Given as e.g. `16:9` or `1.78`.
+    pub aspect: Option<f32>,
+    /// Draw meshes with indirect draw calls, reading the draw parameters from a GPU buffer.
+    pub indirect: bool,
+    /// Draw the first mesh in this .gltf or .glb file instead of the pentagon, e.g.
+    /// `assets/cube.glb`.
+    pub gltf: Option<PathBuf>,
+    /// Use this surface format, e.g. `bgra8unorm-srgb`, instead of picking one. Has to be one the
+    /// surface supports.
+    pub format: Option<String>,
+    /// Run `update` at a fixed rate (see FIXED_TIMESTEP), however fast frames are rendered.
+    pub fixed_timestep: bool,
+    /// Render the scene at this fraction of the window's resolution, then stretch it to fit, e.g.
+    /// `0.5` for half resolution.
+    pub render_scale: Option<f32>,
+    /// Render the scene at exactly this many pixels, however big the window is. Given as e.g.
+    /// `320x240`. Wins over --render-scale.
+    pub internal_res: Option<(u32, u32)>,
+    /// How to stretch the scene to fit the window when it's rendered at a different resolution:
+    /// `nearest` for big sharp pixels, or `linear` (the default) to smooth them out.
+    pub upscale_filter: Option<wgpu::FilterMode>,
+    /// `aos` to interleave each vertex's attributes in one buffer (the default), or `soa` to give
+    /// each attribute its own buffer. Looks the same either way.
+    pub vertex_layout: Option<VertexLayout>,
+    /// Give the scene pass a second colour target, which the shaders write normals into.
+    pub normals_target: bool,
+    /// Store depth reversed, near = 1 and far = 0, for better precision far from the camera.
+    pub reverse_z: bool,
+    /// Save this many frames as numbered PNGs in this directory, given as e.g. `120:frames`.
+    pub record: Option<(u32, PathBuf)>,
+    /// Write every input event the demo handles to this file, with when it happened.
+    pub record_input: Option<PathBuf>,
+    /// Play back a file from --record-input at its original timing, ignoring live input until
+    /// it's finished.
+    pub replay_input: Option<PathBuf>,
+    /// Render this many frames as fast as possible, with vsync off, then quit and print how long
+    /// they took.
+    pub bench_frames: Option<u32>,
+    /// How quickly the camera catches up with where W/S/A/D put it (see `Camera::ease_towards`).
+    /// Higher is snappier, 0 turns smoothing off.
+    pub camera_damping: Option<f32>,
+    /// Draw the scene as points, lines or strips instead of a triangle list, e.g. `line-list`.
+    pub topology: Option<wgpu::PrimitiveTopology>,
+    /// Print everything the adapter and surface support, for bug reports, then quit.
+    pub print_caps: bool,
+    /// Cycle the per-frame uniforms (camera and light) through this many buffers, so a write never
+    /// lands in one the GPU might still be reading. 1, the default, is a single buffer each.
+    pub uniform_ring: Option<usize>,
+    /// Open a second window showing the same scene from another angle.
+    pub second_window: bool,
+    /// Clamp depth instead of clipping at the near and far planes, and add a strip of floor
+    /// reaching past the far plane to show it. U toggles it.
+    pub unclipped_depth: bool,
+    /// Only redraw when something happens (a key, a resize, ...) rather than continuously, while
+    /// nothing's moving. Saves a lot of CPU and GPU time, i.e. battery, on a still scene.
+    pub power_save: bool,
+    /// Which graphics APIs wgpu may use, e.g. `vulkan` or `gl,dx12`. All of them by default.
+    /// Also WGPU_DEMO_BACKEND.
+    pub backend: Option<wgpu::Backends>,
+    /// `low` to prefer an integrated GPU, or `high` to prefer a discrete one. Also
+    /// WGPU_DEMO_POWER.
+    pub power: Option<wgpu::PowerPreference>,
+    /// `on` to wait for the monitor's refresh, `off` to present as soon as a frame's ready. Left to
+    /// the surface by default. Also WGPU_DEMO_VSYNC.
+    pub vsync: Option<bool>,
+    /// The window's starting size in physical pixels, e.g. `1280x720`. Also WGPU_DEMO_SIZE.
+    pub size: Option<(u32, u32)>,
+    /// Also draw every vertex of the pentagon (or model) as a dot this many pixels across.
+    pub point_size: Option<f32>,
+    /// How many times bigger the loupe (F5) shows the pixels around the cursor.
+    pub loupe_zoom: Option<f32>,
+    /// How wide and tall the loupe's inset is, in pixels.
+    pub loupe_size: Option<u32>,
+    /// Orbit the camera around the scene by itself, for leaving it running unattended. Any key
+    /// press or mouse input pauses the orbit for a few seconds.
+    pub demo: bool,
+    /// Make the window see-through where nothing's drawn, so the desktop shows through the clear
+    /// colour. Needs a surface which can blend with the desktop, otherwise the window stays
+    /// opaque.
+    pub transparent: bool,
+    /// How opaque the clear colour is with --transparent, from 0 (invisible) to 1.
+    pub clear_alpha: Option<f64>,
+    /// Quit with an error, instead of warning and carrying on, if a flag is unknown or its value
+    /// is invalid.
+    pub strict: bool,
+    /// Every flag which was unknown or had an invalid value, and was ignored. `parse` turns these
+    /// into an error with --strict.
+    rejected: Vec<String>,
+    /// Turn off everything optional, and ask the GPU for as little as possible, to find out
+    /// whether a problem's in some feature or in the basics. See `apply_safe_mode`.
+    pub safe_mode: bool,
+}
+
+impl Args {
+    /// Parse the process's own arguments, then fill in whatever they didn't set from the
+    /// environment (see `fill_from_env`).
+    ///
+    /// With --strict, any flag that was rejected (see `parse_from`) is an error.
+    pub fn parse() -> Result<Self, AppError> {
+        // Skip the first arg, it's the binary's name.
+        let mut parsed = Self::parse_from(std::env::args().skip(1));
+        if parsed.strict && !parsed.rejected.is_empty() {
+            return Err(AppError::Args {
+                rejected: std::mem::take(&mut parsed.rejected),
+            });
+        }
+        parsed.fill_from_env(|name| std::env::var(name).ok());
+        if parsed.safe_mode {
+            // Last, so it wins over the environment too.
+            let ignored = parsed.apply_safe_mode();
+            log::warn!(
+                "SAFE MODE: all backends, low power, vsync, no MSAA, no HDR, no optional device \
+                 features, WebGL2 limits, and only the built-in shaders and textures"
+            );
+            log::warn!(
+                "SAFE MODE: keeping the depth buffer, in {:?}, because nothing draws in the right \
+                 order without it",
+                crate::texture::Texture::DEPTH_FORMAT
+            );
+            if !ignored.is_empty() {
+                log::warn!("SAFE MODE: ignoring {}", ignored.join(", "));
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Put every optional setting back to its most conservative value. Returns the flags which
+    /// were given, but now won't do anything. `State::new` checks `safe_mode` too, for the parts
+    /// which aren't flags: device features, limits and HDR.
+    ///
+    /// The depth buffer stays on, because nothing can be drawn in the right order without it, so
+    /// safe mode can't rule it out. Its format works everywhere, and the flags which change how
+    /// it's used (--reverse-z and --unclipped-depth) are turned off. `parse` logs that it's kept.
+    pub fn apply_safe_mode(&mut self) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+        let mut ignore = |flag, was_set: bool| {
+            if was_set {
+                ignored.push(flag);
+            }
+        };
+        ignore("--backend", self.backend.take().is_some());
+        let power = self.power.replace(wgpu::PowerPreference::LowPower);
+        ignore(
+            "--power",
+            power.is_some_and(|p| p != wgpu::PowerPreference::LowPower),
+        );
+        ignore("--vsync off", self.vsync.replace(true) == Some(false));
+        ignore("--msaa", self.msaa.take().is_some());
+        ignore("--frame-latency", self.frame_latency.take().is_some());
+        ignore("--particles", self.particles.take().is_some());
+        ignore("--shadows", std::mem::take(&mut self.shadows));
+        ignore("--indirect", std::mem::take(&mut self.indirect));
+        ignore(
+            "--unclipped-depth",
+            std::mem::take(&mut self.unclipped_depth),
+        );
+        ignore("--normals-target", std::mem::take(&mut self.normals_target));
+        ignore("--reverse-z", std::mem::take(&mut self.reverse_z));
+        ignore("--render-scale", self.render_scale.take().is_some());
+        ignore("--internal-res", self.internal_res.take().is_some());
+        ignore("--fullscreen", std::mem::take(&mut self.fullscreen));
+        ignore("--transparent", std::mem::take(&mut self.transparent));
+        ignore("--second-window", std::mem::take(&mut self.second_window));
+        ignore("--point-size", self.point_size.take().is_some());
+        ignore("--uniform-ring", self.uniform_ring.take().is_some());
+        ignore("--format", self.format.take().is_some());
+        ignore("--topology", self.topology.take().is_some());
+        ignore("--vertex-layout", self.vertex_layout.take().is_some());
+        ignore("--shader", self.shader.take().is_some());
+        ignore("--shader-dir", self.shader_dir.take().is_some());
+        ignore("--define", !std::mem::take(&mut self.defines).is_empty());
+        ignore("--gltf", self.gltf.take().is_some());
+        ignore("--skybox-dir", self.skybox_dir.take().is_some());
+        ignored
+    }
+
+    /// Some settings can also come from environment variables, for CI and shell aliases. Which
+    /// one wins, highest first:
+    ///
+    /// 1. The command line.
+    /// 2. The environment variable.
+    /// 3. The default, i.e. leaving it unset.
+    ///
+    /// There's no config file yet. If one turns up, it goes between 2 and 3.
+    ///
+    /// So this only fills in settings which are still unset after the command line. An invalid
+    /// value is logged and ignored, the same as on the command line, so the default gets used.
+    /// `var` looks up a variable, like `std::env::var(name).ok()`.
+    pub fn fill_from_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        self.backend = self
+            .backend
+            .or_else(|| env_value(&var, "WGPU_DEMO_BACKEND", backends));
+        self.power = self
+            .power
+            .or_else(|| env_value(&var, "WGPU_DEMO_POWER", power));
+        self.vsync = self
+            .vsync
+            .or_else(|| env_value(&var, "WGPU_DEMO_VSYNC", on_off));
+        self.size = self
+            .size
+            .or_else(|| env_value(&var, "WGPU_DEMO_SIZE", resolution));
+    }
+
+    /// Unknown flags and invalid values are logged and ignored, and listed in `rejected`.
+    pub fn parse_from(args: impl IntoIterator<Item = String>) -> Self {
+        let mut parsed = Self::default();
+        let mut args = Flags {
+            args: args.into_iter(),
+            rejected: Vec::new(),
+        };
+        while let Some(arg) = args.args.next() {
+            match arg.as_str() {
+                "--no-spin" => parsed.no_spin = true,
+                "--no-cull" => parsed.no_cull = true,
+                "--frame-latency" => {
+                    parsed.frame_latency = args.value(&arg, value).map(clamp_frame_latency)
+                }
+                "--particles" => parsed.particles = args.value(&arg, value),
+                "--msaa" => parsed.msaa = args.value(&arg, sample_count),
+                "--force-fallback" => parsed.force_fallback = true,
+                "--scatter" => parsed.scatter = args.value(&arg, value),
+                "--seed" => parsed.seed = args.value(&arg, value),
+                "--tiling" => parsed.tiling = true,
+                "--vertex-colors" => parsed.vertex_colors = true,
+                "--transparent-quads" => parsed.transparent_quads = true,
+                "--shadows" => parsed.shadows = true,
+                "--ground" => parsed.ground = true,
+                "--on-floor" => {
+                    parsed.on_floor = true;
+                    parsed.ground = true;
+                }
+                "--monitor" => parsed.monitor = args.value(&arg, value),
+                "--fullscreen" => parsed.fullscreen = true,
+                "--dump-shader-info" => parsed.dump_shader_info = true,
+                "--define" => parsed.defines.extend(args.value(&arg, define)),
+                "--gradient" => parsed.gradient = true,
+                "--gradient-top" => parsed.gradient_top = args.value(&arg, hex_color),
+                "--gradient-bottom" => parsed.gradient_bottom = args.value(&arg, hex_color),
+                "--skybox" => parsed.skybox = true,
+                "--skybox-dir" => {
+                    parsed.skybox_dir = args.value(&arg, value);
+                    parsed.skybox |= parsed.skybox_dir.is_some();
+                }
+                "--indirect" => parsed.indirect = true,
+                "--aspect" => parsed.aspect = args.value(&arg, aspect_ratio),
+                "--shader" => parsed.shader = args.value(&arg, value),
+                "--shader-dir" => parsed.shader_dir = args.value(&arg, value),
+                "--gltf" => parsed.gltf = args.value(&arg, value),
+                "--format" => parsed.format = args.value(&arg, value),
+                "--fixed-timestep" => parsed.fixed_timestep = true,
+                "--render-scale" => parsed.render_scale = args.value(&arg, render_scale),
+                "--internal-res" => parsed.internal_res = args.value(&arg, resolution),
+                "--upscale-filter" => parsed.upscale_filter = args.value(&arg, filter_mode),
+                "--vertex-layout" => parsed.vertex_layout = args.value(&arg, value),
+                "--normals-target" => parsed.normals_target = true,
+                "--reverse-z" => parsed.reverse_z = true,
+                "--record" => parsed.record = args.value(&arg, record),
+                "--record-input" => parsed.record_input = args.value(&arg, value),
+                "--replay-input" => parsed.replay_input = args.value(&arg, value),
+                "--bench-frames" => parsed.bench_frames = args.value(&arg, value),
+                "--camera-damping" => parsed.camera_damping = args.value(&arg, value),
+                "--uniform-ring" => parsed.uniform_ring = args.value(&arg, uniform_ring),
+                "--unclipped-depth" => parsed.unclipped_depth = true,
+                "--second-window" => parsed.second_window = true,
+                "--power-save" => parsed.power_save = true,
+                "--print-caps" => parsed.print_caps = true,
+                "--topology" => parsed.topology = args.value(&arg, topology),
+                "--backend" => parsed.backend = args.value(&arg, backends),
+                "--power" => parsed.power = args.value(&arg, power),
+                "--vsync" => parsed.vsync = args.value(&arg, on_off),
+                "--size" => parsed.size = args.value(&arg, resolution),
+                "--point-size" => parsed.point_size = args.value(&arg, point_size),
+                "--loupe-zoom" => parsed.loupe_zoom = args.value(&arg, loupe_zoom),
+                "--loupe-size" => parsed.loupe_size = args.value(&arg, value),
+                "--demo" => parsed.demo = true,
+                "--transparent" => parsed.transparent = true,
+                "--clear-alpha" => parsed.clear_alpha = args.value(&arg, clear_alpha),
+                "--safe-mode" => parsed.safe_mode = true,
+                "--strict" => parsed.strict = true,
+                other => {
+                    log::warn!("Ignoring unknown argument {other}");
+                    args.rejected.push(other.to_owned());
+                }
+            }
+        }
+        parsed.rejected = args.rejected;
+        parsed
+    }
+}
+
+/// The arguments still to be parsed, and which flags have been rejected so far.
+struct Flags<I> {
+    args: I,
+    rejected: Vec<String>,
+}
+
+impl<I: Iterator<Item = String>> Flags<I> {
+    /// Parse the value after `flag` with one of the flag parsers, e.g. `value` or `resolution`,
+    /// remembering the flag if it gets rejected.
+    fn value<T>(&mut self, flag: &str, parse: fn(&str, Option<String>) -> Option<T>) -> Option<T> {
+        let parsed = parse(flag, self.args.next());
+        if parsed.is_none() {
+            self.rejected.push(flag.to_owned());
+        }
+        parsed
+    }
+}
+
+/// Parse the value that follows a flag, e.g. the `2` in `--frame-latency 2`.
+/// Bad values are logged and ignored, so the default gets used instead.
+fn value<T: FromStr>(flag: &str, value: Option<String>) -> Option<T> {
+    let Some(value) = value else {
+        log::warn!("{flag} needs a value, ignoring it");
+        return None;
+    };
+    match value.parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            log::warn!("Ignoring invalid value {value:?} for {flag}");
+            None
+        }
+    }
+}
+
+/// Parse environment variable `name` with one of the flag parsers, e.g. `resolution`. None if
+/// it isn't set.
+fn env_value<T>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    parse: fn(&str, Option<String>) -> Option<T>,
+) -> Option<T> {
+    parse(name, Some(var(name)?))
+}
+
+/// Like `value`, but for a shader constant, like `MAX_LIGHTS=4`.
+fn define(flag: &str, define: Option<String>) -> Option<(String, String)> {
+    let define: String = value(flag, define)?;
+    match define.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Some((name.trim().to_owned(), value.trim().to_owned()))
+        }
+        _ => {
+            log::warn!("Ignoring {flag} {define:?}, it looks like MAX_LIGHTS=4");
+            None
+        }
+    }
+}
+
+/// Like `value`, but for a hex colour.
+fn hex_color(flag: &str, hex: Option<String>) -> Option<[f32; 3]> {
+    let hex: String = value(flag, hex)?;
+    let color = crate::color::parse_hex(&hex);
+    if color.is_none() {
+        log::warn!("Ignoring {flag} {hex:?}, colours look like ff8800");
+    }
+    color
+}
+
+/// Like `value`, but for an aspect ratio, either `16:9` or `1.78`.
+fn aspect_ratio(flag: &str, ratio: Option<String>) -> Option<f32> {
+    let ratio: String = value(flag, ratio)?;
+    let parsed = match ratio.split_once(':') {
+        Some((w, h)) => w
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .zip(h.trim().parse::<f32>().ok())
+            .map(|(w, h)| w / h),
+        None => ratio.trim().parse().ok(),
+    };
+    match parsed {
+        Some(aspect) if aspect.is_finite() && aspect > 0.0 => Some(aspect),
+        _ => {
+            log::warn!("Ignoring {flag} {ratio:?}, aspect ratios look like 16:9 or 1.78");
+            None
+        }
+    }
+}
+
+/// Like `value`, but for an MSAA sample count: 1, 2, 4 or 8.
+fn sample_count(flag: &str, count: Option<String>) -> Option<u32> {
+    let count: u32 = value(flag, count)?;
+    if [1, 2, 4, 8].contains(&count) {
+        Some(count)
+    } else {
+        log::warn!("Ignoring {flag} {count}, it has to be 1, 2, 4 or 8");
+        None
+    }
+}
+
+/// Like `value`, but there has to be at least one buffer.
+fn uniform_ring(flag: &str, ring: Option<String>) -> Option<usize> {
+    let ring: usize = value(flag, ring)?;
+    if ring == 0 {
+        log::warn!("Ignoring {flag} 0, it needs at least one buffer");
+        return None;
+    }
+    Some(ring)
+}
+
+/// Like `value`, but the scale has to be positive.
+fn render_scale(flag: &str, scale: Option<String>) -> Option<f32> {
+    let scale: f32 = value(flag, scale)?;
+    if scale.is_finite() && scale > 0.0 {
+        Some(scale)
+    } else {
+        log::warn!("Ignoring {flag} {scale}, it has to be more than 0");
+        None
+    }
+}
+
+/// Like `value`, but the zoom can't be less than 1, which would shrink things instead.
+fn loupe_zoom(flag: &str, zoom: Option<String>) -> Option<f32> {
+    let zoom: f32 = value(flag, zoom)?;
+    if zoom.is_finite() && zoom >= 1.0 {
+        Some(zoom)
+    } else {
+        log::warn!("Ignoring {flag} {zoom}, it has to be at least 1");
+        None
+    }
+}
+
+/// Like `value`, but alpha goes from 0 to 1.
+fn clear_alpha(flag: &str, alpha: Option<String>) -> Option<f64> {
+    let alpha: f64 = value(flag, alpha)?;
+    if (0.0..=1.0).contains(&alpha) {
+        Some(alpha)
+    } else {
+        log::warn!("Ignoring {flag} {alpha}, it has to be between 0 and 1");
+        None
+    }
+}
+
+/// Like `value`, but the size has to be positive.
+fn point_size(flag: &str, size: Option<String>) -> Option<f32> {
+    let size: f32 = value(flag, size)?;
+    if size.is_finite() && size > 0.0 {
+        Some(size)
+    } else {
+        log::warn!("Ignoring {flag} {size}, it has to be more than 0");
+        None
+    }
+}
+
+/// Like `value`, but for a resolution like `320x240`.
+fn resolution(flag: &str, res: Option<String>) -> Option<(u32, u32)> {
+    let res: String = value(flag, res)?;
+    let parsed = res.split_once(['x', 'X']).and_then(|(w, h)| {
+        w.trim()
+            .parse::<u32>()
+            .ok()
+            .zip(h.trim().parse::<u32>().ok())
+    });
+    match parsed {
+        Some((w, h)) if w > 0 && h > 0 => Some((w, h)),
+        _ => {
+            log::warn!("Ignoring {flag} {res:?}, resolutions look like 320x240");
+            None
+        }
+    }
+}
+
+/// Like `value`, but for a texture filter, `nearest` or `linear`.
+fn filter_mode(flag: &str, filter: Option<String>) -> Option<wgpu::FilterMode> {
+    let filter: String = value(flag, filter)?;
+    match filter.to_lowercase().as_str() {
+        "nearest" => Some(wgpu::FilterMode::Nearest),
+        "linear" => Some(wgpu::FilterMode::Linear),
+        _ => {
+            log::warn!("Ignoring {flag} {filter:?}, it has to be nearest or linear");
+            None
+        }
+    }
+}
+
+/// Like `value`, but for a primitive topology, like `line-strip`.
+fn topology(flag: &str, topology: Option<String>) -> Option<wgpu::PrimitiveTopology> {
+    let topology: String = value(flag, topology)?;
+    match topology.to_lowercase().as_str() {
+        "point-list" => Some(wgpu::PrimitiveTopology::PointList),
+        "line-list" => Some(wgpu::PrimitiveTopology::LineList),
+        "line-strip" => Some(wgpu::PrimitiveTopology::LineStrip),
+        "triangle-list" => Some(wgpu::PrimitiveTopology::TriangleList),
+        "triangle-strip" => Some(wgpu::PrimitiveTopology::TriangleStrip),
+        _ => {
+            log::warn!(
+                "Ignoring {flag} {topology:?}, it has to be point-list, line-list, line-strip, \
+                 triangle-list or triangle-strip"
+            );
+            None
+        }
+    }
+}
+
+/// Like `value`, but for a comma-separated list of backends, like `vulkan,gl`.
+fn backends(flag: &str, list: Option<String>) -> Option<wgpu::Backends> {
+    let list: String = value(flag, list)?;
+    let backends = wgpu::util::parse_backends_from_comma_list(&list);
+    if backends.is_empty() {
+        log::warn!(
+            "Ignoring {flag} {list:?}, it has to be one or more of vulkan, metal, dx12, dx11, gl \
+             and webgpu, separated by commas"
+        );
+        return None;
+    }
+    Some(backends)
+}
+
+/// Like `value`, but for a power preference, `low` or `high`.
+fn power(flag: &str, preference: Option<String>) -> Option<wgpu::PowerPreference> {
+    let preference: String = value(flag, preference)?;
+    match preference.to_lowercase().as_str() {
+        "low" | "low-power" => Some(wgpu::PowerPreference::LowPower),
+        "high" | "high-performance" => Some(wgpu::PowerPreference::HighPerformance),
+        _ => {
+            log::warn!("Ignoring {flag} {preference:?}, it has to be low or high");
+            None
+        }
+    }
+}
+
+/// Like `value`, but `on` or `off`.
+fn on_off(flag: &str, setting: Option<String>) -> Option<bool> {
+    let setting: String = value(flag, setting)?;
+    match setting.to_lowercase().as_str() {
+        "on" | "true" | "1" => Some(true),
+        "off" | "false" | "0" => Some(false),
+        _ => {
+            log::warn!("Ignoring {flag} {setting:?}, it has to be on or off");
+            None
+        }
+    }
+}
+
+/// Like `value`, but for a frame count and a directory, like `120:frames`.
+fn record(flag: &str, spec: Option<String>) -> Option<(u32, PathBuf)> {
+    let spec: String = value(flag, spec)?;
+    let parsed = spec
+        .split_once(':')
+        .and_then(|(n, dir)| Some((n.trim().parse::<u32>().ok()?, dir)))
+        .filter(|(n, dir)| *n > 0 && !dir.is_empty());
+    match parsed {
+        Some((n, dir)) => Some((n, PathBuf::from(dir))),
+        None => {
+            log::warn!("Ignoring {flag} {spec:?}, it looks like 120:frames");
+            None
+        }
+    }
+}
+
+/// Fewer than 1 frame in flight is impossible, and more than 3 just adds latency.
+fn clamp_frame_latency(n: u32) -> u32 {
+    let clamped = n.clamp(1, 3);
+    if clamped != n {
+        log::warn!("--frame-latency must be between 1 and 3, using {clamped}");
+    }
+    clamped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        Args::parse_from(args.iter().map(|&arg| arg.to_owned()))
+    }
+
+    #[test]
+    fn bad_values_and_unknown_flags_are_rejected() {
+        let args = parse(&["--msaa", "16", "--bogus", "--size", "640x480", "--power"]);
+        assert_eq!(args.msaa, None);
+        assert_eq!(args.size, Some((640, 480)));
+        // --power is missing its value.
+        assert_eq!(args.rejected, ["--msaa", "--bogus", "--power"]);
+    }
+
+    #[test]
+    fn good_values_are_not_rejected() {
+        let args = parse(&["--msaa", "4", "--format", "bgra8unorm-srgb", "--strict"]);
+        assert_eq!(args.msaa, Some(4));
+        assert_eq!(args.format.as_deref(), Some("bgra8unorm-srgb"));
+        assert!(args.strict);
+        assert!(args.rejected.is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+/// Where wgpu's backends behave differently enough that the demo has to do something about it.
+///
+/// wgpu already hides the differences tutorials usually warn about: depth always goes from 0 to
+/// 1, clip space Y always points up, and texture coordinates always start at the top left, on
+/// every backend (naga rewrites the shaders for GL's -1 to 1 depth, for example). Clear colours are
+/// in the target's own space everywhere, so the gamma difference is the surface format's, not the
+/// backend's (see `surface_is_srgb`). What's left is here, so the checks don't end up scattered
+/// around `State::new`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// Frames can be copied off the surface texture, which --record needs. GL can't, because its
+    /// surface texture is really a renderbuffer, which wgpu blits to the window when presenting.
+    pub surface_readback: bool,
+    /// What `Device::start_capture` captures with, if anything.
+    pub frame_capture: Option<FrameCapture>,
+}
+
+/// The tool F12 captures a frame with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameCapture {
+    /// Only if the demo was started from RenderDoc (see `crate::renderdoc`).
+    RenderDoc,
+    /// Metal's capture manager, which saves a trace Xcode can open. Only when running from Xcode,
+    /// or with METAL_CAPTURE_ENABLED=1.
+    Xcode,
+}
+
+impl Quirks {
+    /// How a backend without any quirks behaves.
+    pub const NONE: Self = Self {
+        surface_readback: true,
+        frame_capture: Some(FrameCapture::RenderDoc),
+    };
+
+    /// GL's surface can't be copied from.
+    const GL: Self = Self {
+        surface_readback: false,
+        ..Self::NONE
+    };
+
+    /// Metal captures through its own capture manager rather than RenderDoc.
+    const METAL: Self = Self {
+        frame_capture: Some(FrameCapture::Xcode),
+        ..Self::NONE
+    };
+
+    /// wgpu's DX11 backend is unfinished, and panics on `start_capture`.
+    const DX11: Self = Self {
+        frame_capture: None,
+        ..Self::NONE
+    };
+
+    /// The browser owns the GPU, so there's nothing to capture with from inside the page.
+    const BROWSER_WEBGPU: Self = Self {
+        frame_capture: None,
+        ..Self::NONE
+    };
+
+    pub fn for_backend(backend: wgpu::Backend) -> Self {
+        // No wildcard, so a new backend in wgpu has to be thought about here.
+        match backend {
+            wgpu::Backend::Empty | wgpu::Backend::Vulkan | wgpu::Backend::Dx12 => Self::NONE,
+            wgpu::Backend::Metal => Self::METAL,
+            wgpu::Backend::Dx11 => Self::DX11,
+            wgpu::Backend::Gl => Self::GL,
+            wgpu::Backend::BrowserWebGpu => Self::BROWSER_WEBGPU,
+        }
+    }
+
+    /// Log how this backend differs from `NONE`, if it does.
+    pub fn log(&self, backend: wgpu::Backend) {
+        if !self.surface_readback {
+            log::info!("{backend:?} quirk: frames can't be copied off the surface");
+        }
+        if self.frame_capture != Self::NONE.frame_capture {
+            match self.frame_capture {
+                Some(tool) => log::info!("{backend:?} quirk: F12 captures with {tool:?}"),
+                None => log::info!("{backend:?} quirk: F12 can't capture frames"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quirks_for_each_backend() {
+        use wgpu::Backend;
+        let expected = [
+            (Backend::Empty, true, Some(FrameCapture::RenderDoc)),
+            (Backend::Vulkan, true, Some(FrameCapture::RenderDoc)),
+            (Backend::Dx12, true, Some(FrameCapture::RenderDoc)),
+            (Backend::Metal, true, Some(FrameCapture::Xcode)),
+            (Backend::Dx11, true, None),
+            (Backend::Gl, false, Some(FrameCapture::RenderDoc)),
+            (Backend::BrowserWebGpu, true, None),
+        ];
+        for (backend, surface_readback, frame_capture) in expected {
+            assert_eq!(
+                Quirks::for_backend(backend),
+                Quirks {
+                    surface_readback,
+                    frame_capture,
+                },
+                "{backend:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::uniform::Uniform;
+
+/// Corresponds to GradientUniform in background.wgsl. Colours are linear RGBA.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientUniform {
+    top: [f32; 4],
+    bottom: [f32; 4],
+}
+
+/// A vertical gradient drawn behind the scene, as a fullscreen triangle. Drawn first in the scene
+/// pass, so everything else covers it.
+pub struct Background {
+    pipeline: wgpu::RenderPipeline,
+    gradient: Uniform<GradientUniform>,
+    /// Top and bottom are the same colour, for filling just part of the target with the clear
+    /// colour (the pass clear always fills all of it).
+    flat: Uniform<GradientUniform>,
+    /// If false, nothing is drawn, and the pass's clear colour shows instead.
+    pub enabled: bool,
+}
+
+impl Background {
+    /// `top` and `bottom` are linear RGB. The pipeline is drawn in the scene pass, so it has to
+    /// match the scene's format, sample count and targets.
+    pub fn new(
+        device: &wgpu::Device,
+        top: [f32; 3],
+        bottom: [f32; 3],
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+        normals_target: bool,
+    ) -> Self {
+        let [tr, tg, tb] = top;
+        let [br, bg, bb] = bottom;
+        let gradient = Uniform::new(
+            device,
+            GradientUniform {
+                top: [tr, tg, tb, 1.0],
+                bottom: [br, bg, bb, 1.0],
+            },
+            wgpu::ShaderStages::FRAGMENT,
+            "gradient_uniform",
+        );
+        let flat = Uniform::new(
+            device,
+            GradientUniform {
+                top: [0.0; 4],
+                bottom: [0.0; 4],
+            },
+            wgpu::ShaderStages::FRAGMENT,
+            "flat_background_uniform",
+        );
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Background Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("background.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Background Pipeline Layout"),
+            bind_group_layouts: &[&gradient.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Background Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &crate::normals::scene_targets(
+                    target_format,
+                    wgpu::BlendState::REPLACE,
+                    normals_target,
+                ),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // Behind everything, so it mustn't write depth, or nothing could be drawn over it.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+        Self {
+            pipeline,
+            gradient,
+            flat,
+            enabled: false,
+        }
+    }
+
+    /// The colour `draw_flat` uses when the gradient is disabled.
+    pub fn set_flat_color(&mut self, queue: &wgpu::Queue, color: wgpu::Color) {
+        let rgba = [color.r, color.g, color.b, color.a].map(|c| c as f32);
+        self.flat.set(GradientUniform {
+            top: rgba,
+            bottom: rgba,
+        });
+        self.flat.flush(queue);
+    }
+
+    /// Draw the gradient, if it's enabled. Call this before drawing anything else in the pass.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.enabled {
+            self.draw_with(render_pass, &self.gradient);
+        }
+    }
+
+    /// Like `draw`, but when the gradient is disabled, fills the viewport with the flat colour.
+    /// Useful when the viewport doesn't cover the whole target, e.g. letterboxing.
+    pub fn draw_or_fill<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let uniform = if self.enabled {
+            &self.gradient
+        } else {
+            &self.flat
+        };
+        self.draw_with(render_pass, uniform);
+    }
+
+    fn draw_with<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        uniform: &'a Uniform<GradientUniform>,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, uniform.bind_group(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,41 @@
+// A vertical gradient, drawn behind the scene instead of a flat clear colour.
+
+// Corresponds to GradientUniform in the Rust code.
+struct GradientUniform {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> gradient: GradientUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // 0 at the bottom of the screen, 1 at the top.
+    @location(0) height: f32,
+};
+
+// Same trick as tonemap.wgsl: one big triangle covering the whole screen, no vertex buffer.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    // z = 1 is as far away as possible, so it's behind everything anyway.
+    out.clip_position = vec4<f32>(xy * 2.0 - 1.0, 1.0, 1.0);
+    out.height = xy.y;
+    return out;
+}
+
+// The normal is for --normals-target. The background isn't a surface, so it has no normal.
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = mix(gradient.bottom, gradient.top, clamp(in.height, 0.0, 1.0));
+    // Same as the normals target's clear value, i.e. a zero normal.
+    out.normal = vec4<f32>(0.5, 0.5, 0.5, 0.0);
+    return out;
+}
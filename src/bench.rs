@@ -0,0 +1,86 @@
+use std::{fmt, time::Duration};
+
+/// How many bars the histogram has.
+const HISTOGRAM_BUCKETS: usize = 10;
+
+/// How many characters wide the longest bar is.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Times a fixed number of frames for --bench-frames, then summarises them. Its `Display` is the
+/// report printed on exit.
+pub struct FrameTimes {
+    /// How many frames to time in total.
+    frames: usize,
+    /// The CPU time each frame took, in the order they ran. Allocated up front, so recording a
+    /// frame never allocates.
+    times: Vec<Duration>,
+}
+
+impl FrameTimes {
+    pub fn new(frames: u32) -> Self {
+        let frames = frames as usize;
+        Self {
+            frames,
+            times: Vec::with_capacity(frames),
+        }
+    }
+
+    /// Add one frame's time. Frames after the last one are ignored.
+    pub fn record(&mut self, time: Duration) {
+        if !self.is_done() {
+            self.times.push(time);
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.times.len() >= self.frames
+    }
+}
+
+impl fmt::Display for FrameTimes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sorted = self.times.clone();
+        sorted.sort();
+        let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+            return writeln!(f, "No frames were timed");
+        };
+        // Nearest-rank percentile: the smallest time at least p% of frames were no slower than.
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+
+        writeln!(f, "Timed {} of {} frame(s)", sorted.len(), self.frames)?;
+        writeln!(f, "min {:8.3} ms", ms(min))?;
+        for p in [50.0, 95.0, 99.0] {
+            writeln!(f, "p{p:<2} {:8.3} ms", ms(percentile(p)))?;
+        }
+        writeln!(f, "max {:8.3} ms", ms(max))?;
+
+        // Equal-width buckets from the fastest frame to the slowest. A hitch shows up as a lonely
+        // bar at the bottom.
+        let bucket_width = (max - min).as_secs_f64() / HISTOGRAM_BUCKETS as f64;
+        let mut counts = [0usize; HISTOGRAM_BUCKETS];
+        for &time in &sorted {
+            let bucket = if bucket_width > 0.0 {
+                ((time - min).as_secs_f64() / bucket_width) as usize
+            } else {
+                0
+            };
+            counts[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+        }
+        let most = counts.iter().copied().max().unwrap_or(1);
+        for (i, &count) in counts.iter().enumerate() {
+            let from = ms(min) + i as f64 * bucket_width * 1000.0;
+            // Round up, so a bucket with any frames in it always gets at least one character.
+            let bar = (count * HISTOGRAM_WIDTH).div_ceil(most);
+            writeln!(
+                f,
+                "{from:8.3} ms | {:<HISTOGRAM_WIDTH$} {count}",
+                "#".repeat(bar)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+use crate::loupe::LoupeRegion;
+
+/// An offscreen texture the scene renders into, which then gets stretched over the surface.
+/// Lets the scene render at a different resolution to the window (see --render-scale and
+/// --internal-res). `HdrPipeline` does the same job, plus tonemapping, when HDR is supported.
+pub struct BlitPipeline {
+    view: wgpu::TextureView,
+    format: wgpu::TextureFormat,
+    sampler: wgpu::Sampler,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    /// The same texture with a Nearest sampler, for the loupe.
+    loupe_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl BlitPipeline {
+    /// `format` is used for both the offscreen texture and the surface, so nothing gets converted.
+    /// `filter` decides how the texture gets upscaled (or downscaled) to fit the surface: Nearest
+    /// keeps the pixels sharp and blocky, Linear blurs them together.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        filter: wgpu::FilterMode,
+    ) -> Self {
+        let view = create_target_view(device, "blit_texture", format, width, height);
+        let sampler = create_sampler(device, filter, "blit_sampler");
+        let layout = create_bind_group_layout(device, "blit_bind_group_layout");
+        let bind_group = create_bind_group(device, &layout, &view, &sampler, "blit_bind_group");
+        let loupe_bind_group = create_loupe_bind_group(device, &layout, &view, "blit");
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Blit Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("blit.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            view,
+            format,
+            sampler,
+            layout,
+            bind_group,
+            loupe_bind_group,
+            pipeline,
+        }
+    }
+
+    /// Only needed when the render resolution changes, not every time the window does.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.view = create_target_view(device, "blit_texture", self.format, width, height);
+        self.bind_group = create_bind_group(
+            device,
+            &self.layout,
+            &self.view,
+            &self.sampler,
+            "blit_bind_group",
+        );
+        self.loupe_bind_group = create_loupe_bind_group(device, &self.layout, &self.view, "blit");
+    }
+
+    /// Where the scene should render to.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Stretch the offscreen texture over `output`, which is usually the surface.
+    pub fn process(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        self.draw(encoder, output, &self.bind_group, None);
+    }
+
+    /// Draw the loupe's inset onto `output`, over what `process` drew. See `Loupe`.
+    pub fn process_loupe(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        region: &LoupeRegion,
+    ) {
+        self.draw(encoder, output, &self.loupe_bind_group, Some(region));
+    }
+
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        bind_group: &wgpu::BindGroup,
+        region: Option<&LoupeRegion>,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // The fullscreen triangle covers every pixel.
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        if let Some(region) = region {
+            set_loupe_region(&mut pass, region);
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// Confine a fullscreen pass to the loupe's inset, and magnify it.
+pub fn set_loupe_region(pass: &mut wgpu::RenderPass, region: &LoupeRegion) {
+    let [x, y, width, height] = region.viewport;
+    pass.set_viewport(x, y, width, height, 0.0, 1.0);
+    let [x, y, width, height] = region.scissor;
+    pass.set_scissor_rect(x, y, width, height);
+}
+
+/// A texture the scene can render into, and a fullscreen pass can then sample from.
+pub fn create_target_view(
+    device: &wgpu::Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        // The scene renders into it, then the fullscreen pass samples from it.
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// If the target is the same size as the surface, the filter makes no difference. Otherwise it
+/// picks between blocky (Nearest) and smooth (Linear) scaling.
+pub fn create_sampler(
+    device: &wgpu::Device,
+    filter: wgpu::FilterMode,
+    label: &str,
+) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some(label),
+        mag_filter: filter,
+        min_filter: filter,
+        ..Default::default()
+    })
+}
+
+/// A texture at binding 0 and its sampler at binding 1, for the fullscreen fragment shader.
+pub fn create_bind_group_layout(device: &wgpu::Device, label: &str) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+        label: Some(label),
+    })
+}
+
+/// Like `create_bind_group`, but with its own Nearest sampler, so the loupe shows each texel as a
+/// square rather than blurring them together.
+pub fn create_loupe_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+    prefix: &str,
+) -> wgpu::BindGroup {
+    let sampler = create_sampler(
+        device,
+        wgpu::FilterMode::Nearest,
+        &format!("{prefix}_loupe_sampler"),
+    );
+    create_bind_group(
+        device,
+        layout,
+        view,
+        &sampler,
+        &format!("{prefix}_loupe_bind_group"),
+    )
+}
+
+pub fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+    label: &str,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+        label: Some(label),
+    })
+}
@@ -0,0 +1,29 @@
+// Fullscreen copy pass: stretches the offscreen scene texture over the whole surface. Used when
+// the scene renders at a different resolution to the window, but there's no HDR texture to
+// tonemap from.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Same trick as tonemap.wgsl: one big triangle covering the whole screen, no vertex buffer.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(xy * 2.0 - 1.0, 0.0, 1.0);
+    // Texture coordinates have y pointing down, but clip space has y pointing up.
+    out.uv = vec2<f32>(xy.x, 1.0 - xy.y);
+    return out;
+}
+
+@group(0) @binding(0)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(1)
+var s_scene: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_scene, s_scene, in.uv);
+}
@@ -0,0 +1,24 @@
+// Bounding boxes: each object's box outlined in yellow, moving and spinning with the object. The
+// box is in the mesh's own coordinates, so it goes through the same transforms as shader.wgsl.
+
+#include "common.wgsl"
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let instance_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    return camera.view_proj * instance_matrix * model_uniform.transform * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(1.0, 1.0, 0.0, 1.0);
+    // Lines have no surface, so like the grid they get a zero normal.
+    out.normal = vec4<f32>(0.5, 0.5, 0.5, 0.0);
+    return out;
+}
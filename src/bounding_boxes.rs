@@ -0,0 +1,107 @@
+use crate::{
+    scatter::InstanceRaw,
+    scene::{ModelUniforms, SceneObject},
+    texture::Texture,
+};
+
+/// Outlines each object's bounding box, for checking where things are and how big they are. F3
+/// toggles it.
+///
+/// The boxes are worked out once, when each object is created (see
+/// `SceneObject::draw_bounding_box`), in the mesh's own coordinates. They're drawn with the
+/// object's transform and instances, so they move and spin along with it.
+pub struct BoundingBoxes {
+    pub enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl BoundingBoxes {
+    /// Like the grid, the boxes are drawn in the scene pass with the scene's pipeline layout, so
+    /// they have to match the scene's format, sample count and targets, and test depth the same
+    /// way.
+    pub fn new(
+        device: &wgpu::Device,
+        scene_layout: &wgpu::PipelineLayout,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+        normals_target: bool,
+        depth_compare: wgpu::CompareFunction,
+    ) -> Self {
+        let source = crate::shader::resolve_includes(
+            "bounding_box.wgsl",
+            include_str!("bounding_box.wgsl"),
+            None,
+        )
+        .expect("bounding_box.wgsl only includes built-in files");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Bounding Box Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Bounding Box Pipeline"),
+            layout: Some(scene_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                    },
+                    InstanceRaw::descriptor(),
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &crate::normals::scene_targets(
+                    target_format,
+                    wgpu::BlendState::REPLACE,
+                    normals_target,
+                ),
+            }),
+            primitive: wgpu::PrimitiveState {
+                // Every two vertices make a separate line.
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            // Hidden behind whatever's in front, but doesn't hide anything itself, so a box
+            // doesn't cover up the object inside it.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+        Self {
+            enabled: false,
+            pipeline,
+        }
+    }
+
+    /// Draw the boxes around `objects`, if they're enabled. Like `Grid::draw`, expects the scene's
+    /// bind groups to be set already. Leaves a different model bound than it found.
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        objects: impl IntoIterator<Item = &'a SceneObject>,
+        models: &'a ModelUniforms,
+        model_group: u32,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        for object in objects {
+            object.draw_bounding_box(render_pass, models, model_group);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::{collections::HashSet, time::Duration};
+
+use cgmath::{
+    Angle, Deg, InnerSpace, Matrix4, Point3, Quaternion, Rad, Rotation3, SquareMatrix, Vector3,
+};
+use winit::event::VirtualKeyCode;
+
+use crate::mesh::BoundingBox;
+
+/// cgmath's projection matrices are made for OpenGL, whose clip space has z from -1 to 1.
+/// wgpu's has z from 0 to 1, so squash and shift z to fit.
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+/// Flips clip space z, so the near plane ends up at depth 1 and the far plane at 0 (see
+/// `Camera::reverse_z`).
+#[rustfmt::skip]
+const REVERSE_Z_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0, 1.0, 1.0,
+);
+
+/// Narrower than this is like looking through a telescope, and any wider looks like a fisheye lens.
+const MIN_FOVY: Deg<f32> = Deg(10.0);
+const MAX_FOVY: Deg<f32> = Deg(120.0);
+
+/// Where we're looking from, and what we're looking at.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    /// Which way is up, so the camera knows how to roll.
+    pub up: Vector3<f32>,
+    /// Width / height of the window, so things don't get stretched.
+    pub aspect: f32,
+    /// How much of the scene fits on screen vertically.
+    pub fovy: Deg<f32>,
+    /// Anything closer than this, or further than zfar, gets clipped.
+    pub znear: f32,
+    pub zfar: f32,
+    /// If true, near things get depth 1 and far things 0, rather than the other way round.
+    /// Perspective squeezes most of the depth range into the bit just past the near plane, and
+    /// floats are most precise near 0, so flipping them spreads the precision out much more
+    /// evenly. The depth test and clear value have to be flipped to match.
+    pub reverse_z: bool,
+}
+
+impl Camera {
+    /// Looking straight down -Z at the origin, from just far enough back that the region from
+    /// -1 to 1 fills the window's height. That's the same area the old clip-space demo showed.
+    pub fn new(aspect: f32) -> Self {
+        let fovy = Deg(45.0);
+        let distance = 1.0 / (fovy / 2.0).tan();
+        Self {
+            eye: Point3::new(0.0, 0.0, distance),
+            target: Point3::new(0.0, 0.0, 0.0),
+            up: Vector3::unit_y(),
+            aspect,
+            fovy,
+            znear: 0.1,
+            zfar: 100.0,
+            reverse_z: false,
+        }
+    }
+
+    /// Zoom by narrowing (negative) or widening (positive) the field of view. Unlike moving the
+    /// eye, this doesn't change the perspective, just how much of it fits on screen.
+    pub fn change_fovy(&mut self, delta: Deg<f32>) {
+        self.fovy = Deg((self.fovy + delta).0.clamp(MIN_FOVY.0, MAX_FOVY.0));
+    }
+
+    /// Aim at the middle of `bbox`, and move the eye back (or forward) along the way it's already
+    /// looking, until the whole box fits on screen. The far plane moves out too, if the box would
+    /// poke through it.
+    pub fn frame(&mut self, bbox: &BoundingBox) {
+        let radius = bbox.radius();
+        // The sphere around the box has to fit in both the vertical and horizontal field of view.
+        let half_fovy = self.fovy / 2.0;
+        let half_fovx = Rad((half_fovy.tan() * self.aspect).atan());
+        let half_fov = Rad::from(half_fovy).0.min(half_fovx.0);
+        let distance = radius / half_fov.sin();
+        let forward = self.forward();
+        self.target = bbox.center();
+        self.eye = self.target - forward * distance;
+        self.zfar = self.zfar.max(distance + radius * 2.0);
+    }
+
+    /// Swing the eye around the target by `angle`, about the up axis, keeping the same distance
+    /// from it. Positive is anticlockwise, seen from above.
+    pub fn orbit(&mut self, angle: Rad<f32>) {
+        let rotation = Quaternion::from_axis_angle(self.up.normalize(), angle);
+        self.eye = self.target - rotation * (self.target - self.eye);
+    }
+
+    /// The unit vector pointing from the eye towards what it's looking at.
+    pub fn forward(&self) -> Vector3<f32> {
+        (self.target - self.eye).normalize()
+    }
+
+    /// Somewhere between `previous` and `self`: at `previous` when `alpha` is 0, and at `self`
+    /// when it's 1. Only the position blends, everything else comes from `self`.
+    pub fn interpolate(&self, previous: &Camera, alpha: f32) -> Camera {
+        Camera {
+            eye: previous.eye + (self.eye - previous.eye) * alpha,
+            target: previous.target + (self.target - previous.target) * alpha,
+            ..*self
+        }
+    }
+
+    /// Move the eye and target part of the way towards `goal`'s, for smooth movement. Everything
+    /// else comes straight from `goal`.
+    ///
+    /// `damping` is how quickly: the distance left to go shrinks by a factor of `e^damping` each
+    /// second. That only depends on how much time has passed, not how it's split up, so two
+    /// updates of `dt` end up exactly where one of `2 * dt` does, whatever the frame rate. Zero or
+    /// less (or infinity) snaps straight to the goal.
+    pub fn ease_towards(&mut self, goal: &Camera, damping: f32, dt: Duration) {
+        let t = if damping > 0.0 {
+            1.0 - (-damping * dt.as_secs_f32()).exp()
+        } else {
+            1.0
+        };
+        *self = goal.interpolate(self, t);
+    }
+
+    /// Moves points from world space into clip space.
+    pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
+        let view = Matrix4::look_at_rh(self.eye, self.target, self.up);
+        self.build_projection_matrix() * view
+    }
+
+    /// The ray from the camera through the point on screen at `ndc`, i.e. normalized device
+    /// coordinates: x from -1 (left) to 1 (right), and y from -1 (bottom) to 1 (top). Returns where
+    /// the ray starts, on the near plane, and which way it goes, normalized.
+    ///
+    /// Found by unprojecting the points at that x and y on the near and far planes, back through
+    /// the inverse of the view-projection matrix.
+    pub fn ray_through(&self, ndc: [f32; 2]) -> (Point3<f32>, Vector3<f32>) {
+        let inverse = self
+            .build_view_projection_matrix()
+            .invert()
+            .unwrap_or(Matrix4::identity());
+        // With reversed Z, the near plane is at depth 1 instead of 0.
+        let (near_depth, far_depth) = if self.reverse_z {
+            (1.0, 0.0)
+        } else {
+            (0.0, 1.0)
+        };
+        let near = unproject(inverse, Vector3::new(ndc[0], ndc[1], near_depth));
+        let far = unproject(inverse, Vector3::new(ndc[0], ndc[1], far_depth));
+        (near, (far - near).normalize())
+    }
+
+    /// Moves points from view space (relative to the eye, looking down -Z) into clip space.
+    pub fn build_projection_matrix(&self) -> Matrix4<f32> {
+        let proj = cgmath::perspective(self.fovy, self.aspect, self.znear, self.zfar);
+        let proj = OPENGL_TO_WGPU_MATRIX * proj;
+        if self.reverse_z {
+            REVERSE_Z_MATRIX * proj
+        } else {
+            proj
+        }
+    }
+}
+
+/// Take a point in clip space (after the divide by w) back to world space, through `inverse`, the
+/// inverse of a view-projection matrix.
+pub fn unproject(inverse: Matrix4<f32>, ndc: Vector3<f32>) -> Point3<f32> {
+    let world = inverse * ndc.extend(1.0);
+    // The projection's divide by w has to be undone too.
+    Point3::from_homogeneous(world)
+}
+
+/// Corresponds to CameraUniform in shader.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    // cgmath's matrices aren't Pod, so store it as plain arrays.
+    view_proj: [[f32; 4]; 4],
+    /// Where the camera is, for specular highlights. A vec3 in a uniform takes up 16 bytes
+    /// anyway, so this is a vec4 with an unused w.
+    eye_position: [f32; 4],
+}
+
+impl From<&Camera> for CameraUniform {
+    fn from(camera: &Camera) -> Self {
+        Self {
+            view_proj: camera.build_view_projection_matrix().into(),
+            eye_position: camera.eye.to_homogeneous().into(),
+        }
+    }
+}
+
+/// Moves the camera with the keyboard. W/S move towards/away from the target, A/D orbit around it.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraController {
+    /// World units (or, when orbiting, radians) per second.
+    speed: f32,
+}
+
+impl CameraController {
+    pub fn new(speed: f32) -> Self {
+        Self { speed }
+    }
+
+    /// Checks which keys are held, rather than reacting to key presses, so holding a key keeps
+    /// the camera moving smoothly.
+    pub fn update_camera(
+        &self,
+        camera: &mut Camera,
+        pressed_keys: &HashSet<VirtualKeyCode>,
+        dt: Duration,
+    ) {
+        let step = self.speed * dt.as_secs_f32();
+        let held = |key| pressed_keys.contains(&key);
+
+        let to_target = camera.target - camera.eye;
+        let distance = to_target.magnitude();
+        let forward = to_target / distance;
+        // Stop short of the target, otherwise the camera would flip around once it passed it.
+        if held(VirtualKeyCode::W) && distance > step + camera.znear {
+            camera.eye += forward * step;
+        }
+        if held(VirtualKeyCode::S) {
+            camera.eye -= forward * step;
+        }
+
+        // Orbiting spins the eye around the target, keeping the same distance from it.
+        let orbit = match (held(VirtualKeyCode::A), held(VirtualKeyCode::D)) {
+            (true, false) => -step,
+            (false, true) => step,
+            _ => 0.0,
+        };
+        if orbit != 0.0 {
+            camera.orbit(cgmath::Rad(orbit));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_through_round_trips() {
+        for reverse_z in [false, true] {
+            let camera = Camera {
+                eye: Point3::new(1.0, 2.0, 3.0),
+                reverse_z,
+                ..Camera::new(1.5)
+            };
+            let view_proj = camera.build_view_projection_matrix();
+            for ndc in [[0.0, 0.0], [0.5, -0.25], [-1.0, 1.0]] {
+                let (origin, direction) = camera.ray_through(ndc);
+                assert!((direction.magnitude() - 1.0).abs() < 1e-4);
+                // Any point along the ray lands back on the same spot on screen.
+                for distance in [0.0, 1.0, 10.0] {
+                    let point = origin + direction * distance;
+                    let clip = view_proj * point.to_homogeneous();
+                    let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+                    assert!((x - ndc[0]).abs() < 1e-3, "{x} vs {ndc:?}");
+                    assert!((y - ndc[1]).abs() < 1e-3, "{y} vs {ndc:?}");
+                }
+            }
+            // Through the middle of the screen is straight at the target.
+            let (_, direction) = camera.ray_through([0.0, 0.0]);
+            assert!((direction - camera.forward()).magnitude() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn unproject_undoes_the_projection() {
+        let view_proj = Camera::new(1.0).build_view_projection_matrix();
+        let point = Point3::new(0.3, -0.2, -1.0);
+        let clip = view_proj * point.to_homogeneous();
+        let ndc = clip.truncate() / clip.w;
+        let back = unproject(view_proj.invert().unwrap(), ndc);
+        assert!((back - point).magnitude() < 1e-4, "{back:?}");
+    }
+
+    fn assert_near(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).magnitude() < 1e-4, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn easing_is_frame_rate_independent() {
+        let goal = Camera {
+            eye: Point3::new(4.0, 1.0, -2.0),
+            target: Point3::new(1.0, 0.0, 0.0),
+            ..Camera::new(1.0)
+        };
+        let dt = Duration::from_millis(16);
+        let mut two_steps = Camera::new(1.0);
+        two_steps.ease_towards(&goal, 3.0, dt);
+        two_steps.ease_towards(&goal, 3.0, dt);
+        let mut one_step = Camera::new(1.0);
+        one_step.ease_towards(&goal, 3.0, dt * 2);
+        assert_near(two_steps.eye, one_step.eye);
+        assert_near(two_steps.target, one_step.target);
+        // Part of the way there, not all of it.
+        assert!((one_step.eye - goal.eye).magnitude() > 0.1);
+    }
+
+    #[test]
+    fn easing_without_damping_snaps_to_the_goal() {
+        let goal = Camera {
+            eye: Point3::new(4.0, 1.0, -2.0),
+            ..Camera::new(1.0)
+        };
+        for damping in [0.0, -1.0] {
+            let mut camera = Camera::new(1.0);
+            camera.ease_towards(&goal, damping, Duration::from_millis(16));
+            assert_near(camera.eye, goal.eye);
+            assert_near(camera.target, goal.target);
+        }
+    }
+}
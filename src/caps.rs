@@ -0,0 +1,92 @@
+use std::fmt;
+
+use crate::draw::format_name;
+
+/// Everything the adapter and the window's surface support, for --print-caps. `State::new` only
+/// uses the first suitable entry of each list, so this is the place to see the rest. Its `Display`
+/// is meant for pasting into bug reports.
+pub struct Capabilities {
+    info: wgpu::AdapterInfo,
+    features: wgpu::Features,
+    limits: wgpu::Limits,
+    downlevel: wgpu::DownlevelCapabilities,
+    surface: wgpu::SurfaceCapabilities,
+    /// What each of the surface's formats can be used for on this adapter, in the same order as
+    /// `surface.formats`. wgpu 0.15's surface capabilities don't say, so ask the adapter.
+    format_usages: Vec<wgpu::TextureUsages>,
+}
+
+impl Capabilities {
+    pub fn query(adapter: &wgpu::Adapter, surface: &wgpu::Surface) -> Self {
+        let surface = surface.get_capabilities(adapter);
+        let format_usages = surface
+            .formats
+            .iter()
+            .map(|&format| adapter.get_texture_format_features(format).allowed_usages)
+            .collect();
+        Self {
+            info: adapter.get_info(),
+            features: adapter.features(),
+            limits: adapter.limits(),
+            downlevel: adapter.get_downlevel_capabilities(),
+            surface,
+            format_usages,
+        }
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = &self.info;
+        writeln!(f, "Adapter: {} ({:?})", info.name, info.device_type)?;
+        writeln!(f, "  backend: {:?}", info.backend)?;
+        writeln!(
+            f,
+            "  vendor: {:#06x}, device: {:#06x}",
+            info.vendor, info.device
+        )?;
+        writeln!(f, "  driver: {} {}", info.driver, info.driver_info)?;
+
+        writeln!(f, "Surface formats (the first is preferred):")?;
+        for (&format, usages) in self.surface.formats.iter().zip(&self.format_usages) {
+            writeln!(f, "  {:<24} {}", format_name(format), flag_list(usages))?;
+        }
+        write_list(f, "Present modes", &self.surface.present_modes)?;
+        write_list(f, "Alpha modes", &self.surface.alpha_modes)?;
+
+        writeln!(f, "Features:")?;
+        writeln!(f, "  {}", flag_list(&self.features))?;
+        writeln!(f, "Downlevel flags:")?;
+        writeln!(f, "  {}", flag_list(&self.downlevel.flags))?;
+        writeln!(f, "Shader model: {:?}", self.downlevel.shader_model)?;
+        writeln!(f, "Downlevel limits: {:?}", self.downlevel.limits)?;
+        write!(f, "Limits: {:#?}", self.limits)
+    }
+}
+
+/// A heading, then each item on its own line.
+fn write_list<T: fmt::Debug>(
+    f: &mut fmt::Formatter<'_>,
+    heading: &str,
+    items: &[T],
+) -> fmt::Result {
+    writeln!(f, "{heading}:")?;
+    if items.is_empty() {
+        writeln!(f, "  none")?;
+    }
+    for item in items {
+        writeln!(f, "  {item:?}")?;
+    }
+    Ok(())
+}
+
+/// Bitflags' `Debug` joins the flags with ` | `, which gets hard to read when there are dozens.
+/// Commas wrap better.
+fn flag_list(flags: &impl fmt::Debug) -> String {
+    let flags = format!("{flags:?}");
+    if flags.is_empty() || flags == "(empty)" {
+        "none".to_string()
+    } else {
+        flags.split(" | ").collect::<Vec<_>>().join(", ")
+    }
+}
@@ -0,0 +1,120 @@
+/// Convert a colour from HSV to RGB. All inputs and outputs are in 0..1.
+/// Hue wraps around, so 1.25 is the same as 0.25.
+pub fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> [f64; 3] {
+    // Split the colour wheel into 6 sectors, between each pair of primary/secondary colours.
+    let h = hue.rem_euclid(1.0) * 6.0;
+    let sector = h.floor();
+    let within_sector = h - sector;
+    let p = value * (1.0 - saturation);
+    let q = value * (1.0 - saturation * within_sector);
+    let t = value * (1.0 - saturation * (1.0 - within_sector));
+    match sector as u8 {
+        0 => [value, t, p],
+        1 => [q, value, p],
+        2 => [p, value, t],
+        3 => [p, q, value],
+        4 => [t, p, value],
+        _ => [value, p, q],
+    }
+}
+
+/// Parse a colour like `ff8800` or `#ff8800` into RGB, each in 0..1. These are sRGB values, like
+/// colour pickers give you, so use `srgb_to_linear` before handing them to the GPU.
+pub fn parse_hex(hex: &str) -> Option<[f32; 3]> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?].map(|c| c as f32 / 255.0))
+}
+
+/// Shaders work in linear colour, where doubling a value doubles the light. sRGB spends more of its
+/// range on dark colours, because that's where eyes notice differences, so it has to be converted.
+pub fn srgb_to_linear(rgb: [f32; 3]) -> [f32; 3] {
+    rgb.map(|c| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+/// The inverse of `srgb_to_linear`.
+pub fn linear_to_srgb(rgb: [f32; 3]) -> [f32; 3] {
+    rgb.map(|c| {
+        if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
+
+/// How much red, green and blue each contribute to how bright a linear colour looks (Rec. 709's
+/// weights, which sRGB shares). Green looks far brighter than blue at the same value.
+pub const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Colour matrices apply to linear RGB, one row per output channel, so a colour's new red is the
+/// dot product of the first row with it, and so on.
+pub type ColorMatrix = [[f32; 3]; 3];
+
+/// Replaces each colour with a grey of the same luminance, since every row is `LUMINANCE`.
+pub const GRAYSCALE: ColorMatrix = [LUMINANCE, LUMINANCE, LUMINANCE];
+
+// Simulations of the three kinds of dichromacy, where one of the eye's three kinds of cone is
+// missing, so colours which only differ in what that cone sees look the same. These are Machado,
+// Oliveira and Fernandes's matrices (2009) at full severity.
+
+/// No red cones: reds look dark, and get confused with greens.
+pub const PROTANOPIA: ColorMatrix = [
+    [0.152286, 1.052583, -0.204868],
+    [0.114503, 0.786281, 0.099216],
+    [-0.003882, -0.048116, 1.051998],
+];
+
+/// No green cones: the most common kind, with reds and greens confused, but reds not darkened.
+pub const DEUTERANOPIA: ColorMatrix = [
+    [0.367322, 0.860646, -0.227968],
+    [0.280085, 0.672501, 0.047413],
+    [-0.011820, 0.042940, 0.968881],
+];
+
+/// No blue cones: rare, with blues confused with greens, and yellows with violets.
+pub const TRITANOPIA: ColorMatrix = [
+    [1.255528, -0.076749, -0.178779],
+    [-0.078411, 0.930809, 0.148020],
+    [0.004733, 0.691367, 0.303900],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What tonemap.wgsl does with a colour matrix.
+    fn apply(matrix: ColorMatrix, rgb: [f32; 3]) -> [f32; 3] {
+        matrix.map(|row| row.iter().zip(rgb).map(|(weight, c)| weight * c).sum())
+    }
+
+    fn assert_grey(rgb: [f32; 3], luminance: f32) {
+        for c in rgb {
+            assert!(
+                (c - luminance).abs() < 1e-5,
+                "{rgb:?} isn't {luminance} grey"
+            );
+        }
+    }
+
+    #[test]
+    fn grayscale_keeps_luminance() {
+        assert_grey(apply(GRAYSCALE, [1.0, 1.0, 1.0]), 1.0);
+        assert_grey(apply(GRAYSCALE, [0.0, 0.0, 0.0]), 0.0);
+        assert_grey(apply(GRAYSCALE, [1.0, 0.0, 0.0]), 0.2126);
+        assert_grey(apply(GRAYSCALE, [0.0, 1.0, 0.0]), 0.7152);
+        assert_grey(apply(GRAYSCALE, [0.0, 0.0, 1.0]), 0.0722);
+        // Greys stay as they are.
+        assert_grey(apply(GRAYSCALE, [0.5, 0.5, 0.5]), 0.5);
+        assert_grey(apply(GRAYSCALE, [0.5, 0.25, 1.0]), 0.3573);
+    }
+}
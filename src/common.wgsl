@@ -0,0 +1,90 @@
+// Declarations every scene shader needs, matching the Rust side's vertex buffers, bind groups
+// and colour targets. Shaders get them with:
+//
+// #include "common.wgsl"
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    // Multiplied with the texture. White unless the mesh says otherwise.
+    @location(3) color: vec3<f32>,
+};
+
+// One row per instance. Corresponds to InstanceRaw in the Rust code.
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) texture_layer: u32,
+};
+
+// Where the model is and which way it's facing, and what colour to tint it.
+// Corresponds to ModelUniform in the Rust code.
+struct ModelUniform {
+    transform: mat4x4<f32>,
+    tint: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> model_uniform: ModelUniform;
+
+// Corresponds to CameraUniform in the Rust code.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    // Only xyz is used.
+    eye_position: vec4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// A directional light. Corresponds to LightUniform in the Rust code.
+struct LightUniform {
+    // Where things land in the shadow map.
+    view_proj: mat4x4<f32>,
+    // Towards the light, normalized.
+    direction: vec3<f32>,
+    // Zero when the light is off.
+    intensity: f32,
+    // How much light everything gets, even facing away from the light.
+    ambient_strength: f32,
+    // How bright highlights are, and how small: higher shininess is a smaller, sharper highlight.
+    specular_strength: f32,
+    shininess: f32,
+};
+@group(3) @binding(0)
+var<uniform> light: LightUniform;
+
+// Corresponds to the BindGroupDescriptor in the Rust code.
+// These are 'uniforms'.
+@group(0) @binding(0)
+var t_diffuse: texture_2d_array<f32>;
+@group(0)@binding(1)
+var s_diffuse: sampler;
+// What the light can see, as depth. See ShadowMap in the Rust code, and `shadow` below.
+@group(0) @binding(2)
+var t_shadow: texture_depth_2d;
+@group(0) @binding(3)
+var s_shadow: sampler_comparison;
+
+// Two outputs, for two colour targets. Without --normals-target there's only one target, and
+// wgpu ignores the normal.
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    // The normal, squashed from -1..1 into 0..1 so it fits in a unorm texture.
+    @location(1) normal: vec4<f32>,
+};
+
+// How much of the light reaches `world_position`: 1 if nothing's in the way, 0 if it's in shadow,
+// and in between along the edges of shadows, where the comparison sampler blends neighbouring
+// texels. Everything outside the shadow map is lit.
+fn shadow(world_position: vec3<f32>) -> f32 {
+    let light_clip = light.view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = light_clip.xyz / light_clip.w;
+    // Clip space y points up, texture coordinates' y points down.
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    // The Level version doesn't need derivatives, so this works in non-uniform control flow too.
+    let lit = textureSampleCompareLevel(t_shadow, s_shadow, uv, ndc.z);
+    let outside = any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0;
+    return select(lit, 1.0, outside);
+}
@@ -1,8 +1,47 @@
-use wgpu::util::DeviceExt;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Duration,
+};
+
+use crate::{
+    args::Args,
+    backend_quirks::{FrameCapture, Quirks},
+    background::Background,
+    bench::FrameTimes,
+    blit::BlitPipeline,
+    bounding_boxes::BoundingBoxes,
+    camera::{Camera, CameraController, CameraUniform},
+    caps::Capabilities,
+    error::{AppError, StateError, EXIT_FAILURE},
+    frame_graph::FrameGraph,
+    frustum::Frustum,
+    grid::Grid,
+    hdr::HdrPipeline,
+    hud::Hud,
+    input_log::{InputEvent, InputRecorder, InputReplay},
+    light::{Light, LightUniform},
+    loupe::{Loupe, LoupeRegion},
+    mesh::{Mesh, Vertex, VertexLayout},
+    overdraw::Overdraw,
+    particles::ParticleSystem,
+    point_sprites::PointSprites,
+    recorder::FrameRecorder,
+    scatter::{Instance, InstanceRaw, ScatterBuilder, DEFAULT_SEED},
+    scene::{ModelUniforms, SceneObject},
+    shader::Shader,
+    shadow::ShadowMap,
+    skybox::Skybox,
+    texture::Texture,
+    uniform::Uniform,
+    wireframe::Wireframe,
+};
+use cgmath::{Deg, InnerSpace, Matrix4, Rad, SquareMatrix, Vector3};
 use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
     event::*,
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
+    monitor::{MonitorHandle, VideoMode},
+    window::{Fullscreen, Window, WindowBuilder, WindowId},
 };
 
 const BLUE: wgpu::Color = wgpu::Color {
@@ -12,39 +51,163 @@ const BLUE: wgpu::Color = wgpu::Color {
     a: 1.0,
 };
 
+/// How opaque the clear colour is with --transparent, unless --clear-alpha says otherwise.
+const TRANSPARENT_CLEAR_ALPHA: f64 = 0.5;
+
+/// How fast the pentagon spins. Multiplied by the frame's delta time, so the speed doesn't depend
+/// on the framerate.
+const SPIN_RADIANS_PER_SEC: f32 = std::f32::consts::FRAC_PI_4;
+
+/// How much time each `update` simulates with --fixed-timestep, i.e. 60 updates per second.
+const FIXED_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// With --fixed-timestep, frames longer than this only simulate this much time. Otherwise after a
+/// long stall (e.g. dragging the window) there'd be so many updates to catch up on that the next
+/// frame would stall too, and so on.
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// How long it takes the hue-cycling clear colour to go all the way round the colour wheel.
+const HUE_CYCLE_PERIOD_SECS: f64 = 10.0;
+
+/// The window's title. The active pipeline's name gets added on the end.
+const WINDOW_TITLE: &str = "Adam GPU Demo";
+
+/// What the built-in shader's pipeline is called. Pipelines from --shader-dir are named after
+/// their files.
+const BUILT_IN_SHADER_NAME: &str = "built-in";
+
+/// Default gradient background colours, as sRGB.
+const GRADIENT_TOP: [f32; 3] = [0.2, 0.33, 0.47];
+const GRADIENT_BOTTOM: [f32; 3] = [0.04, 0.06, 0.1];
+
+/// How quickly the camera eases towards where it's going, unless --camera-damping says otherwise.
+/// At 10 it's covered 63% of the way in a tenth of a second, and nearly all of it in half a second.
+const CAMERA_DAMPING: f32 = 10.0;
+
+/// How fast W/S/A/D move the camera, in world units (or radians) per second.
+const CAMERA_SPEED: f32 = 1.5;
+
+/// How fast --demo orbits the camera around the scene: once round every 16 seconds.
+const DEMO_ORBIT_RADIANS_PER_SEC: f32 = std::f32::consts::FRAC_PI_8;
+
+/// How long --demo waits after the last key press or mouse movement before orbiting again.
+const DEMO_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// If this many frames in a row fail to render, the GPU's probably not coming back, so quit.
+const MAX_CONSECUTIVE_RENDER_ERRORS: u32 = 100;
+
+/// How much each press of -/= (or notch of the scroll wheel) changes the field of view.
+const FOVY_STEP: Deg<f32> = Deg(5.0);
+
+/// Bind group indices in the scene shader.
+const MODEL_GROUP: u32 = 1;
+const CAMERA_GROUP: u32 = 2;
+const LIGHT_GROUP: u32 = 3;
+
+/// The pentagon. Normals are left as zero, `Mesh::with_computed_flat_normals` fills them in.
 const VERTICES: &[Vertex] = &[
     // A
     Vertex {
         position: [0.0, 0.5, 0.0],
         tex_coords: [0.4131759, 0.99240386],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
     },
     // B
     Vertex {
         position: [-0.25, 0.0, 0.0],
         tex_coords: [0.0048659444, 0.56958647],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
     },
     // C
     Vertex {
         position: [0.25, 0.0, 0.0],
         tex_coords: [0.28081453, 0.05060294],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
     },
     // D
     Vertex {
         position: [-0.5, -0.5, 0.0],
         tex_coords: [0.4131759, 0.99240386],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
     },
     // E
     Vertex {
         position: [0.0, -0.5, 0.0],
         tex_coords: [0.85967, 0.1526709],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
     },
     // F
     Vertex {
         position: [0.5, -0.5, 0.0],
         tex_coords: [0.9414737, 0.7347359],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
+    },
+];
+
+/// With --vertex-colors, the colours of the pentagon's vertices, A to F: red, green, blue, blue,
+/// red, green. Each triangle has one of each, so every triangle blends between all three.
+const VERTEX_COLORS: [[f32; 3]; 6] = [
+    [1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [0.0, 0.0, 1.0],
+    [1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+];
+
+/// A square with texture coordinates going up to 2, so the texture tiles 2x2 when the sampler's
+/// address mode is Repeat.
+const TILING_QUAD_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.5, -0.5, 0.0],
+        tex_coords: [0.0, 2.0],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
+    },
+    Vertex {
+        position: [0.5, -0.5, 0.0],
+        tex_coords: [2.0, 2.0],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
+    },
+    Vertex {
+        position: [0.5, 0.5, 0.0],
+        tex_coords: [2.0, 0.0],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
+    },
+    Vertex {
+        position: [-0.5, 0.5, 0.0],
+        tex_coords: [0.0, 0.0],
+        normal: [0.0; 3],
+        color: Vertex::WHITE,
     },
 ];
 
+const TILING_QUAD_INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
+
+/// Every image the scene can use, as layers of one array texture. They get resized to match the
+/// first one, because every layer of an array texture is the same size.
+const TEXTURE_LAYERS: [(&[u8], &str); 3] = [
+    (include_bytes!("gold.png"), "gold.png"),
+    (include_bytes!("rusted_copper.jpg"), "rusted_copper.jpg"),
+    (include_bytes!("tree.png"), "tree.png"),
+];
+const TREE_LAYER: u32 = 2;
+
+/// What the sampler does with texture coordinates outside 0..1. Cycled with R.
+const ADDRESS_MODES: [wgpu::AddressMode; 3] = [
+    wgpu::AddressMode::ClampToEdge,
+    wgpu::AddressMode::Repeat,
+    wgpu::AddressMode::MirrorRepeat,
+];
+
 #[rustfmt::skip]
 const INDICES: &[u16] = &[
     // ABC
@@ -56,60 +219,307 @@ const INDICES: &[u16] = &[
 ];
 
 struct State {
+    /// Made by `create_owned_surface`. Must be declared before `window`, so it's dropped first.
     surface: wgpu::Surface,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    /// Kept for making surfaces for more windows, see `PerWindowState`.
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    /// Its width and height are the window's size, as of the last `resize`. Read them with
+    /// `current_size`, and only change them with `resize`, so the surface always matches.
     surface_config: wgpu::SurfaceConfiguration,
-    size: winit::dpi::PhysicalSize<u32>,
+    /// How big the scene's render target is compared to the window.
+    render_resolution: RenderResolution,
+    /// The size the scene is actually rendered at. The HDR (or blit), MSAA and depth textures are
+    /// all this size, and only get recreated when it changes.
+    render_size: PhysicalSize<u32>,
+    /// The latest size from a resize event, not applied yet. Dragging a window's edge sends lots
+    /// of resize events per frame, and each resize recreates the surface, depth, MSAA and HDR
+    /// textures, so only the last one each frame gets applied, in `update`.
+    pending_size: Option<PhysicalSize<u32>>,
     window: Window,
     color: wgpu::Color,
-    render_pipelines: Vec<wgpu::RenderPipeline>,
-    active_texture: usize,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
-    diffuse_bind_group: wgpu::BindGroup,
-    diffuse_textures: Vec<crate::texture::Texture>,
+    render_pipelines: Vec<NamedPipeline>,
+    /// Same shaders as render_pipelines, but blending, for the transparent objects.
+    transparent_pipelines: Vec<NamedPipeline>,
+    /// Kept so the pipelines can be rebuilt when a baked-in option (e.g. culling) changes.
+    render_pipeline_layout: wgpu::PipelineLayout,
+    shaders: Vec<Shader>,
+    /// uv_debug.wgsl, as is and with v flipped. X draws everything with these instead.
+    uv_shaders: Vec<Shader>,
+    /// One per `uv_shaders`, all opaque: the debug view has no need for blending.
+    uv_pipelines: Vec<NamedPipeline>,
+    /// X toggles it. Shows the objects' texture coordinates as colours, see uv_debug.wgsl.
+    uv_debug: bool,
+    /// F toggles it. Which of `uv_pipelines` the debug view uses.
+    flip_v: bool,
+    pipeline_options: PipelineOptions,
+    /// Index into `render_pipelines` (and `transparent_pipelines`) of the one being drawn with.
+    /// Space cycles through them.
+    active_pipeline: usize,
+    /// Drawn first, writing to the depth buffer.
+    opaque_objects: Vec<SceneObject>,
+    /// Drawn after the opaque objects, blended over them, furthest from the camera first.
+    transparent_objects: Vec<SceneObject>,
+    /// Every object's transform and tint, in one buffer.
+    model_uniforms: ModelUniforms,
+    /// Slot (see `SceneObject::slot`) of the object whose tint O changes. Tab picks another.
+    selected_object: u32,
+    camera: Camera,
+    /// Where the controller and zooming have put the camera. `camera` eases towards it.
+    camera_goal: Camera,
+    /// How quickly `camera` catches up with `camera_goal`. See `Camera::ease_towards`.
+    camera_damping: f32,
+    camera_uniform: Uniform<CameraUniform>,
+    camera_controller: CameraController,
+    /// With --demo, the camera orbits the scene by itself, whenever nobody's touched anything for
+    /// `DEMO_IDLE_TIMEOUT`.
+    demo: bool,
+    /// How long since the last key press or mouse input, counted in update time, so replays and
+    /// --fixed-timestep see the same thing. Only counted with --demo.
+    idle: Duration,
+    /// Arrow keys turn it, [ and ] change its brightness, L turns it off and on.
+    light: Light,
+    light_uniform: Uniform<LightUniform>,
+    /// The light's view of the scene, for shadows. Only drawn into with --shadows.
+    shadow_map: ShadowMap,
+    /// Same size and sample count as the texture the scene is drawn into.
+    depth_texture: Texture,
+    /// One bind group per entry in ADDRESS_MODES, because samplers can't be changed after
+    /// they're created.
+    diffuse_bind_groups: Vec<wgpu::BindGroup>,
+    /// Index into ADDRESS_MODES.
+    address_mode: usize,
+    // Never read, but the bind group refers to them, so keep them around.
+    #[allow(dead_code)]
+    diffuse_texture: Texture,
+    spin: bool,
+    spin_angle: Rad<f32>,
+    /// `spin_angle` and `camera` as they were before the latest update. Rendering blends between
+    /// these and the current ones, so movement looks smooth even when updates and frames don't
+    /// line up (see --fixed-timestep).
+    previous_spin_angle: Rad<f32>,
+    previous_camera: Camera,
+    /// If set, the scene renders into an HDR texture which then gets tonemapped onto the surface.
+    /// None if the adapter can't render to HDR textures.
+    hdr: Option<HdrPipeline>,
+    /// Without HDR, the scene still needs an offscreen texture if it renders at a different
+    /// resolution to the window. This is it, and stretches it over the surface afterwards.
+    blit: Option<BlitPipeline>,
+    /// If true, the clear colour slowly cycles through every hue.
+    hue_cycle: bool,
+    /// Where the hue cycle is up to, from 0 to 1.
+    hue: f64,
+    /// If true, moving the mouse sets the clear colour's red and green. Off by default, so the
+    /// background doesn't change every time the mouse goes past.
+    mouse_color: bool,
+    /// Where the cursor last was in the window, whether or not it's setting the clear colour.
+    cursor_position: PhysicalPosition<f64>,
+    /// If set, the CPU waits for the GPU whenever more than this many frames are in flight.
+    frame_latency: Option<u32>,
+    /// Submissions the GPU might still be working on, oldest first.
+    in_flight: VecDeque<wgpu::SubmissionIndex>,
+    particles: Option<ParticleSystem>,
+    /// Keys currently held down. Toggles only fire when a key goes from released to pressed,
+    /// not on the OS's key-repeat events. Continuous actions (e.g. movement) can check this set
+    /// every update instead.
+    pressed_keys: HashSet<VirtualKeyCode>,
+    /// The scene renders into this when MSAA is on, then it's resolved into the scene view.
+    /// None when the sample count is 1.
+    multisampled_view: Option<wgpu::TextureView>,
+    /// The scene pass's second colour target, which the shaders write normals into, if
+    /// --normals-target is on. Nothing reads it yet, but it's what deferred shading or picking
+    /// would start from. Same size as the scene's other render targets.
+    normals_view: Option<wgpu::TextureView>,
+    /// Like `multisampled_view`, but for the normals. Only exists if both MSAA and the normals
+    /// target are on.
+    multisampled_normals_view: Option<wgpu::TextureView>,
+    /// FPS counter and help text, drawn over everything else.
+    hud: Hud,
+    /// The last few seconds of frame times, drawn over the scene. G toggles it.
+    frame_graph: FrameGraph,
+    /// Optional gradient drawn behind everything, instead of the flat clear colour.
+    background: Background,
+    /// Optional cubemap drawn behind everything, instead of the background.
+    skybox: Skybox,
+    /// Optional grid and axes, drawn with the scene. Q toggles it.
+    grid: Grid,
+    /// Outlines of every object's bounding box. F3 toggles it.
+    bounding_boxes: BoundingBoxes,
+    /// Every object's triangle edges, drawn over it. F6 toggles it.
+    wireframe: Wireframe,
+    /// With --point-size, the main object's vertices, drawn as dots.
+    point_sprites: Option<PointSprites>,
+    /// Shown instead of the scene when enabled, with V.
+    overdraw: Overdraw,
+    /// Magnifies the pixels around the cursor into a corner of the window. F5 toggles it.
+    loupe: Loupe,
+    /// If set, the scene keeps this width/height ratio, letterboxed with black bars.
+    aspect: Option<f32>,
+    /// How many frames in a row `render` has failed. Reset by any successful frame.
+    consecutive_render_errors: u32,
+    /// J toggles it. If true, the scene's colour isn't cleared between frames, so anything moving
+    /// leaves a trail. Depth is still cleared, otherwise the trail would hide the object.
+    trails: bool,
+    /// Clear next frame, even with `trails` on. The first frame has to, because there's nothing
+    /// to load yet, and so does the first after the scene's textures are recreated. E sets it.
+    clear_next_frame: bool,
+    /// F12 sets it. If true, the next frame gets captured by RenderDoc (or whatever
+    /// `quirks.frame_capture` says), if it's attached.
+    capture_next_frame: bool,
+    /// How the backend in use differs from the others.
+    quirks: Quirks,
+    /// Skip instances the camera can't see (see `SceneObject::cull`). Off with --no-cull, and with
+    /// --shadows, because things off screen can still cast shadows onto things on screen.
+    frustum_culling: bool,
+    /// How many instances the main window's camera culled last frame. Logged when it changes.
+    culled: u32,
+    /// True while the window is completely hidden, e.g. behind another window or on another
+    /// virtual desktop, so drawing it would be wasted. Only some platforms say.
+    occluded: bool,
+    /// With --scatter, places the main object's instances. N rerolls them with a new `seed`.
+    scatter: Option<ScatterBuilder>,
+    /// What `scatter` was last built with. Logged whenever it changes, so a layout worth keeping
+    /// can be brought back with --seed.
+    seed: u64,
+    /// The layer of the model's own texture, which all of the instances use, if there's a model.
+    model_texture_layer: Option<u32>,
+    /// Saves frames for --record. None once they've all been saved.
+    recorder: Option<FrameRecorder>,
+    /// How things were when the demo started, for Home to go back to.
+    initial: InitialState,
+}
+
+/// The parts of `State` which keys can change and whose starting values depend on the flags. Home
+/// restores these, and resets everything else interactive to its usual starting value.
+struct InitialState {
+    color: wgpu::Color,
+    camera: Camera,
+    pipeline_options: PipelineOptions,
+    address_mode: usize,
+    gradient: bool,
+    skybox: bool,
+    seed: u64,
+}
+
+/// Another window onto the same scene, opened with --second-window. It shares the main window's
+/// device, objects, textures, shaders and light, but has its own surface, depth texture and
+/// camera, so it shows the scene from somewhere else.
+///
+/// Only the objects get drawn. The background, skybox, particles, HDR and MSAA all belong to the
+/// main window's render targets, and the keys and mouse only work in the main window.
+struct PerWindowState {
+    /// Made by `create_owned_surface`. Must be declared before `window`, so it's dropped first.
+    surface: wgpu::Surface,
+    window: Window,
+    config: wgpu::SurfaceConfiguration,
+    camera: Camera,
+    camera_uniform: Uniform<CameraUniform>,
+    depth_texture: Texture,
+    /// The main window's pipelines, but built for this surface. They get rebuilt when the main
+    /// window's change (e.g. C changes the culling), which `pipeline_options` is for checking.
+    pipeline_options: PipelineOptions,
+    render_pipelines: Vec<NamedPipeline>,
+    transparent_pipelines: Vec<NamedPipeline>,
+    /// How many frames in a row have failed to render in this window, like `State`'s.
+    consecutive_render_errors: u32,
+}
+
+impl PerWindowState {
+    fn resize(&mut self, device: &wgpu::Device, new_size: PhysicalSize<u32>) {
+        if new_size.width > 0 && new_size.height > 0 {
+            self.config.width = new_size.width;
+            self.config.height = new_size.height;
+            self.surface.configure(device, &self.config);
+            self.camera.aspect = new_size.width as f32 / new_size.height as f32;
+            self.depth_texture = Texture::create_depth_texture(
+                device,
+                new_size.width,
+                new_size.height,
+                1,
+                "second_window_depth_texture",
+            );
+        }
+    }
 }
 
 impl State {
-    async fn new(window: Window) -> Self {
+    /// None if there's nothing to run, because --print-caps printed the adapter's and surface's
+    /// capabilities instead.
+    async fn new(window: Window, args: &Args) -> Result<Option<Self>, StateError> {
         let size = window.inner_size();
 
         // The instance is a handle to our GPU.
-        // Backends::all => Vulkan + Metal + DX12 + Browser.
+        // Backends::all => Vulkan + Metal + DX12 + Browser, unless --backend says otherwise.
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
+            backends: args.backend.unwrap_or(wgpu::Backends::all()),
             dx12_shader_compiler: Default::default(),
         });
 
         // The part of the window our code draws to.
-        // Safety
-        // Surface needs to live as long as the window that created it.
-        // State owns the window so this should be safe.
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        let (surface, window) =
+            create_owned_surface(&instance, window).map_err(StateError::CreateSurface)?;
 
         // Adapter is a handle to the actual graphics card.
         // Use this to get info about GPU e.g. name, which backend it uses.
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
+        let request_adapter = |force_fallback_adapter| {
+            instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: args.power.unwrap_or_default(),
                 compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+                // The fallback adapter is a software renderer, for machines with no GPU driver
+                // (e.g. CI containers).
+                force_fallback_adapter,
             })
-            .await
-            .expect("No suitable graphics card available.");
+        };
+        let (adapter, is_fallback) = if args.force_fallback {
+            (request_adapter(true).await, true)
+        } else {
+            match request_adapter(false).await {
+                Some(adapter) => (Some(adapter), false),
+                None => {
+                    log::warn!("No hardware adapter available, trying the fallback adapter");
+                    (request_adapter(true).await, true)
+                }
+            }
+        };
+        let adapter = adapter.ok_or(StateError::NoAdapter)?;
+        let adapter_info = adapter.get_info();
+        let adapter_kind = if is_fallback { "fallback" } else { "hardware" };
+        log::info!(
+            "Using {adapter_kind} adapter {:?} on {:?}",
+            adapter_info.name,
+            adapter_info.backend
+        );
+        let quirks = Quirks::for_backend(adapter_info.backend);
+        quirks.log(adapter_info.backend);
+        if args.print_caps {
+            println!("{}", Capabilities::query(&adapter, &surface));
+            return Ok(None);
+        }
 
+        // Unclipped depth is optional, so only ask for it if the adapter has it. Then U can turn
+        // it on and off whether or not --unclipped-depth was given.
+        let features = if args.safe_mode {
+            wgpu::Features::empty()
+        } else {
+            adapter.features() & wgpu::Features::DEPTH_CLIP_CONTROL
+        };
+        if args.unclipped_depth && features.is_empty() {
+            log::warn!("This adapter doesn't support unclipped depth, so clipping as usual");
+        }
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     // Extra device features we need.
-                    // We don't need any for now.
-                    features: wgpu::Features::empty(),
+                    features,
                     // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web we'll have to disable some.
+                    // we're building for the web we'll have to disable some. Safe mode asks for
+                    // no more than WebGL has either, except for textures as big as the window.
                     limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits::downlevel_webgl2_defaults()
+                    } else if args.safe_mode {
+                        wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
                     } else {
                         wgpu::Limits::default()
                     },
@@ -118,55 +528,194 @@ impl State {
                 None, // Trace path
             )
             .await
-            .unwrap();
+            .map_err(StateError::RequestDevice)?;
 
         let surface_caps = surface.get_capabilities(&adapter);
-        // This tutorial assumes sRGB surface texture. If you want to support others, account for
-        // them when drawing. If you don't, colours will come out darker than intended.
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.describe().srgb)
-            .unwrap_or(surface_caps.formats[0]);
+        let surface_format = match &args.format {
+            // Only formats the surface supports, so a typo can't pick something unusable.
+            Some(name) => surface_caps
+                .formats
+                .iter()
+                .copied()
+                .find(|&f| format_name(f).eq_ignore_ascii_case(name))
+                .ok_or_else(|| StateError::UnsupportedSurfaceFormat {
+                    requested: name.clone(),
+                    available: surface_caps
+                        .formats
+                        .iter()
+                        .copied()
+                        .map(format_name)
+                        .collect(),
+                })?,
+            // This tutorial assumes sRGB surface texture. If you want to support others, account
+            // for them when drawing. If you don't, colours will come out darker than intended.
+            None => surface_caps
+                .formats
+                .iter()
+                .copied()
+                .find(|f| f.describe().srgb)
+                .or_else(|| surface_caps.formats.first().copied())
+                .ok_or(StateError::NoSurfaceFormat)?,
+        };
+        let surface_is_srgb = surface_format.describe().srgb;
+        log::info!("Surface format: {}", format_name(surface_format));
+        if !surface_is_srgb {
+            log::warn!(
+                "{} isn't sRGB, so lighting and blending will happen in sRGB space, not linear",
+                format_name(surface_format)
+            );
+        }
+        let mut present_mode = *surface_caps
+            .present_modes
+            .first()
+            .ok_or(StateError::NoPresentMode)?;
+        // Benchmarking (and --vsync off) wants frames as fast as possible, not at the monitor's
+        // refresh rate.
+        if args.safe_mode {
+            // Every surface supports Fifo, even when benchmarking.
+            present_mode = wgpu::PresentMode::Fifo;
+        } else if args.bench_frames.is_some() || args.vsync == Some(false) {
+            let uncapped = [wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox]
+                .into_iter()
+                .find(|mode| surface_caps.present_modes.contains(mode));
+            match uncapped {
+                Some(mode) => present_mode = mode,
+                None => {
+                    log::warn!("Can't turn vsync off, so frames are capped at the refresh rate")
+                }
+            }
+        } else if args.vsync == Some(true) {
+            // Every surface supports Fifo.
+            present_mode = wgpu::PresentMode::Fifo;
+        }
+        let (alpha_mode, transparent) =
+            choose_alpha_mode(&surface_caps.alpha_modes, args.transparent)?;
+        // Only the clear colour is see-through. Everything drawn over it is opaque, apart from the
+        // transparent objects, which blend with it, alpha and all.
+        let clear_alpha = if transparent {
+            args.clear_alpha.unwrap_or(TRANSPARENT_CLEAR_ALPHA)
+        } else {
+            if args.clear_alpha.is_some() && !args.transparent {
+                log::warn!("--clear-alpha only does anything with --transparent, ignoring it");
+            }
+            1.0
+        };
+
+        // --record copies frames off the surface, which not every backend can do.
+        let recorder = match &args.record {
+            Some(_) if !quirks.surface_readback => {
+                log::warn!(
+                    "Can't record frames with the {:?} backend, so not recording",
+                    adapter_info.backend
+                );
+                None
+            }
+            Some((frames, dir)) => match FrameRecorder::new(*frames, dir.clone(), surface_format) {
+                Ok(recorder) => {
+                    log::info!("Recording {frames} frame(s) to {}", dir.display());
+                    Some(recorder)
+                }
+                Err(e) => {
+                    log::warn!("Not recording: {e}");
+                    None
+                }
+            },
+            None => None,
+        };
 
         // Defines how surface creates its underlying SurfaceTextures.
         let surface_config = wgpu::SurfaceConfiguration {
-            // How will the SurfaceTexture be used? They'll be used to write to the screen.
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // How will the SurfaceTexture be used? They'll be used to write to the screen, and
+            // copied from if recording.
+            usage: if recorder.is_some() {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            },
             // How will they be stored on the GPU.
             format: surface_format,
             width: size.width,
             height: size.height,
             // This present_mode should be "Fifo" i.e. vsync. But in later extensions, users can
             // choose to disable that, so maybe it'll be customizable.
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
+            present_mode,
+            alpha_mode,
             view_formats: vec![],
         };
         surface.configure(&device, &surface_config);
+        // wgpu 0.15 doesn't let us set the surface's own frame latency, it picks its own (usually
+        // 2 or 3 frames). So instead, limit how many frames the CPU can get ahead by waiting for
+        // old submissions in `render`.
+        match args.frame_latency {
+            Some(n) => log::info!("Frame latency: at most {n} frame(s) in flight"),
+            None => log::info!("Frame latency: wgpu's default"),
+        }
 
-        let diffuse_textures = vec![
-            crate::texture::Texture::from_bytes(
-                &device,
-                &queue,
-                include_bytes!("gold.png"),
-                "gold.png",
-            )
-            .unwrap(),
-            crate::texture::Texture::from_bytes(
-                &device,
-                &queue,
-                include_bytes!("rusted_copper.jpg"),
-                "rusted_copper.jpg",
-            )
-            .unwrap(),
-        ];
+        // --gltf swaps the pentagon for a model loaded from a file.
+        let mut model = args
+            .gltf
+            .as_deref()
+            .map(crate::model::load_gltf)
+            .transpose()
+            .map_err(StateError::Model)?;
+        // Whatever size it was made at, and wherever it was, make it about pentagon-sized and
+        // put it in the middle, so the camera can see it.
+        if let Some(original) = model
+            .as_mut()
+            .and_then(|model| model.mesh.normalize_to_unit())
+        {
+            let (size, center) = (original.size(), original.center());
+            log::info!(
+                "Fitted the model into the unit cube. It was {:.2}x{:.2}x{:.2}, centred on \
+                 ({:.2}, {:.2}, {:.2})",
+                size.x,
+                size.y,
+                size.z,
+                center.x,
+                center.y,
+                center.z
+            );
+        }
+        if args.on_floor {
+            match &mut model {
+                Some(model) => model.mesh.place_on_floor(),
+                None => log::warn!("--on-floor only moves --gltf models"),
+            }
+        }
 
+        // Every image goes in one array texture, so objects with different textures can be drawn
+        // without switching bind groups. Each instance says which layer it uses.
+        let mut texture_images = crate::texture::decode_images(&TEXTURE_LAYERS);
+        // The model's own texture goes on the end.
+        let model_texture_layer = model
+            .as_ref()
+            .and_then(|model| model.base_color.clone())
+            .map(|image| {
+                texture_images.push(image);
+                texture_images.len() as u32 - 1
+            });
+        let diffuse_texture = Texture::array_from_images(
+            &device,
+            &queue,
+            &texture_images,
+            "diffuse_texture_array",
+            surface_is_srgb,
+            // Transparent objects blend with premultiplied alpha, so the tree's edges don't get
+            // dark fringes.
+            true,
+        )
+        .map_err(StateError::Texture)?;
+
+        let diffuse_format = diffuse_texture.texture.format();
+        let (diffuse_sample_type, diffuse_sampler_type) = crate::texture::sample_binding_types(
+            "diffuse_texture_array",
+            diffuse_format,
+            adapter.get_texture_format_features(diffuse_format),
+        );
         // How the GPU lays out the texture on its side of memory.
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                // This needs two entries:
+                // This needs four entries:
                 entries: &[
                     // Entry 0 is the sampled texture itself.
                     wgpu::BindGroupLayoutEntry {
@@ -176,8 +725,11 @@ impl State {
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            // An array texture, i.e. a stack of same-sized images. Unlike binding
+                            // arrays, these don't need any optional features, so they work on
+                            // WebGL2 too.
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: diffuse_sample_type,
                         },
                         count: None,
                     },
@@ -185,119 +737,707 @@ impl State {
                     wgpu::BindGroupLayoutEntry {
                         binding: 1, // index
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        // This has to match the sample type of the texture entry above, so both
+                        // come from `sample_binding_types`.
+                        ty: wgpu::BindingType::Sampler(diffuse_sampler_type),
                         count: None,
                     },
-                    // Entry 2/3 are like 0/1
+                    // Entries 2 and 3 are the shadow map, and the sampler which compares its
+                    // depths with the fragment's.
                     wgpu::BindGroupLayoutEntry {
-                        binding: 2, // index
-                        // This binding should be visible to the fragment shader, because it's
-                        // used to colour the pixels.
+                        binding: 2,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
                             view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            sample_type: wgpu::TextureSampleType::Depth,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 3, // index
+                        binding: 3,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
                         count: None,
                     },
                 ],
                 label: Some("texture_bind_group_layout"),
             });
 
-        // Conforms to the Bind Group Layout defined above.
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_textures[0].view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_textures[0].sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_textures[1].view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_textures[1].sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
-        });
+        let mut camera = Camera::new(
+            args.aspect
+                .unwrap_or(size.width as f32 / size.height as f32),
+        );
+        // Fill the window with the model, unless there are lots of copies of it all over the place.
+        let model_bbox = model.as_ref().and_then(|model| model.mesh.bounding_box());
+        if let (Some(bbox), None | Some(0)) = (model_bbox, args.scatter) {
+            camera.frame(&bbox);
+        }
+        camera.reverse_z = args.reverse_z;
+        if args.reverse_z {
+            log::info!("Using reversed Z");
+        }
+        // The camera and light get set every frame, so they're the ones worth a ring.
+        let uniform_ring = args.uniform_ring.unwrap_or(1);
+        if uniform_ring > 1 {
+            log::info!("Cycling per-frame uniforms through {uniform_ring} buffers");
+        }
+        let camera_uniform = Uniform::with_ring(
+            &device,
+            CameraUniform::from(&camera),
+            // The fragment shader needs the camera's position for specular highlights.
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            "camera_uniform",
+            uniform_ring,
+        );
 
-        let boring_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Boring Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-        });
+        let light = Light::default();
+        let light_uniform = Uniform::with_ring(
+            &device,
+            LightUniform::from(&light),
+            // The shadow pass needs the light's view in its vertex shader.
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            "light_uniform",
+            uniform_ring,
+        );
+
+        let (vertices, indices) = if args.tiling {
+            (TILING_QUAD_VERTICES, TILING_QUAD_INDICES)
+        } else {
+            (VERTICES, INDICES)
+        };
+        let mut vertices = vertices.to_vec();
+        if args.vertex_colors && !args.tiling {
+            for (vertex, color) in vertices.iter_mut().zip(VERTEX_COLORS) {
+                vertex.color = color;
+            }
+        }
+        let (main_name, main_mesh) = match model {
+            Some(model) => ("Model", model.mesh),
+            None => (
+                "Pentagon",
+                Mesh::new(vertices, indices.iter().copied().map(u32::from).collect())
+                    .with_computed_flat_normals(),
+            ),
+        };
+
+        // An empty instance buffer can't be bound, so --scatter 0 is the same as no --scatter.
+        let scatter = match args.scatter {
+            Some(count) if count > 0 => {
+                Some(ScatterBuilder::new(count).texture_layers(TEXTURE_LAYERS.len() as u32))
+            }
+            _ => None,
+        };
+        let seed = args.seed.unwrap_or(DEFAULT_SEED);
+        // Usually just one pentagon in the middle, unless --scatter asks for lots.
+        let mut instances = match &scatter {
+            Some(scatter) => scatter_layout(scatter, seed),
+            // The tiling demo shows off the tree, because it's obvious where its edges are.
+            _ if args.tiling => vec![Instance {
+                texture_layer: TREE_LAYER,
+                ..Instance::identity()
+            }],
+            _ => vec![Instance::identity()],
+        };
+        use_model_texture(&mut instances, model_texture_layer);
+        let vertex_layout = args.vertex_layout.unwrap_or_default();
+        if vertex_layout != VertexLayout::Interleaved {
+            log::info!("Vertex layout: {vertex_layout:?}");
+        }
+        let topology = args
+            .topology
+            .unwrap_or(wgpu::PrimitiveTopology::TriangleList);
+        log::info!("Topology: {topology:?}");
+        let mut model_uniforms = ModelUniforms::new(&device);
+        let mut main_object = SceneObject::new(
+            &device,
+            &mut model_uniforms,
+            main_name,
+            &main_mesh,
+            vertex_layout,
+            topology,
+            &instances,
+            Matrix4::identity(),
+            [1.0; 4],
+        );
+        // Unless --no-spin turns spinning off altogether.
+        main_object.spins = true;
+        let mut transparent_objects = if args.transparent_quads {
+            crate::scene::transparent_quads(&device, &mut model_uniforms, vertex_layout, topology)
+        } else {
+            Vec::new()
+        };
+        let mut opaque_objects = vec![main_object];
+        // Something reaching past the far plane, to show what unclipped depth does.
+        if args.unclipped_depth {
+            opaque_objects.push(crate::scene::far_strip(
+                &device,
+                &mut model_uniforms,
+                vertex_layout,
+                topology,
+                camera.zfar,
+            ));
+        }
+        if args.ground {
+            opaque_objects.push(crate::scene::ground(
+                &device,
+                &mut model_uniforms,
+                vertex_layout,
+                topology,
+            ));
+        }
+        // A cube on the ground, to cast a shadow.
+        if args.shadows {
+            opaque_objects.extend(crate::scene::shadow_demo(
+                &device,
+                &mut model_uniforms,
+                vertex_layout,
+                topology,
+            ));
+        }
+        if args.indirect {
+            // WebGL can't read draw parameters from a buffer.
+            let supported = adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(wgpu::DownlevelFlags::INDIRECT_EXECUTION);
+            if supported {
+                log::info!("Using indirect draws");
+                for object in opaque_objects.iter_mut().chain(&mut transparent_objects) {
+                    object.use_indirect_draw(&device);
+                }
+            } else {
+                log::warn!("This adapter doesn't support indirect draws, using direct ones");
+            }
+        }
+
+        // --shader replaces the built-in shader, unless it can't be loaded. Then the built-in one
+        // is still there, so the window shows something.
+        let custom_shader = match &args.shader {
+            Some(path) => {
+                match Shader::from_file(&device, path, &args.defines, args.dump_shader_info).await {
+                    Ok(shader) => {
+                        log::info!("Using shader {}", path.display());
+                        Some(shader)
+                    }
+                    Err(e) => {
+                        log::warn!("Using the built-in shader instead, because {e}");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let boring_shader = match custom_shader {
+            Some(shader) => shader,
+            None => {
+                let mut shader = Shader::from_wgsl(
+                    &device,
+                    "shader.wgsl",
+                    include_str!("shader.wgsl"),
+                    None,
+                    &args.defines,
+                    args.dump_shader_info,
+                )
+                .await
+                .map_err(StateError::Shader)?;
+                shader.name = BUILT_IN_SHADER_NAME.to_owned();
+                shader
+            }
+        };
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout],
+                // Indices in this list become the @group(n) numbers in the shader.
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &model_uniforms.layout,
+                    &camera_uniform.layout,
+                    &light_uniform.layout,
+                ],
                 push_constant_ranges: &[],
             });
 
-        let render_pipelines = vec![create_pipeline(
+        let hud = Hud::new(
             &device,
-            &render_pipeline_layout,
-            &boring_shader,
-            &surface_config,
-        )];
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
+            size.width,
+            size.height,
+            surface_config.format,
+            window.scale_factor(),
+            quirks.frame_capture,
+        )
+        .map_err(StateError::Font)?;
+        let frame_graph = FrameGraph::new(
+            &device,
+            size.width,
+            size.height,
+            surface_config.format,
+            window.scale_factor(),
+        );
+
+        let render_resolution = RenderResolution::from_args(args);
+        let render_size = render_resolution.size(size, device.limits().max_texture_dimension_2d);
+        if render_size != size {
+            log::info!(
+                "Rendering at {}x{}, then scaling to fit the window",
+                render_size.width,
+                render_size.height
+            );
+        }
+        let upscale_filter = args.upscale_filter.unwrap_or(wgpu::FilterMode::Linear);
+        let hdr = if args.safe_mode {
+            None
+        } else if crate::hdr::is_supported(&adapter) {
+            Some(HdrPipeline::new(
+                &device,
+                surface_config.format,
+                render_size.width,
+                render_size.height,
+                upscale_filter,
+            ))
+        } else {
+            log::warn!(
+                "Adapter can't render to {:?}, so rendering straight to the surface without HDR",
+                crate::hdr::HDR_FORMAT
+            );
+            None
+        };
+        let blit = (hdr.is_none() && render_resolution != RenderResolution::Window).then(|| {
+            BlitPipeline::new(
+                &device,
+                surface_config.format,
+                render_size.width,
+                render_size.height,
+                upscale_filter,
+            )
         });
+        // The scene pipelines draw into the HDR texture if there is one, otherwise the surface
+        // (or the blit texture, which has the same format).
+        let scene_format = if hdr.is_some() {
+            crate::hdr::HDR_FORMAT
+        } else {
+            surface_config.format
+        };
+
+        let sample_count = choose_sample_count(&adapter, scene_format, args.msaa.unwrap_or(1));
+        let multisampled_view =
+            create_multisampled_view(&device, render_size, scene_format, sample_count);
+        let depth_texture = Texture::create_depth_texture(
+            &device,
+            render_size.width,
+            render_size.height,
+            sample_count,
+            "depth_texture",
+        );
+        let (normals_view, multisampled_normals_view) = if args.normals_target {
+            log::info!("Writing normals to a second colour target");
+            create_normals_views(&device, render_size, sample_count)
+        } else {
+            (None, None)
+        };
 
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(INDICES),
-            usage: wgpu::BufferUsages::INDEX,
+        let mut background = Background::new(
+            &device,
+            crate::color::srgb_to_linear(args.gradient_top.unwrap_or(GRADIENT_TOP)),
+            crate::color::srgb_to_linear(args.gradient_bottom.unwrap_or(GRADIENT_BOTTOM)),
+            scene_format,
+            sample_count,
+            args.normals_target,
+        );
+        background.enabled = args.gradient;
+
+        let skybox_faces = crate::skybox::load_faces(args.skybox_dir.as_deref());
+        let create_skybox = |faces: &[image::DynamicImage]| {
+            Skybox::new(
+                &device,
+                &queue,
+                faces,
+                surface_is_srgb,
+                scene_format,
+                sample_count,
+                args.normals_target,
+            )
+        };
+        let mut skybox = match create_skybox(&skybox_faces) {
+            Ok(skybox) => skybox,
+            // Only --skybox-dir's faces can be the wrong shape, so the built-in ones will do.
+            Err(e) => {
+                log::warn!("Couldn't create the skybox ({e}), using the built-in faces instead");
+                create_skybox(&crate::skybox::load_faces(None)).map_err(StateError::Texture)?
+            }
+        };
+        skybox.enabled = args.skybox;
+
+        let pipeline_options = PipelineOptions {
+            target_format: scene_format,
+            sample_count,
+            cull_mode: Some(wgpu::Face::Back),
+            vertex_layout,
+            topology,
+            normals_target: args.normals_target,
+            reverse_z: args.reverse_z,
+            alpha_to_coverage: false,
+            unclipped_depth: args.unclipped_depth && !features.is_empty(),
+            sample_shading: false,
+        };
+        let shadow_map = ShadowMap::new(
+            &device,
+            args.shadows,
+            &light_uniform.layout,
+            &model_uniforms,
+            &vertex_buffer_layouts(pipeline_options),
+            primitive_state(pipeline_options, false),
+        );
+        // Conforms to the Bind Group Layout defined above.
+        // Make one for each address mode up front, so switching between them is instant.
+        let diffuse_bind_groups = ADDRESS_MODES
+            .iter()
+            .map(|&address_mode| {
+                let sampler = crate::texture::create_sampler(
+                    &device,
+                    address_mode,
+                    diffuse_sampler_type == wgpu::SamplerBindingType::Filtering,
+                );
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &texture_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&shadow_map.texture.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::Sampler(&shadow_map.texture.sampler),
+                        },
+                    ],
+                    label: Some("diffuse_bind_group"),
+                })
+            })
+            .collect();
+        let grid = Grid::new(
+            &device,
+            &render_pipeline_layout,
+            scene_format,
+            sample_count,
+            args.normals_target,
+            depth_compare(pipeline_options),
+        );
+        let bounding_boxes = BoundingBoxes::new(
+            &device,
+            &render_pipeline_layout,
+            scene_format,
+            sample_count,
+            args.normals_target,
+            depth_compare(pipeline_options),
+        );
+        let wireframe = Wireframe::new(
+            &device,
+            &camera_uniform.layout,
+            &model_uniforms,
+            scene_format,
+            sample_count,
+            args.normals_target,
+            primitive_state(pipeline_options, false),
+            depth_compare(pipeline_options),
+        );
+        let point_sprites = args.point_size.map(|size| {
+            PointSprites::new(
+                &device,
+                &main_mesh,
+                &instances,
+                &camera_uniform.layout,
+                &model_uniforms,
+                size,
+                scene_format,
+                sample_count,
+                args.normals_target,
+                depth_compare(pipeline_options),
+            )
+        });
+        let overdraw = Overdraw::new(
+            &device,
+            &render_pipeline_layout,
+            &vertex_buffer_layouts(pipeline_options),
+            // Like the transparent objects, nothing's culled, so back faces get counted too.
+            primitive_state(pipeline_options, true),
+            surface_config.format,
+            render_size.width,
+            render_size.height,
+        );
+        let mut shaders = vec![boring_shader];
+        // Extra shaders get their own pipelines, so Space cycles through them too.
+        if let Some(dir) = &args.shader_dir {
+            shaders
+                .extend(Shader::load_dir(&device, dir, &args.defines, args.dump_shader_info).await);
+        }
+        let [render_pipelines, transparent_pipelines] = [false, true].map(|transparent| {
+            create_pipelines(
+                &device,
+                &render_pipeline_layout,
+                &shaders,
+                pipeline_options,
+                &depth_texture,
+                transparent,
+            )
         });
+        let mut uv_shaders = Vec::new();
+        for fs_entry in ["fs_main", "fs_flipped"] {
+            let mut shader = Shader::from_wgsl(
+                &device,
+                "uv_debug.wgsl",
+                include_str!("uv_debug.wgsl"),
+                None,
+                &args.defines,
+                false,
+            )
+            .await
+            .map_err(StateError::Shader)?;
+            shader.fs_entry = fs_entry.to_owned();
+            uv_shaders.push(shader);
+        }
+        let uv_pipelines = create_pipelines(
+            &device,
+            &render_pipeline_layout,
+            &uv_shaders,
+            pipeline_options,
+            &depth_texture,
+            false,
+        );
+
+        let particles = match args.particles {
+            // Zero particles would mean binding a zero-sized buffer, which wgpu rejects.
+            Some(0) | None => None,
+            Some(n) if crate::particles::is_supported(&adapter) => Some(ParticleSystem::new(
+                &device,
+                n,
+                scene_format,
+                sample_count,
+                args.normals_target,
+            )),
+            Some(_) => {
+                log::warn!(
+                    "Particles need compute shaders, which this adapter (e.g. WebGL) doesn't \
+                     support. Running without them."
+                );
+                None
+            }
+        };
+
+        // Start the tiling demo off on Repeat (ADDRESS_MODES[1]), otherwise it just looks like
+        // a stretched-out tree.
+        let address_mode = if args.tiling { 1 } else { 0 };
+        let color = wgpu::Color {
+            a: clear_alpha,
+            ..BLUE
+        };
+        let initial = InitialState {
+            color,
+            camera,
+            pipeline_options,
+            address_mode,
+            gradient: background.enabled,
+            skybox: skybox.enabled,
+            seed,
+        };
 
-        Self {
+        Ok(Some(Self {
             window,
             surface,
             device,
             queue,
+            instance,
+            adapter,
             surface_config,
-            size,
-            color: BLUE,
+            render_resolution,
+            render_size,
+            pending_size: None,
+            color,
             render_pipelines,
-            active_texture: 0,
-            vertex_buffer,
-            index_buffer,
-            num_indices: INDICES.len() as u32,
-            diffuse_bind_group,
-            diffuse_textures,
-        }
+            transparent_pipelines,
+            render_pipeline_layout,
+            shaders,
+            uv_shaders,
+            uv_pipelines,
+            uv_debug: false,
+            flip_v: false,
+            pipeline_options,
+            active_pipeline: 0,
+            opaque_objects,
+            transparent_objects,
+            model_uniforms,
+            selected_object: 0,
+            camera,
+            camera_goal: camera,
+            camera_damping: args.camera_damping.unwrap_or(CAMERA_DAMPING),
+            camera_uniform,
+            camera_controller: CameraController::new(CAMERA_SPEED),
+            demo: args.demo,
+            // So the orbit starts straight away.
+            idle: DEMO_IDLE_TIMEOUT,
+            light,
+            light_uniform,
+            shadow_map,
+            depth_texture,
+            diffuse_bind_groups,
+            address_mode,
+            diffuse_texture,
+            spin: !args.no_spin,
+            spin_angle: Rad(0.0),
+            previous_spin_angle: Rad(0.0),
+            previous_camera: camera,
+            hdr,
+            blit,
+            hue_cycle: false,
+            hue: 0.0,
+            mouse_color: false,
+            cursor_position: PhysicalPosition::new(0.0, 0.0),
+            frame_latency: args.frame_latency,
+            in_flight: VecDeque::new(),
+            particles,
+            pressed_keys: HashSet::new(),
+            multisampled_view,
+            normals_view,
+            multisampled_normals_view,
+            hud,
+            frame_graph,
+            background,
+            skybox,
+            grid,
+            bounding_boxes,
+            wireframe,
+            point_sprites,
+            overdraw,
+            loupe: Loupe::new(
+                args.loupe_zoom.unwrap_or(crate::loupe::DEFAULT_ZOOM),
+                args.loupe_size.unwrap_or(crate::loupe::DEFAULT_SIZE),
+            ),
+            aspect: args.aspect,
+            consecutive_render_errors: 0,
+            trails: false,
+            clear_next_frame: true,
+            capture_next_frame: false,
+            quirks,
+            frustum_culling: !args.no_cull && !args.shadows,
+            culled: 0,
+            occluded: false,
+            scatter,
+            seed,
+            model_texture_layer,
+            recorder,
+            initial,
+        }))
     }
 
     pub fn window(&self) -> &Window {
         &self.window
     }
 
-    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+    /// The clear colour, premultiplied by its alpha like everything else drawn in the scene. Only
+    /// makes a difference with --transparent, otherwise alpha is 1.
+    fn clear_color(&self) -> wgpu::Color {
+        let wgpu::Color { r, g, b, a } = self.color;
+        wgpu::Color {
+            r: r * a,
+            g: g * a,
+            b: b * a,
+            a,
+        }
+    }
+
+    /// The size the surface is configured at, which is the window's size as of the last `resize`.
+    fn current_size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.surface_config.width, self.surface_config.height)
+    }
+
+    /// The format of the texture the scene gets drawn into.
+    fn scene_format(&self) -> wgpu::TextureFormat {
+        if self.hdr.is_some() {
+            crate::hdr::HDR_FORMAT
+        } else {
+            self.surface_config.format
+        }
+    }
+
+    /// Resize at the start of the next update, rather than right now.
+    fn request_resize(&mut self, new_size: PhysicalSize<u32>) {
+        self.pending_size = Some(new_size);
+    }
+
+    /// The only place the window size gets changed. Everything sized to match it (the surface,
+    /// render targets, camera aspect and HUD) gets updated together.
+    fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
             self.surface_config.width = new_size.width;
             self.surface_config.height = new_size.height;
             self.surface.configure(&self.device, &self.surface_config);
+            let render_size = self
+                .render_resolution
+                .size(new_size, self.device.limits().max_texture_dimension_2d);
+            if render_size != self.render_size {
+                self.resize_render_targets(render_size);
+            }
+            // When letterboxed the scene's shape never changes, only the black bars do.
+            self.camera_goal.aspect = self
+                .aspect
+                .unwrap_or(new_size.width as f32 / new_size.height as f32);
+            self.camera.aspect = self.camera_goal.aspect;
+            self.hud.resize(
+                &self.queue,
+                new_size.width,
+                new_size.height,
+                self.window.scale_factor(),
+            );
+            self.frame_graph.resize(
+                &self.queue,
+                new_size.width,
+                new_size.height,
+                self.window.scale_factor(),
+            );
+        }
+    }
+
+    /// Recreate every texture the scene renders into at the new render resolution. With
+    /// --internal-res that never happens, however the window gets resized.
+    fn resize_render_targets(&mut self, render_size: PhysicalSize<u32>) {
+        self.render_size = render_size;
+        // The new textures start out empty.
+        self.clear_next_frame = true;
+        let PhysicalSize { width, height } = render_size;
+        if let Some(hdr) = &mut self.hdr {
+            hdr.resize(&self.device, width, height);
+        }
+        if let Some(blit) = &mut self.blit {
+            blit.resize(&self.device, width, height);
+        }
+        self.overdraw.resize(&self.device, width, height);
+        self.multisampled_view = create_multisampled_view(
+            &self.device,
+            render_size,
+            self.scene_format(),
+            self.pipeline_options.sample_count,
+        );
+        self.depth_texture = Texture::create_depth_texture(
+            &self.device,
+            width,
+            height,
+            self.pipeline_options.sample_count,
+            "depth_texture",
+        );
+        if self.pipeline_options.normals_target {
+            (self.normals_view, self.multisampled_normals_view) = create_normals_views(
+                &self.device,
+                render_size,
+                self.pipeline_options.sample_count,
+            );
         }
     }
 
@@ -308,243 +1448,2027 @@ impl State {
         false
     }
 
-    fn update(&mut self) {
-        // This is where we would e.g. move objects. But there's nothing to do yet.
+    /// Recreate every scene pipeline, e.g. after changing `pipeline_options`.
+    fn rebuild_pipelines(&mut self) {
+        let [opaque, transparent] = [false, true].map(|transparent| {
+            create_pipelines(
+                &self.device,
+                &self.render_pipeline_layout,
+                &self.shaders,
+                self.pipeline_options,
+                &self.depth_texture,
+                transparent,
+            )
+        });
+        self.render_pipelines = opaque;
+        self.transparent_pipelines = transparent;
+        self.uv_pipelines = create_pipelines(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.uv_shaders,
+            self.pipeline_options,
+            &self.depth_texture,
+            false,
+        );
+        self.wireframe.rebuild_pipeline(
+            &self.device,
+            self.pipeline_options.target_format,
+            self.pipeline_options.sample_count,
+            self.pipeline_options.normals_target,
+            primitive_state(self.pipeline_options, false),
+            depth_compare(self.pipeline_options),
+        );
     }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        // Get a frame to render to. Wait for the surface to provide a SurfaceTexture (frame),
-        // which we'll render to.
-        let output = self.surface.get_current_texture()?;
-        // Controls how the render code interacts with the texture.
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        // Create a CommandEncoder which creates the actual commands sent to the GPU.
-        // Modern graphics frameworks expect cmds to be stored in a cmdbuf, before being sent to GPU.
-        // (presumably to minimize IO overhead). So, build the cmdbuf.
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+    /// The name of the pipeline being drawn with, i.e. of the shader it was built from.
+    pub fn active_pipeline_name(&self) -> &str {
+        &self.render_pipelines[self.active_pipeline].name
+    }
 
-        // Clear the screen. Start a new block, because `render_pass` holds a &mut to `encoder`.
-        // This way when render_pass is dropped, encoder becomes usable again.
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                // Describe where to draw the color to.
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    // Same as view, unless multisampling is used.
-                    resolve_target: None,
-                    // What to do with the colours on the screen.
-                    ops: wgpu::Operations {
-                        // 'load' field is what to do with colours stored from previous frame.
-                        load: wgpu::LoadOp::Clear(self.color),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
+    /// Show the active pipeline in the window's title, so it's clear what Space switched to.
+    fn update_title(&self) {
+        self.window
+            .set_title(&format!("{WINDOW_TITLE} - {}", self.active_pipeline_name()));
+    }
+
+    /// Adjust the light, and log how it ends up. `update` uploads it.
+    fn change_light(&mut self, change: impl FnOnce(&mut Light)) {
+        change(&mut self.light);
+        let Light {
+            yaw,
+            pitch,
+            intensity,
+            enabled,
+            ambient_strength,
+            specular_strength,
+            shininess,
+        } = self.light;
+        log::info!(
+            "Light: yaw {:.0}°, pitch {:.0}°, intensity {intensity:.2}, {}, ambient \
+             {ambient_strength:.2}, specular {specular_strength:.2}, shininess {shininess}",
+            yaw.0,
+            pitch.0,
+            if enabled { "on" } else { "off" }
+        );
+    }
 
-            render_pass.set_pipeline(&self.render_pipelines[self.active_texture]);
-            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            let buffer_slot = 0;
-            render_pass.set_vertex_buffer(buffer_slot, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    /// Re-scatter the main object's instances with this seed. Does nothing without --scatter.
+    fn set_seed(&mut self, seed: u64) {
+        let Some(scatter) = &self.scatter else {
+            return;
+        };
+        self.seed = seed;
+        let mut instances = scatter_layout(scatter, seed);
+        use_model_texture(&mut instances, self.model_texture_layer);
+        self.opaque_objects[0].set_instances(&self.queue, &instances);
+        if let Some(point_sprites) = &self.point_sprites {
+            point_sprites.set_instances(&self.queue, &instances);
         }
+    }
 
-        // Submit the cmdbuf to the GPU.
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-        Ok(())
+    /// Put everything the keys and mouse can change back how it was at startup, for the Home key.
+    fn reset(&mut self) {
+        // The window may have been resized since, and the camera has to keep up with that.
+        let aspect = self.camera_goal.aspect;
+        self.camera_goal = Camera {
+            aspect,
+            ..self.initial.camera
+        };
+        // Snap straight there rather than easing, so the camera doesn't fly across the scene.
+        self.camera = self.camera_goal;
+        self.previous_camera = self.camera_goal;
+        self.color = self.initial.color;
+        self.hue_cycle = false;
+        self.hue = 0.0;
+        self.mouse_color = false;
+        self.background.enabled = self.initial.gradient;
+        self.skybox.enabled = self.initial.skybox;
+        self.grid.enabled = false;
+        self.bounding_boxes.enabled = false;
+        self.wireframe.reset(&self.queue);
+        self.loupe.enabled = false;
+        self.overdraw.enabled = false;
+        self.trails = false;
+        self.uv_debug = false;
+        self.flip_v = false;
+        self.light = Light::default();
+        self.address_mode = self.initial.address_mode;
+        self.active_pipeline = 0;
+        self.spin_angle = Rad(0.0);
+        self.previous_spin_angle = Rad(0.0);
+        self.selected_object = 0;
+        for object in self
+            .opaque_objects
+            .iter_mut()
+            .chain(self.transparent_objects.iter_mut())
+        {
+            object.reset_tint(&mut self.model_uniforms);
+        }
+        if let Some(hdr) = &mut self.hdr {
+            hdr.reset(&self.queue);
+        }
+        if self.pipeline_options != self.initial.pipeline_options {
+            self.pipeline_options = self.initial.pipeline_options;
+            self.rebuild_pipelines();
+        }
+        if self.scatter.is_some() && self.seed != self.initial.seed {
+            self.set_seed(self.initial.seed);
+        }
+        // `render` uploads the camera and the objects' transforms and tints.
+        self.update_title();
+        log::info!("Reset everything");
     }
-}
 
-fn create_pipeline(
-    device: &wgpu::Device,
-    render_pipeline_layout: &wgpu::PipelineLayout,
-    shader: &wgpu::ShaderModule,
-    surface_config: &wgpu::SurfaceConfiguration,
-) -> wgpu::RenderPipeline {
+    /// Log what's being drawn and how, for the I key.
+    fn log_info(&self) {
+        log::info!(
+            "Pipeline {}/{}: {}",
+            self.active_pipeline + 1,
+            self.render_pipelines.len(),
+            self.active_pipeline_name()
+        );
+        log::info!(
+            "Window {}x{}, rendering at {}x{}, {}x MSAA, culling {:?}",
+            self.current_size().width,
+            self.current_size().height,
+            self.render_size.width,
+            self.render_size.height,
+            self.pipeline_options.sample_count,
+            self.pipeline_options.cull_mode
+        );
+        if self.frustum_culling {
+            log::info!("{} instances outside the camera's view", self.culled);
+        }
+    }
+
+    /// Called when a key goes down. Holding the key doesn't call this again until it's released.
+    fn key_pressed(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Space => {
+                self.active_pipeline += 1;
+                self.active_pipeline %= self.render_pipelines.len();
+                log::info!("Pipeline: {}", self.active_pipeline_name());
+                self.update_title();
+            }
+            VirtualKeyCode::I => self.log_info(),
+            VirtualKeyCode::G => {
+                self.frame_graph.enabled = !self.frame_graph.enabled;
+                log::info!("Frame time graph: {}", self.frame_graph.enabled);
+            }
+            VirtualKeyCode::Home => self.reset(),
+            VirtualKeyCode::Left => self.change_light(|light| light.turn(-1.0)),
+            VirtualKeyCode::Right => self.change_light(|light| light.turn(1.0)),
+            VirtualKeyCode::Up => self.change_light(|light| light.raise(1.0)),
+            VirtualKeyCode::Down => self.change_light(|light| light.raise(-1.0)),
+            VirtualKeyCode::LBracket => self.change_light(|light| light.brighten(-1.0)),
+            VirtualKeyCode::RBracket => self.change_light(|light| light.brighten(1.0)),
+            VirtualKeyCode::L => self.change_light(|light| light.enabled = !light.enabled),
+            VirtualKeyCode::Key1 => self.change_light(|light| light.change_ambient(-1.0)),
+            VirtualKeyCode::Key2 => self.change_light(|light| light.change_ambient(1.0)),
+            VirtualKeyCode::Key3 => self.change_light(|light| light.change_specular(-1.0)),
+            VirtualKeyCode::Key4 => self.change_light(|light| light.change_specular(1.0)),
+            VirtualKeyCode::Key5 => self.change_light(|light| light.change_shininess(-1.0)),
+            VirtualKeyCode::Key6 => self.change_light(|light| light.change_shininess(1.0)),
+            VirtualKeyCode::V => {
+                self.overdraw.enabled = !self.overdraw.enabled;
+                log::info!("Overdraw heatmap: {}", self.overdraw.enabled);
+            }
+            VirtualKeyCode::T => match &mut self.hdr {
+                Some(hdr) => hdr.cycle_tonemap(&self.queue),
+                None => log::warn!("HDR isn't supported, so there's no tonemapping"),
+            },
+            VirtualKeyCode::F4 => match &mut self.hdr {
+                Some(hdr) => hdr.cycle_color_filter(&self.queue),
+                // The filter's applied while tonemapping, so there's nowhere else to put it.
+                None => log::warn!("HDR isn't supported, so there's no colour filter"),
+            },
+            VirtualKeyCode::C => {
+                // Culling is baked into the pipeline, so changing it means a new pipeline.
+                self.pipeline_options.cull_mode = match self.pipeline_options.cull_mode {
+                    Some(wgpu::Face::Back) => Some(wgpu::Face::Front),
+                    Some(wgpu::Face::Front) => None,
+                    None => Some(wgpu::Face::Back),
+                };
+                log::info!("Culling: {:?}", self.pipeline_options.cull_mode);
+                self.rebuild_pipelines();
+            }
+            VirtualKeyCode::K => {
+                // With one sample there's nothing to cover, and wgpu rejects the pipeline.
+                if self.pipeline_options.sample_count == 1 {
+                    log::warn!("Alpha to coverage needs MSAA, try --msaa 4");
+                    return;
+                }
+                self.pipeline_options.alpha_to_coverage = !self.pipeline_options.alpha_to_coverage;
+                log::info!(
+                    "Alpha to coverage: {}",
+                    self.pipeline_options.alpha_to_coverage
+                );
+                self.rebuild_pipelines();
+            }
+            VirtualKeyCode::Z => {
+                let sample_count = self.pipeline_options.sample_count;
+                if sample_count == 1 {
+                    log::warn!("Sample-rate shading needs MSAA, try --msaa 4");
+                    return;
+                }
+                let supported = self
+                    .adapter
+                    .get_downlevel_capabilities()
+                    .flags
+                    .contains(wgpu::DownlevelFlags::MULTISAMPLED_SHADING);
+                if !supported {
+                    log::warn!("This adapter doesn't support sample-rate shading");
+                    return;
+                }
+                self.pipeline_options.sample_shading = !self.pipeline_options.sample_shading;
+                if self.pipeline_options.sample_shading {
+                    log::info!(
+                        "Sample-rate shading: on, fragment shaders run {sample_count} times per \
+                         pixel"
+                    );
+                    let shader = &self.shaders[self.active_pipeline];
+                    if shader.per_sample_entries.is_none() {
+                        log::warn!(
+                            "{} has no vs_per_sample and fs_per_sample, so it still shades once \
+                             per pixel",
+                            shader.name
+                        );
+                    }
+                } else {
+                    log::info!("Sample-rate shading: off, fragment shaders run once per pixel");
+                }
+                self.rebuild_pipelines();
+            }
+            VirtualKeyCode::U => {
+                if !self
+                    .device
+                    .features()
+                    .contains(wgpu::Features::DEPTH_CLIP_CONTROL)
+                {
+                    log::warn!("This adapter doesn't support unclipped depth");
+                    return;
+                }
+                self.pipeline_options.unclipped_depth = !self.pipeline_options.unclipped_depth;
+                log::info!("Unclipped depth: {}", self.pipeline_options.unclipped_depth);
+                self.rebuild_pipelines();
+            }
+            VirtualKeyCode::R => {
+                self.address_mode = (self.address_mode + 1) % ADDRESS_MODES.len();
+                log::info!("Address mode: {:?}", ADDRESS_MODES[self.address_mode]);
+            }
+            VirtualKeyCode::H => self.hud.show_help = !self.hud.show_help,
+            // = is the unshifted +, so it zooms in.
+            VirtualKeyCode::Equals => self.zoom(-FOVY_STEP),
+            VirtualKeyCode::Minus => self.zoom(FOVY_STEP),
+            VirtualKeyCode::B => {
+                self.background.enabled = !self.background.enabled;
+                log::info!("Gradient background: {}", self.background.enabled);
+            }
+            VirtualKeyCode::X => {
+                self.uv_debug = !self.uv_debug;
+                log::info!("Texture coordinates view: {}", self.uv_debug);
+            }
+            VirtualKeyCode::F => {
+                self.flip_v = !self.flip_v;
+                log::info!("Texture coordinates view flips v: {}", self.flip_v);
+            }
+            VirtualKeyCode::J => {
+                self.trails = !self.trails;
+                log::info!("Trails: {}", self.trails);
+                if self.trails && self.hdr.is_none() && self.blit.is_none() {
+                    log::warn!(
+                        "Without HDR or a different render resolution, the scene draws straight \
+                         onto the surface, which doesn't reliably keep the last frame, so trails \
+                         may flicker"
+                    );
+                }
+            }
+            VirtualKeyCode::F12 => match self.quirks.frame_capture {
+                Some(FrameCapture::RenderDoc) => match crate::renderdoc::is_attached() {
+                    Some(false) => {
+                        log::warn!("RenderDoc isn't attached, so there's nothing to capture")
+                    }
+                    attached => {
+                        if attached.is_none() {
+                            log::info!("Can't tell whether RenderDoc is attached, trying anyway");
+                        }
+                        self.capture_next_frame = true;
+                    }
+                },
+                Some(FrameCapture::Xcode) => {
+                    log::info!("Capturing with Xcode, if it's capturing this process");
+                    self.capture_next_frame = true;
+                }
+                None => log::warn!("This backend can't capture frames"),
+            },
+            VirtualKeyCode::N => {
+                if self.scatter.is_some() {
+                    self.set_seed(rand::random());
+                } else {
+                    log::warn!("Only --scatter has a random layout to reroll");
+                }
+            }
+            VirtualKeyCode::E => {
+                self.clear_next_frame = true;
+                log::info!("Clearing the scene");
+            }
+            VirtualKeyCode::Q => {
+                self.grid.enabled = !self.grid.enabled;
+                log::info!("Grid and axes: {}", self.grid.enabled);
+            }
+            VirtualKeyCode::F3 => {
+                self.bounding_boxes.enabled = !self.bounding_boxes.enabled;
+                log::info!("Bounding boxes: {}", self.bounding_boxes.enabled);
+            }
+            VirtualKeyCode::F6 => {
+                self.wireframe.enabled = !self.wireframe.enabled;
+                log::info!("Wireframe: {}", self.wireframe.enabled);
+            }
+            VirtualKeyCode::F7 => {
+                let thickness = self.wireframe.adjust_thickness(&self.queue, -1.0);
+                log::info!("Wireframe thickness: {thickness} pixels");
+            }
+            VirtualKeyCode::F8 => {
+                let thickness = self.wireframe.adjust_thickness(&self.queue, 1.0);
+                log::info!("Wireframe thickness: {thickness} pixels");
+            }
+            VirtualKeyCode::F5 => {
+                if self.hdr.is_none() && self.blit.is_none() {
+                    log::warn!(
+                        "The loupe needs the scene in an offscreen texture, which needs HDR or a \
+                         different render resolution"
+                    );
+                } else {
+                    self.loupe.enabled = !self.loupe.enabled;
+                    log::info!("Loupe: {}", self.loupe.enabled);
+                }
+            }
+            VirtualKeyCode::Y => {
+                self.skybox.enabled = !self.skybox.enabled;
+                log::info!("Skybox: {}", self.skybox.enabled);
+            }
+            VirtualKeyCode::P => {
+                self.hue_cycle = !self.hue_cycle;
+                log::info!("Hue cycling clear colour: {}", self.hue_cycle);
+            }
+            VirtualKeyCode::M => {
+                self.mouse_color = !self.mouse_color;
+                log::info!("Mouse sets clear colour: {}", self.mouse_color);
+            }
+            VirtualKeyCode::Tab => {
+                self.selected_object =
+                    (self.selected_object + 1) % self.model_uniforms.slots_used();
+                let selected = self.selected_object;
+                let object = self
+                    .opaque_objects
+                    .iter()
+                    .chain(&self.transparent_objects)
+                    .find(|object| object.slot() == selected);
+                if let Some(object) = object {
+                    log::info!("Selected {}", object.label());
+                }
+            }
+            VirtualKeyCode::O => {
+                let selected = self.selected_object;
+                let object = self
+                    .opaque_objects
+                    .iter_mut()
+                    .chain(self.transparent_objects.iter_mut())
+                    .find(|object| object.slot() == selected);
+                if let Some(object) = object {
+                    let tint = object.next_palette_tint(&mut self.model_uniforms);
+                    log::info!("{} tint: {tint:?}", object.label());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// React to a live or replayed input event. Quitting is up to the caller.
+    fn handle_input(&mut self, event: InputEvent) {
+        if self.demo && event.is_user_input() {
+            if self.idle >= DEMO_IDLE_TIMEOUT {
+                log::info!(
+                    "Demo mode: pausing the orbit until there's been no input for {}s",
+                    DEMO_IDLE_TIMEOUT.as_secs()
+                );
+            }
+            self.idle = Duration::ZERO;
+        }
+        match event {
+            // While a key is held, the OS keeps sending Pressed events (key repeat).
+            // `insert` returns false for those, because the key is already in the set.
+            InputEvent::KeyPressed(key) => {
+                if self.pressed_keys.insert(key) {
+                    self.key_pressed(key);
+                }
+            }
+            InputEvent::KeyReleased(key) => {
+                self.pressed_keys.remove(&key);
+            }
+            // Keys released while the window is in the background never send Released, so
+            // forget about everything when focus is lost. Otherwise those keys would get stuck.
+            InputEvent::FocusLost => self.pressed_keys.clear(),
+            InputEvent::Resized(size) => self.request_resize(size),
+            InputEvent::ScrolledLines(lines) => {
+                self.scroll(MouseScrollDelta::LineDelta(0.0, lines))
+            }
+            InputEvent::ScrolledPixels(pixels) => self.scroll(MouseScrollDelta::PixelDelta(
+                PhysicalPosition::new(0.0, pixels),
+            )),
+            InputEvent::CursorMoved(position) => {
+                self.cursor_position = position;
+                if !self.mouse_color {
+                    return;
+                }
+                // The mouse takes over the clear colour again.
+                self.hue_cycle = false;
+                let size = self.current_size();
+                let percent_of_screen_width = position.x / size.width as f64;
+                let percent_of_screen_height = position.y / size.height as f64;
+                self.color = wgpu::Color {
+                    r: percent_of_screen_width,
+                    g: percent_of_screen_height,
+                    ..self.color
+                };
+            }
+            InputEvent::LeftClicked => self.log_click(),
+            InputEvent::CloseRequested => {}
+        }
+    }
+
+    /// Log where the cursor is, in window pixels, normalized device coordinates, and as a ray
+    /// into the world, for left clicks.
+    fn log_click(&self) {
+        let PhysicalPosition { x, y } = self.cursor_position;
+        let size = self.current_size();
+        // The scene only covers the viewport when letterboxing, so NDC are relative to that.
+        let [left, top, width, height] = match self.aspect {
+            Some(aspect) => letterbox(size.width, size.height, aspect),
+            None => [0.0, 0.0, size.width as f32, size.height as f32],
+        };
+        // Window pixels have y pointing down, NDC have it pointing up.
+        let ndc_x = (x as f32 - left) / width * 2.0 - 1.0;
+        let ndc_y = 1.0 - (y as f32 - top) / height * 2.0;
+        log::info!("Clicked pixel ({x:.0}, {y:.0}), NDC ({ndc_x:.3}, {ndc_y:.3})");
+        if !(-1.0..=1.0).contains(&ndc_x) || !(-1.0..=1.0).contains(&ndc_y) {
+            log::info!("That's outside the scene, in the letterbox bars");
+            return;
+        }
+        let (origin, direction) = self.camera.ray_through([ndc_x, ndc_y]);
+        log::info!(
+            "Ray from ({:.3}, {:.3}, {:.3}) towards ({:.3}, {:.3}, {:.3})",
+            origin.x,
+            origin.y,
+            origin.z,
+            direction.x,
+            direction.y,
+            direction.z
+        );
+    }
+
+    /// Change the camera's field of view. `update` uploads the new projection.
+    fn zoom(&mut self, delta: Deg<f32>) {
+        self.camera_goal.change_fovy(delta);
+        log::info!("Field of view: {:.0}°", self.camera_goal.fovy.0);
+    }
+
+    /// Zoom with the scroll wheel, unless the camera is orbiting (A/D held), because then the
+    /// zoom would fight with the orbit.
+    fn scroll(&mut self, delta: MouseScrollDelta) {
+        if self.pressed_keys.contains(&VirtualKeyCode::A)
+            || self.pressed_keys.contains(&VirtualKeyCode::D)
+        {
+            return;
+        }
+        let notches = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            // Touchpads scroll in pixels. Call every 50 pixels one notch.
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+        };
+        // Scrolling up (positive) zooms in, i.e. narrows the field of view.
+        self.zoom(-FOVY_STEP * notches);
+    }
+
+    /// Things which happen once per frame, however many updates there are. `dt` is the real time
+    /// since the last frame.
+    fn begin_frame(&mut self, dt: Duration) {
+        // Before anything else, so this frame renders at the new size.
+        if let Some(new_size) = self.pending_size.take() {
+            self.resize(new_size);
+        }
+        self.hud.update(&self.device, &self.queue, dt);
+        self.frame_graph.update(&self.queue, dt);
+    }
+
+    /// Orbit the camera, if nobody's touched anything for long enough. Moves the goal, so the
+    /// camera eases into the orbit when it resumes, and out of it when someone takes over.
+    fn update_demo(&mut self, dt: Duration) {
+        // Held keys count as input for as long as they're held, not just when pressed.
+        if !self.pressed_keys.is_empty() {
+            self.idle = Duration::ZERO;
+            return;
+        }
+        let was_idle = self.idle >= DEMO_IDLE_TIMEOUT;
+        self.idle += dt;
+        if self.idle < DEMO_IDLE_TIMEOUT {
+            return;
+        }
+        if !was_idle {
+            log::info!("Demo mode: orbiting again");
+        }
+        demo_orbit(&mut self.camera_goal, dt);
+    }
+
+    /// Whether anything would look different next frame even if no more events came in, e.g.
+    /// because something's spinning or the camera is still easing. With --power-save, the event
+    /// loop only redraws continuously while this is true.
+    fn is_animating(&self) -> bool {
+        self.spin
+            || self.hue_cycle
+            || self.particles.is_some()
+            || self.recorder.is_some()
+            // Waiting to orbit counts, or the orbit would never resume.
+            || self.demo
+            // Held keys keep moving the camera.
+            || !self.pressed_keys.is_empty()
+            // The latest update moved it, so it hasn't settled on its goal yet.
+            || self.camera != self.previous_camera
+    }
+
+    /// Move objects around. `dt` is how long since the last update, so movement should be
+    /// proportional to it. Otherwise things would move faster on faster computers.
+    fn update(&mut self, dt: Duration) {
+        self.previous_camera = self.camera;
+        self.previous_spin_angle = self.spin_angle;
+        self.camera_controller
+            .update_camera(&mut self.camera_goal, &self.pressed_keys, dt);
+        if self.demo {
+            self.update_demo(dt);
+        }
+        self.camera
+            .ease_towards(&self.camera_goal, self.camera_damping, dt);
+        self.light_uniform.set(LightUniform::from(&self.light));
+        if self.spin {
+            self.spin_angle = advance_spin(self.spin_angle, dt);
+        }
+        // Particles aren't interpolated, so with --fixed-timestep they move at the update rate.
+        if let Some(particles) = &mut self.particles {
+            particles.update(&self.queue, dt);
+        }
+        if self.hue_cycle {
+            self.hue = (self.hue + dt.as_secs_f64() / HUE_CYCLE_PERIOD_SECS).rem_euclid(1.0);
+            let [r, g, b] = crate::color::hsv_to_rgb(self.hue, 0.7, 0.4);
+            self.color = wgpu::Color {
+                r,
+                g,
+                b,
+                ..self.color
+            };
+        }
+        if self.aspect.is_some() {
+            // The clear colour is drawn as a fullscreen triangle when letterboxing.
+            self.background
+                .set_flat_color(&self.queue, self.clear_color());
+        }
+    }
+
+    /// `alpha` says how far between the previous update and the latest one to draw things, from 0
+    /// to 1. Without --fixed-timestep it's always 1, i.e. exactly where the latest update put them.
+    fn render(&mut self, alpha: f32) -> Result<(), wgpu::SurfaceError> {
+        let camera = self.camera.interpolate(&self.previous_camera, alpha);
+        self.camera_uniform.set(CameraUniform::from(&camera));
+        // Once a frame, however many updates there were, and only if they changed anything.
+        self.camera_uniform.flush(&self.queue);
+        self.light_uniform.flush(&self.queue);
+        self.skybox.set_camera(&self.queue, &camera);
+        // Sized in the viewport's pixels, which with letterboxing isn't the whole render target.
+        let [_, _, width, height] = self.letterbox_viewport().unwrap_or([
+            0.0,
+            0.0,
+            self.render_size.width as f32,
+            self.render_size.height as f32,
+        ]);
+        if let Some(point_sprites) = &mut self.point_sprites {
+            point_sprites.set_viewport(&self.queue, width, height);
+        }
+        // The camera may have moved, so what's at the back may have changed.
+        crate::scene::sort_back_to_front(&mut self.transparent_objects, &camera);
+        self.spin_objects(alpha);
+        let culled = self.cull_objects(&camera);
+        if culled != self.culled {
+            log::debug!("Culled {culled} instances");
+            self.culled = culled;
+        }
+
+        // Get a frame to render to. Wait for the surface to provide a SurfaceTexture (frame),
+        // which we'll render to.
+        let output = self.surface.get_current_texture()?;
+        // Controls how the render code interacts with the texture.
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        // Create a CommandEncoder which creates the actual commands sent to the GPU.
+        // Modern graphics frameworks expect cmds to be stored in a cmdbuf, before being sent to GPU.
+        // (presumably to minimize IO overhead). So, build the cmdbuf.
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+
+        // Compute passes can't run inside a render pass, so move the particles first.
+        if let Some(particles) = &self.particles {
+            particles.compute(&mut encoder);
+        }
+
+        // Before the scene, which reads it.
+        self.shadow_map.render(
+            &mut encoder,
+            self.light_uniform.bind_group(),
+            &self.model_uniforms,
+            &self.opaque_objects,
+        );
+        if self.overdraw.enabled {
+            self.encode_overdraw(&mut encoder, &view);
+        } else {
+            self.encode_scene(&mut encoder, &view);
+            self.clear_next_frame = false;
+        }
+        // After tonemapping, so it's drawn in the surface's own pixels.
+        self.frame_graph.draw(&mut encoder, &view);
+
+        // The HUD draws in its own pass, after the scene and tonemapping, straight onto the surface.
+        let hud_commands = self.hud.draw(&self.device, &view);
+
+        let mut command_buffers = vec![encoder.finish(), hud_commands];
+        // Recording copies the finished frame, HUD and all, so it goes after everything else.
+        if let Some(recorder) = &mut self.recorder {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Record Encoder"),
+                });
+            recorder.capture(
+                &self.device,
+                &mut encoder,
+                &output.texture,
+                self.surface_config.width,
+                self.surface_config.height,
+            );
+            command_buffers.push(encoder.finish());
+        }
+
+        // Submit the cmdbufs to the GPU. They run in order, so the text ends up on top.
+        let submission = self.queue.submit(command_buffers);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.after_submit(&self.device);
+            if recorder.is_done_capturing() {
+                recorder.finish(&self.device);
+                self.recorder = None;
+            }
+        }
+        if let Some(frame_latency) = self.frame_latency {
+            // Block until the GPU catches up. Less input latency, but maybe lower throughput.
+            self.in_flight.push_back(submission);
+            while self.in_flight.len() > frame_latency as usize {
+                let oldest = self.in_flight.pop_front().unwrap();
+                self.device
+                    .poll(wgpu::Maintain::WaitForSubmissionIndex(oldest));
+            }
+        }
+        output.present();
+        Ok(())
+    }
+
+    /// Turn the spinning objects to where they are at `alpha` (see `render`), and upload every
+    /// object's transform and tint.
+    fn spin_objects(&mut self, alpha: f32) {
+        let spin_angle = interpolate_angle(self.previous_spin_angle, self.spin_angle, alpha);
+        for object in self.opaque_objects.iter_mut().filter(|o| o.spins) {
+            object.spin(&mut self.model_uniforms, spin_angle);
+        }
+        self.model_uniforms.flush(&self.queue);
+    }
+
+    /// Leave out every instance `camera` can't see, until the next call. Returns how many that
+    /// was. Does nothing with culling off.
+    fn cull_objects(&mut self, camera: &Camera) -> u32 {
+        if !self.frustum_culling {
+            return 0;
+        }
+        let frustum = Frustum::from_view_proj(camera.build_view_projection_matrix());
+        self.opaque_objects
+            .iter_mut()
+            .chain(&mut self.transparent_objects)
+            .map(|object| object.cull(&self.queue, &frustum))
+            .sum()
+    }
+
+    /// Open another window onto the same scene, from a different viewpoint. See `PerWindowState`.
+    fn open_window(&self, window: Window) -> Result<PerWindowState, StateError> {
+        let (surface, window) =
+            create_owned_surface(&self.instance, window).map_err(StateError::CreateSurface)?;
+        let caps = surface.get_capabilities(&self.adapter);
+        // The same format as the main window if possible, so the colours match.
+        let format = caps
+            .formats
+            .iter()
+            .copied()
+            .find(|&f| f == self.surface_config.format)
+            .or_else(|| caps.formats.first().copied())
+            .ok_or(StateError::NoSurfaceFormat)?;
+        let size = window.inner_size();
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: *caps
+                .present_modes
+                .first()
+                .ok_or(StateError::NoPresentMode)?,
+            alpha_mode: choose_alpha_mode(&caps.alpha_modes, false)?.0,
+            view_formats: vec![],
+        };
+        surface.configure(&self.device, &config);
+
+        // Off to the side and a bit above, looking at the same spot as the main camera.
+        let mut camera = self.initial.camera;
+        camera.aspect = config.width as f32 / config.height as f32;
+        let distance = (camera.eye - camera.target).magnitude();
+        camera.eye = camera.target + Vector3::new(0.7, 0.4, 0.7).normalize() * distance;
+        let camera_uniform = Uniform::new(
+            &self.device,
+            CameraUniform::from(&camera),
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            "second_window_camera_uniform",
+        );
+        let depth_texture = Texture::create_depth_texture(
+            &self.device,
+            config.width,
+            config.height,
+            1,
+            "second_window_depth_texture",
+        );
+        let mut per_window = PerWindowState {
+            surface,
+            window,
+            config,
+            camera,
+            camera_uniform,
+            depth_texture,
+            pipeline_options: self.pipeline_options,
+            render_pipelines: Vec::new(),
+            transparent_pipelines: Vec::new(),
+            consecutive_render_errors: 0,
+        };
+        self.update_window_pipelines(&mut per_window);
+        Ok(per_window)
+    }
+
+    /// Rebuild `per_window`'s pipelines if the main window's have changed since they were built.
+    fn update_window_pipelines(&self, per_window: &mut PerWindowState) {
+        let options = PipelineOptions {
+            target_format: per_window.config.format,
+            // Drawn straight onto the surface, with none of the main window's extra targets.
+            sample_count: 1,
+            normals_target: false,
+            alpha_to_coverage: false,
+            ..self.pipeline_options
+        };
+        if options == per_window.pipeline_options && !per_window.render_pipelines.is_empty() {
+            return;
+        }
+        per_window.pipeline_options = options;
+        let [opaque, transparent] = [false, true].map(|transparent| {
+            create_pipelines(
+                &self.device,
+                &self.render_pipeline_layout,
+                &self.shaders,
+                options,
+                &per_window.depth_texture,
+                transparent,
+            )
+        });
+        per_window.render_pipelines = opaque;
+        per_window.transparent_pipelines = transparent;
+    }
+
+    /// Draw the scene into another window, from its own camera. `alpha` is the same as for
+    /// `render`.
+    fn render_window(
+        &mut self,
+        per_window: &mut PerWindowState,
+        alpha: f32,
+    ) -> Result<(), wgpu::SurfaceError> {
+        self.update_window_pipelines(per_window);
+        per_window
+            .camera_uniform
+            .set(CameraUniform::from(&per_window.camera));
+        per_window.camera_uniform.flush(&self.queue);
+        // Back to front from this window's camera. The main window sorts them again for its own.
+        crate::scene::sort_back_to_front(&mut self.transparent_objects, &per_window.camera);
+        self.spin_objects(alpha);
+        // The main window culls again for its own camera too.
+        self.cull_objects(&per_window.camera);
+
+        let output = per_window.surface.get_current_texture()?;
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Second Window Encoder"),
+            });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Second Window Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // Always opaque, because only the main window can be transparent.
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            a: 1.0,
+                            ..self.color
+                        }),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &per_window.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(far_depth(self.pipeline_options.reverse_z)),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            render_pass.set_pipeline(&per_window.render_pipelines[self.active_pipeline].pipeline);
+            render_pass.set_bind_group(0, &self.diffuse_bind_groups[self.address_mode], &[]);
+            render_pass.set_bind_group(CAMERA_GROUP, per_window.camera_uniform.bind_group(), &[]);
+            render_pass.set_bind_group(LIGHT_GROUP, self.light_uniform.bind_group(), &[]);
+            for object in &self.opaque_objects {
+                object.draw(&mut render_pass, &self.model_uniforms, MODEL_GROUP);
+            }
+            render_pass
+                .set_pipeline(&per_window.transparent_pipelines[self.active_pipeline].pipeline);
+            for object in &self.transparent_objects {
+                object.draw(&mut render_pass, &self.model_uniforms, MODEL_GROUP);
+            }
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+        Ok(())
+    }
+
+    /// Draw the scene into `view` the normal way: the scene pass, then tonemapping or stretching
+    /// it onto `view` if it was drawn somewhere else.
+    fn encode_scene(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        // Clear the screen. Start a new block, because `render_pass` holds a &mut to `encoder`.
+        // This way when render_pass is dropped, encoder becomes usable again.
+        {
+            // With HDR, draw the scene into the HDR texture, it gets copied to `view` afterwards.
+            // Same for the blit texture, when rendering at a different resolution without HDR.
+            let scene_view = match (&self.hdr, &self.blit) {
+                (Some(hdr), _) => hdr.view(),
+                (None, Some(blit)) => blit.view(),
+                (None, None) => view,
+            };
+            // With MSAA, draw into the multisampled texture, then average ("resolve") its
+            // samples into the scene view.
+            let (attachment, resolve_target) = match &self.multisampled_view {
+                Some(multisampled_view) => (multisampled_view, Some(scene_view)),
+                None => (scene_view, None),
+            };
+            // The normals, if there's a normals target. Resolved the same way as the colour.
+            let normals_attachment = self.normals_view.as_ref().map(|normals_view| {
+                let (view, resolve_target) = match &self.multisampled_normals_view {
+                    Some(multisampled) => (multisampled, Some(normals_view)),
+                    None => (normals_view, None),
+                };
+                wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(crate::normals::CLEAR_NORMAL),
+                        store: true,
+                    },
+                }
+            });
+            // With trails on, keep last frame's colours, except when they have to be cleared.
+            let clear = !self.trails || self.clear_next_frame;
+            // Describe where to draw the color to.
+            let color_attachment = wgpu::RenderPassColorAttachment {
+                view: attachment,
+                // Same as view, unless multisampling is used.
+                resolve_target,
+                // What to do with the colours on the screen.
+                ops: wgpu::Operations {
+                    // 'load' field is what to do with colours stored from previous frame.
+                    // When letterboxing, clear to black for the bars. The scene's own
+                    // background gets filled in later, just inside the viewport.
+                    load: if !clear {
+                        wgpu::LoadOp::Load
+                    } else if self.aspect.is_some() {
+                        wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                    } else {
+                        wgpu::LoadOp::Clear(self.clear_color())
+                    },
+                    store: true,
+                },
+            };
+            // Attachment n is where the shaders' @location(n) output goes.
+            let color_attachments: Vec<_> = std::iter::once(color_attachment)
+                .chain(normals_attachment)
+                .map(Some)
+                .collect();
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &color_attachments,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        // Clear to as far away as possible, so everything is in front of it.
+                        load: wgpu::LoadOp::Clear(far_depth(self.pipeline_options.reverse_z)),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            // The background goes behind everything, so it's drawn first. The skybox covers
+            // the whole viewport, so with it on there's no need for the background.
+            let letterbox_viewport = self.letterbox_viewport();
+            if let Some([x, y, width, height]) = letterbox_viewport {
+                // Everything after this only draws inside the viewport, and gets squashed to fit.
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            }
+            // Not while there are trails, though, because it would cover them up.
+            if clear {
+                if self.skybox.enabled {
+                    self.skybox.draw(&mut render_pass);
+                } else if letterbox_viewport.is_some() {
+                    self.background.draw_or_fill(&mut render_pass);
+                } else {
+                    self.background.draw(&mut render_pass);
+                }
+            }
+
+            render_pass.set_bind_group(0, &self.diffuse_bind_groups[self.address_mode], &[]);
+            render_pass.set_bind_group(CAMERA_GROUP, self.camera_uniform.bind_group(), &[]);
+            render_pass.set_bind_group(LIGHT_GROUP, self.light_uniform.bind_group(), &[]);
+            if self.uv_debug {
+                // Everything's drawn opaque, transparent objects too, so their UVs show as
+                // clearly as the rest.
+                let pipeline = &self.uv_pipelines[usize::from(self.flip_v)];
+                render_pass.set_pipeline(&pipeline.pipeline);
+                for object in self.opaque_objects.iter().chain(&self.transparent_objects) {
+                    object.draw(&mut render_pass, &self.model_uniforms, MODEL_GROUP);
+                }
+            } else {
+                // Opaque objects first. They write depth, so later draws behind them get skipped.
+                render_pass.set_pipeline(&self.render_pipelines[self.active_pipeline].pipeline);
+                for object in &self.opaque_objects {
+                    object.draw(&mut render_pass, &self.model_uniforms, MODEL_GROUP);
+                }
+            }
+
+            // Among the opaque objects, so transparent ones blend over it.
+            self.grid.draw(&mut render_pass);
+
+            if let Some(particles) = &self.particles {
+                particles.draw(&mut render_pass);
+            }
+
+            // Then transparent ones, already sorted back to front by `update`. Setting the
+            // pipeline keeps the bind groups, because the layouts match.
+            if !self.uv_debug {
+                let pipeline = &self.transparent_pipelines[self.active_pipeline];
+                render_pass.set_pipeline(&pipeline.pipeline);
+                for object in &self.transparent_objects {
+                    object.draw(&mut render_pass, &self.model_uniforms, MODEL_GROUP);
+                }
+            }
+
+            // Over the transparent objects, so the boxes around them aren't tinted. They don't
+            // write depth, so this doesn't hide anything.
+            self.bounding_boxes.draw(
+                &mut render_pass,
+                self.opaque_objects.iter().chain(&self.transparent_objects),
+                &self.model_uniforms,
+                MODEL_GROUP,
+            );
+
+            // Over everything it outlines, transparent objects included. Changes the bind groups,
+            // like the point sprites.
+            self.wireframe.draw(
+                &mut render_pass,
+                self.camera_uniform.bind_group(),
+                self.opaque_objects.iter().chain(&self.transparent_objects),
+                &self.model_uniforms,
+            );
+
+            // Last, because it changes the bind groups. The main object always comes first.
+            if let Some(point_sprites) = &self.point_sprites {
+                point_sprites.draw(
+                    &mut render_pass,
+                    self.camera_uniform.bind_group(),
+                    &self.model_uniforms,
+                    &self.opaque_objects[0],
+                );
+            }
+        }
+
+        if let Some(hdr) = &self.hdr {
+            hdr.process(encoder, view);
+        } else if let Some(blit) = &self.blit {
+            blit.process(encoder, view);
+        }
+
+        if let Some(region) = self.loupe_region() {
+            if let Some(hdr) = &self.hdr {
+                hdr.process_loupe(encoder, view, &region);
+            } else if let Some(blit) = &self.blit {
+                blit.process_loupe(encoder, view, &region);
+            }
+        }
+    }
+
+    /// Where the loupe goes this frame, or None if it's off.
+    fn loupe_region(&self) -> Option<LoupeRegion> {
+        if !self.loupe.enabled {
+            return None;
+        }
+        let PhysicalPosition { x, y } = self.cursor_position;
+        let size = self.current_size();
+        self.loupe.region(
+            [x as f32, y as f32],
+            size.width,
+            size.height,
+            self.device.limits().max_texture_dimension_2d,
+        )
+    }
+
+    /// Draw the overdraw heatmap into `view` instead of the scene. See `Overdraw`.
+    fn encode_overdraw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        {
+            let mut render_pass = self.overdraw.begin_accumulate(encoder);
+            if let Some([x, y, width, height]) = self.letterbox_viewport() {
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            }
+            render_pass.set_bind_group(0, &self.diffuse_bind_groups[self.address_mode], &[]);
+            render_pass.set_bind_group(CAMERA_GROUP, self.camera_uniform.bind_group(), &[]);
+            render_pass.set_bind_group(LIGHT_GROUP, self.light_uniform.bind_group(), &[]);
+            for object in self.opaque_objects.iter().chain(&self.transparent_objects) {
+                object.draw(&mut render_pass, &self.model_uniforms, MODEL_GROUP);
+            }
+        }
+        self.overdraw.resolve(encoder, view);
+    }
+
+    /// With --aspect, the part of the render target the scene goes in, as x, y, width and height.
+    /// The bars have to be the right shape in the window, so work them out there, then scale them
+    /// to the render target, which might not be the window's shape.
+    fn letterbox_viewport(&self) -> Option<[f32; 4]> {
+        let aspect = self.aspect?;
+        let size = self.current_size();
+        let [x, y, width, height] = letterbox(size.width, size.height, aspect);
+        let scale_x = self.render_size.width as f32 / size.width as f32;
+        let scale_y = self.render_size.height as f32 / size.height as f32;
+        Some([x * scale_x, y * scale_y, width * scale_x, height * scale_y])
+    }
+
+    /// Wait for the GPU to finish everything it's been given, so nothing is still in use when the
+    /// process tears everything down. Otherwise validation layers complain, and buffers being
+    /// mapped (e.g. for readback) might never finish.
+    fn shutdown(&mut self) {
+        self.device.poll(wgpu::Maintain::Wait);
+        self.in_flight.clear();
+        // Save whatever was recorded before quitting early.
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.finish(&self.device);
+        }
+        log::info!("clean shutdown");
+    }
+}
+
+/// How many pixels the scene renders at. Anything other than the window's own size renders into an
+/// offscreen texture, which then gets stretched over the surface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum RenderResolution {
+    Window,
+    /// This fraction of the window's size, from --render-scale.
+    Scaled(f32),
+    /// Always this size, from --internal-res.
+    Fixed(u32, u32),
+}
+
+impl RenderResolution {
+    fn from_args(args: &Args) -> Self {
+        match (args.internal_res, args.render_scale) {
+            (Some((width, height)), _) => Self::Fixed(width, height),
+            (None, Some(scale)) => Self::Scaled(scale),
+            (None, None) => Self::Window,
+        }
+    }
+
+    /// The render size for a window this big. Never 0, and never bigger than the device allows.
+    fn size(self, window: PhysicalSize<u32>, max_dimension: u32) -> PhysicalSize<u32> {
+        let (width, height) = match self {
+            Self::Window => (window.width, window.height),
+            Self::Scaled(scale) => (
+                (window.width as f32 * scale).round() as u32,
+                (window.height as f32 * scale).round() as u32,
+            ),
+            Self::Fixed(width, height) => (width, height),
+        };
+        PhysicalSize::new(
+            width.clamp(1, max_dimension),
+            height.clamp(1, max_dimension),
+        )
+    }
+}
+
+/// Settings which get baked into a pipeline when it's created. Pipelines are immutable, so changing
+/// any of these means building new pipelines.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct PipelineOptions {
+    target_format: wgpu::TextureFormat,
+    /// Samples per pixel. The one place the sample count is kept: the multisampled colour
+    /// textures and the depth texture are all created with this, and must agree with it.
+    sample_count: u32,
+    cull_mode: Option<wgpu::Face>,
+    /// Has to match the vertex buffers of the objects drawn with the pipeline.
+    vertex_layout: VertexLayout,
+    /// Has to match the index buffers of the objects drawn with the pipeline, see
+    /// `Mesh::indices_for`.
+    topology: wgpu::PrimitiveTopology,
+    /// Whether the scene pass has a second colour target for normals.
+    normals_target: bool,
+    /// Must match the camera's `reverse_z`, because it flips which way the depth test goes.
+    reverse_z: bool,
+    /// Turn each fragment's alpha into how many of its samples it covers, so cutout edges (like
+    /// the tree's) get antialiased without sorting. Needs MSAA, i.e. `sample_count` > 1.
+    alpha_to_coverage: bool,
+    /// Clamp depth to the far (and near) plane instead of clipping whatever's past it, so things
+    /// beyond the far plane still get drawn, as far away as possible. Needs the
+    /// DEPTH_CLIP_CONTROL feature.
+    unclipped_depth: bool,
+    /// Run the fragment shader once per sample rather than once per pixel, using the shaders'
+    /// per-sample entry points (see `Shader::per_sample_entries`). Needs MSAA, and the
+    /// MULTISAMPLED_SHADING downlevel flag. Shaders without those entry points are unaffected.
+    sample_shading: bool,
+}
+
+/// A scene pipeline, and the name of the shader it was built from.
+struct NamedPipeline {
+    name: String,
+    pipeline: wgpu::RenderPipeline,
+}
+
+/// One pipeline per shader, in the same order. `depth_texture` is only there to check it agrees
+/// with `options`.
+fn create_pipelines(
+    device: &wgpu::Device,
+    render_pipeline_layout: &wgpu::PipelineLayout,
+    shaders: &[Shader],
+    options: PipelineOptions,
+    depth_texture: &Texture,
+    transparent: bool,
+) -> Vec<NamedPipeline> {
+    // Both come from `options.sample_count`, but if they ever disagree, wgpu's validation error
+    // when drawing doesn't make it obvious why.
+    debug_assert_eq!(
+        depth_texture.texture.sample_count(),
+        options.sample_count,
+        "the depth texture and the pipelines must have the same sample count"
+    );
+    shaders
+        .iter()
+        .map(|shader| NamedPipeline {
+            name: shader.name.clone(),
+            pipeline: create_pipeline(device, render_pipeline_layout, shader, options, transparent),
+        })
+        .collect()
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    render_pipeline_layout: &wgpu::PipelineLayout,
+    shader: &Shader,
+    options: PipelineOptions,
+    transparent: bool,
+) -> wgpu::RenderPipeline {
+    let label = if transparent {
+        format!("{} Transparent Render Pipeline", shader.name)
+    } else {
+        format!("{} Render Pipeline", shader.name)
+    };
+    let buffers = vertex_buffer_layouts(options);
+    let (vs_entry, fs_entry) = match &shader.per_sample_entries {
+        Some((vs_entry, fs_entry)) if options.sample_shading => (vs_entry, fs_entry),
+        _ => (&shader.vs_entry, &shader.fs_entry),
+    };
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Render Pipeline"),
+        label: Some(&label),
         layout: Some(render_pipeline_layout),
         vertex: wgpu::VertexState {
-            module: shader,
-            entry_point: "vs_main",
-            // Define how the vertex buffer is laid out.
-            buffers: &[Vertex::descriptor()],
+            module: &shader.module,
+            entry_point: vs_entry,
+            // Define how the vertex buffers are laid out.
+            buffers: &buffers,
         },
-        // Stores color data in the `surface`.
+        // Stores color data in the `surface` (or the HDR texture).
         fragment: Some(wgpu::FragmentState {
-            module: shader,
-            entry_point: "fs_main",
+            module: &shader.module,
+            entry_point: fs_entry,
             // What colour outputs it should set up.
-            targets: &[
-                // We only need one colour output, the `surface`.
-                Some(wgpu::ColorTargetState {
-                    format: surface_config.format,
-                    // Replace old pixel data with new data. I guess other alternatives would be
-                    // 'blend them together' somehow.
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                }),
-            ],
+            // The scene's colour (the `surface` or HDR texture), plus the normals if there's a
+            // normals target.
+            targets: &crate::normals::scene_targets(
+                options.target_format,
+                // Replace old pixel data with new data. Transparent objects blend with whatever's
+                // behind them instead, according to their alpha. The textures and tints are
+                // premultiplied, so the source colour isn't multiplied by alpha again.
+                if transparent {
+                    wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING
+                } else {
+                    wgpu::BlendState::REPLACE
+                },
+                options.normals_target,
+            ),
+        }),
+        primitive: primitive_state(options, transparent),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            // Transparent objects are still hidden behind opaque ones, but mustn't hide each
+            // other, or the one behind wouldn't show through.
+            depth_write_enabled: !transparent,
+            // Draw a pixel if it's closer than what's already there.
+            depth_compare: depth_compare(options),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
         }),
-        primitive: wgpu::PrimitiveState {
-            // i.e. every 3 vertices corresponds to one triangle.
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            // How wgpu should tell if a given triangle is facing forwards or not.
-            // CCW means it's facing forwards if vertices are arranged counter-clockwise.
-            front_face: wgpu::FrontFace::Ccw,
-            // What to cull (i.e. not draw). Anything facing backwards.
-            cull_mode: Some(wgpu::Face::Back),
-            polygon_mode: wgpu::PolygonMode::Fill,
-            unclipped_depth: false,
-            conservative: false,
-        },
-        depth_stencil: None,
         multisample: wgpu::MultisampleState {
             // How many samples the pipeline will use
-            count: 1,
+            count: options.sample_count,
             // Which samples should be active? All of them.
             mask: !0,
-            // For antialiasing.
-            alpha_to_coverage_enabled: false,
+            // For antialiasing cutout edges. See PipelineOptions.
+            alpha_to_coverage_enabled: options.alpha_to_coverage,
         },
         // How many array layers the render attachments can have. Not using this.
         multiview: None,
     })
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2], // NEW!
-}
-
-impl Vertex {
-    /// How does the vertex buffer's internal layout correspond to a set of these Vertices?
-    /// Note this is pretty verbose, a macro `vertex_attr_array` exists to help.
-    fn descriptor<'a>() -> wgpu::VertexBufferLayout<'a> {
-        wgpu::VertexBufferLayout {
-            /// How many bytes are in each element of the array
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            /// Do you increment the array index per-vertex or per-instance?
-            /// I don't know what instances are yet, so, vertices here.
-            step_mode: wgpu::VertexStepMode::Vertex,
-            /// Maps attributes of the struct to locations in each element of the buffer.
-            attributes: &[
-                wgpu::VertexAttribute {
-                    // Where the attribute starts.
-                    offset: 0,
-                    // In WGSL each attribute has a 'location' (analogous to protobuf's field number)
-                    // This describes which location number the given attribute corresponds to.
-                    shader_location: 0,
-                    // Internal format of the attribute
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    // Offset after the [f32; 3] used for the previous attribute
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    // Store in @location(1)
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-            ],
+/// How the surface's alpha blends it with whatever's behind the window. With `transparent`
+/// (--transparent), PreMultiplied if the surface has it, because the scene's colours already are.
+/// Otherwise, or if it doesn't, Opaque if the surface has that, or whatever it has. Returns
+/// whether the window will really be see-through.
+fn choose_alpha_mode(
+    modes: &[wgpu::CompositeAlphaMode],
+    transparent: bool,
+) -> Result<(wgpu::CompositeAlphaMode, bool), StateError> {
+    if transparent {
+        if modes.contains(&wgpu::CompositeAlphaMode::PreMultiplied) {
+            log::info!("Transparent window, with premultiplied alpha");
+            return Ok((wgpu::CompositeAlphaMode::PreMultiplied, true));
         }
+        log::warn!(
+            "The surface can't blend premultiplied alpha with the desktop (it has {modes:?}), so \
+             the window stays opaque"
+        );
     }
+    let mode = if modes.contains(&wgpu::CompositeAlphaMode::Opaque) {
+        wgpu::CompositeAlphaMode::Opaque
+    } else {
+        *modes.first().ok_or(StateError::NoAlphaMode)?
+    };
+    Ok((mode, false))
 }
 
-pub async fn run() {
+/// The depth of the far plane: 1, or 0 with reversed Z.
+fn far_depth(reverse_z: bool) -> f32 {
+    if reverse_z {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+/// How the scene pipelines turn vertices into triangles (or lines or points).
+fn primitive_state(options: PipelineOptions, transparent: bool) -> wgpu::PrimitiveState {
+    wgpu::PrimitiveState {
+        // TriangleList (the default) means every 3 vertices corresponds to one triangle.
+        topology: options.topology,
+        // Strips need to know which index means "start a new strip", which depends on the
+        // index format. Every other topology has to leave this unset.
+        strip_index_format: options
+            .topology
+            .is_strip()
+            .then_some(wgpu::IndexFormat::Uint32),
+        // How wgpu should tell if a given triangle is facing forwards or not.
+        // CCW means it's facing forwards if vertices are arranged counter-clockwise.
+        front_face: wgpu::FrontFace::Ccw,
+        // What to cull (i.e. not draw). Usually anything facing backwards.
+        // You can see the back of a transparent object, so never cull those.
+        cull_mode: if transparent { None } else { options.cull_mode },
+        polygon_mode: wgpu::PolygonMode::Fill,
+        unclipped_depth: options.unclipped_depth,
+        conservative: false,
+    }
+}
+
+/// The per-vertex data takes one slot, or one per attribute. Per-instance data comes after.
+fn vertex_buffer_layouts(options: PipelineOptions) -> Vec<wgpu::VertexBufferLayout<'static>> {
+    let mut buffers = options.vertex_layout.buffer_layouts();
+    buffers.push(InstanceRaw::descriptor());
+    buffers
+}
+
+/// The depth test which passes for fragments closer to the camera than what's already there.
+fn depth_compare(options: PipelineOptions) -> wgpu::CompareFunction {
+    // Clamped depth lands exactly on the far plane, the same as the depth buffer is cleared to,
+    // so it has to pass when equal. Otherwise clamping would hide things just like clipping.
+    match (options.reverse_z, options.unclipped_depth) {
+        (true, false) => wgpu::CompareFunction::Greater,
+        (true, true) => wgpu::CompareFunction::GreaterEqual,
+        (false, false) => wgpu::CompareFunction::Less,
+        (false, true) => wgpu::CompareFunction::LessEqual,
+    }
+}
+
+/// Pick the MSAA sample count. If the format can't do `requested` samples, use the highest count
+/// it can do which is still below `requested`.
+fn choose_sample_count(
+    adapter: &wgpu::Adapter,
+    format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    let flags = adapter.get_texture_format_features(format).flags;
+    if flags.sample_count_supported(requested) {
+        log::info!("Using {requested}x MSAA");
+        return requested;
+    }
+    let supported: Vec<u32> = [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&n| flags.sample_count_supported(n))
+        .collect();
+    // 1 is always supported, so there's always a fallback.
+    let fallback = supported
+        .iter()
+        .copied()
+        .filter(|&n| n <= requested)
+        .max()
+        .unwrap_or(1);
+    log::warn!(
+        "{requested}x MSAA isn't supported for {format:?} (supported: {supported:?}), using \
+         {fallback}x instead"
+    );
+    fallback
+}
+
+/// Create a surface for `window`, and hand the window back, to be kept alongside the surface.
+///
+/// This is the only place the surface's one safety requirement needs checking: the window must
+/// outlive the surface. Taking the window by value means nothing else can close it in the
+/// meantime. Whoever keeps the pair must drop the surface first, which `State` does by declaring
+/// `surface` before `window` (struct fields are dropped in declaration order).
+fn create_owned_surface(
+    instance: &wgpu::Instance,
+    window: Window,
+) -> Result<(wgpu::Surface, Window), wgpu::CreateSurfaceError> {
+    // Safety: the window is returned alongside the surface, and the caller keeps it alive for at
+    // least as long as the surface (see above).
+    let surface = unsafe { instance.create_surface(&window) }?;
+    Ok((surface, window))
+}
+
+/// The texture the scene renders into when MSAA is on. It has to be recreated whenever the render
+/// resolution changes, because it must be the same size as the texture it resolves into.
+fn create_multisampled_view(
+    device: &wgpu::Device,
+    size: PhysicalSize<u32>,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count == 1 {
+        return None;
+    }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Multisampled Texture"),
+        size: wgpu::Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        // Only ever rendered to. The resolve copies it into a normal texture.
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+/// The normals target, and its multisampled twin if MSAA is on. Like the other render targets,
+/// these have to be recreated when the render resolution changes.
+fn create_normals_views(
+    device: &wgpu::Device,
+    size: PhysicalSize<u32>,
+    sample_count: u32,
+) -> (Option<wgpu::TextureView>, Option<wgpu::TextureView>) {
+    // TEXTURE_BINDING as well as RENDER_ATTACHMENT, so a later pass could read the normals.
+    let normals_view = crate::blit::create_target_view(
+        device,
+        "normals_texture",
+        crate::normals::NORMALS_FORMAT,
+        size.width,
+        size.height,
+    );
+    let multisampled_normals_view =
+        create_multisampled_view(device, size, crate::normals::NORMALS_FORMAT, sample_count);
+    (Some(normals_view), multisampled_normals_view)
+}
+
+/// The biggest rectangle with the given aspect ratio which fits in the window, centred.
+/// Returns [x, y, width, height] in pixels, as `set_viewport` wants them.
+fn letterbox(window_width: u32, window_height: u32, aspect: f32) -> [f32; 4] {
+    let (window_width, window_height) = (window_width as f32, window_height as f32);
+    let (width, height) = if window_width / window_height > aspect {
+        // Window's too wide, so bars on the left and right.
+        (window_height * aspect, window_height)
+    } else {
+        // Window's too tall, so bars on the top and bottom.
+        (window_width, window_width / aspect)
+    };
+    [
+        (window_width - width) / 2.0,
+        (window_height - height) / 2.0,
+        width,
+        height,
+    ]
+}
+
+/// Scatter the instances with `seed`, and log it, so the same layout can be had again with
+/// `--seed`.
+fn scatter_layout(scatter: &ScatterBuilder, seed: u64) -> Vec<Instance> {
+    log::info!("Scatter seed: {seed}");
+    scatter.clone().seed(seed).build()
+}
+
+/// Models are always drawn with their own texture, whatever layer the instances were given.
+fn use_model_texture(instances: &mut [Instance], model_texture_layer: Option<u32>) {
+    if let Some(layer) = model_texture_layer {
+        for instance in instances {
+            instance.texture_layer = layer;
+        }
+    }
+}
+
+/// Count another frame in a row that failed to render with `error`, and quit once there have
+/// been `MAX_CONSECUTIVE_RENDER_ERRORS` of them.
+fn count_render_error(
+    consecutive_errors: &mut u32,
+    error: &wgpu::SurfaceError,
+    control_flow: &mut ControlFlow,
+) {
+    *consecutive_errors += 1;
+    if *consecutive_errors >= MAX_CONSECUTIVE_RENDER_ERRORS {
+        log::error!(
+            "Giving up after {consecutive_errors} frames in a row failed to render. The last \
+             error was: {error:?}"
+        );
+        *control_flow = ControlFlow::ExitWithCode(EXIT_FAILURE);
+    }
+}
+
+/// Swing `camera` around its target by however much --demo orbits in `dt`.
+fn demo_orbit(camera: &mut Camera, dt: Duration) {
+    camera.orbit(Rad(DEMO_ORBIT_RADIANS_PER_SEC * dt.as_secs_f32()));
+}
+
+/// Turn the spin angle by however much it should have turned in `dt`.
+/// Wraps around at a full turn so the angle doesn't lose float precision after running a while.
+fn advance_spin(angle: Rad<f32>, dt: Duration) -> Rad<f32> {
+    let full_turn = std::f32::consts::TAU;
+    Rad((angle.0 + SPIN_RADIANS_PER_SEC * dt.as_secs_f32()).rem_euclid(full_turn))
+}
+
+/// Blend between two angles from `advance_spin`. It wraps around at a full turn, so a `current`
+/// smaller than `previous` means it went past the wrap, not backwards.
+fn interpolate_angle(previous: Rad<f32>, current: Rad<f32>, alpha: f32) -> Rad<f32> {
+    let full_turn = std::f32::consts::TAU;
+    let mut delta = current.0 - previous.0;
+    if delta < 0.0 {
+        delta += full_turn;
+    }
+    Rad((previous.0 + delta * alpha).rem_euclid(full_turn))
+}
+
+/// The name --format accepts for a texture format, e.g. `bgra8unorm-srgb`. The same names WebGPU
+/// uses.
+pub fn format_name(format: wgpu::TextureFormat) -> String {
+    let name = format!("{format:?}").to_lowercase();
+    match name.strip_suffix("srgb") {
+        Some(base) => format!("{base}-srgb"),
+        None => name,
+    }
+}
+
+/// Run as many updates as `dt` calls for: one, or with --fixed-timestep, as many whole
+/// FIXED_TIMESTEP updates as fit in the time that's passed. Whatever's left over carries on to the
+/// next frame, in `accumulator`. Returns how far towards the next update this frame is, for
+/// `render`.
+fn run_updates(
+    state: &mut State,
+    dt: Duration,
+    accumulator: &mut Duration,
+    fixed_timestep: bool,
+) -> f32 {
+    if !fixed_timestep {
+        state.update(dt);
+        return 1.0;
+    }
+    *accumulator += dt.min(MAX_FRAME_TIME);
+    while *accumulator >= FIXED_TIMESTEP {
+        state.update(FIXED_TIMESTEP);
+        *accumulator -= FIXED_TIMESTEP;
+    }
+    accumulator.as_secs_f32() / FIXED_TIMESTEP.as_secs_f32()
+}
+
+/// The monitor at `index` in the OS's list of monitors. If the index is out of range, logs
+/// which monitors there are and returns None, so the window goes wherever the OS puts it.
+fn choose_monitor(event_loop: &EventLoop<()>, index: Option<usize>) -> Option<MonitorHandle> {
+    let index = index?;
+    let monitors: Vec<_> = event_loop.available_monitors().collect();
+    match monitors.get(index) {
+        Some(monitor) => {
+            log::info!("Using monitor {index}: {:?}", monitor.name());
+            Some(monitor.clone())
+        }
+        None => {
+            log::warn!(
+                "There's no monitor {index}, using the default placement. Available monitors:"
+            );
+            for (i, monitor) in monitors.iter().enumerate() {
+                log::warn!("  {i}: {:?} ({:?})", monitor.name(), monitor.size());
+            }
+            None
+        }
+    }
+}
+
+/// The biggest, then fastest, then most colourful video mode the monitor has.
+fn best_video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    monitor.video_modes().max_by_key(|mode| {
+        let size = mode.size();
+        (
+            size.width * size.height,
+            mode.refresh_rate_millihertz(),
+            mode.bit_depth(),
+        )
+    })
+}
+
+/// Open the window and run the demo until it quits. Only returns if it can't start, or with
+/// --print-caps, because otherwise winit's event loop takes over the thread.
+pub async fn run() -> Result<(), AppError> {
     env_logger::init();
+    let args = Args::parse()?;
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new()
-        .with_title("Adam GPU Demo")
+    let mut window_builder = WindowBuilder::new().with_title(WINDOW_TITLE);
+    let monitor = choose_monitor(&event_loop, args.monitor);
+    if args.fullscreen {
+        match monitor.clone().or_else(|| event_loop.primary_monitor()) {
+            Some(monitor) => match best_video_mode(&monitor) {
+                Some(mode) => {
+                    log::info!("Going fullscreen at {mode}");
+                    window_builder =
+                        window_builder.with_fullscreen(Some(Fullscreen::Exclusive(mode)));
+                }
+                None => log::warn!("{monitor:?} has no video modes, so staying windowed"),
+            },
+            None => log::warn!("Couldn't find a monitor to go fullscreen on"),
+        }
+    } else if let Some((width, height)) = args.size {
+        window_builder = window_builder.with_inner_size(PhysicalSize::new(width, height));
+    }
+    if let (Some(monitor), false) = (&monitor, args.fullscreen) {
+        // Windows are positioned in desktop coordinates, where each monitor has its own area.
+        // Put the window near the monitor's top-left corner, so the title bar isn't cut off.
+        let origin = monitor.position();
+        window_builder =
+            window_builder.with_position(PhysicalPosition::new(origin.x + 50, origin.y + 50));
+    }
+    if args.transparent {
+        // Whether the desktop really shows through is up to the surface, see `choose_alpha_mode`.
+        window_builder = window_builder.with_transparent(true);
+    }
+    let window = window_builder
         .build(&event_loop)
-        .unwrap();
-    let mut state = State::new(window).await;
+        .map_err(AppError::Window)?;
+    let Some(mut state) = State::new(window, &args).await.map_err(AppError::State)? else {
+        return Ok(());
+    };
+    state.update_title();
+    // Every window except the main one, so their events can be sent to the right place.
+    let mut windows: HashMap<WindowId, PerWindowState> = HashMap::new();
+    if args.second_window {
+        // Optional, so failing to open it isn't worth stopping for.
+        match WindowBuilder::new()
+            .with_title(format!("{WINDOW_TITLE} (second window)"))
+            .build(&event_loop)
+        {
+            Ok(window) => match state.open_window(window) {
+                Ok(per_window) => {
+                    windows.insert(per_window.window.id(), per_window);
+                }
+                Err(e) => log::warn!("Couldn't open the second window: {e}"),
+            },
+            Err(e) => log::warn!("Couldn't open the second window: {e}"),
+        }
+    }
+    // Closing the main window only hides it while other windows are still open.
+    let mut main_window_open = true;
+    // How far between updates the latest frame was. The other windows draw there too.
+    let mut alpha = 1.0;
+    let start = std::time::Instant::now();
+    let mut input_recorder = match &args.record_input {
+        Some(path) => match InputRecorder::create(path) {
+            Ok(recorder) => {
+                log::info!("Recording input to {}", path.display());
+                Some(recorder)
+            }
+            Err(e) => return Err(AppError::RecordInput(e)),
+        },
+        None => None,
+    };
+    let mut replay = match &args.replay_input {
+        Some(path) => match InputReplay::load(path) {
+            Ok(replay) => {
+                log::info!(
+                    "Replaying input from {}, ignoring live input",
+                    path.display()
+                );
+                Some(replay)
+            }
+            Err(e) => return Err(AppError::ReplayInput(e)),
+        },
+        None => None,
+    };
+    let mut bench = args.bench_frames.map(|frames| {
+        log::info!("Benchmarking {frames} frame(s)");
+        FrameTimes::new(frames)
+    });
+    let mut last_render_time = start;
+    // Simulated time which hasn't been used up by an update yet. Only used with --fixed-timestep.
+    let mut accumulator = Duration::ZERO;
+    let fixed_timestep = args.fixed_timestep;
+    let power_save = args.power_save;
 
     event_loop.run(move |event, _, control_flow| match event {
-        Event::RedrawRequested(window_id) if window_id == state.window().id() => {
-            state.update();
-            match state.render() {
-                Ok(_) => {}
+        Event::RedrawRequested(window_id)
+            if window_id == state.window().id() && main_window_open && !state.occluded =>
+        {
+            let now = std::time::Instant::now();
+            let dt = now - last_render_time;
+            last_render_time = now;
+            // Replayed events happen at the start of the first frame after they're due, the
+            // same as live ones would have.
+            if let Some(events) = &mut replay {
+                while let Some(input) = events.next_due(now - start) {
+                    // A replayed resize gets recorded when the real window's Resized arrives.
+                    if let (Some(recorder), false) =
+                        (&mut input_recorder, matches!(input, InputEvent::Resized(_)))
+                    {
+                        recorder.record(now - start, &input);
+                    }
+                    match input {
+                        InputEvent::CloseRequested
+                        | InputEvent::KeyPressed(VirtualKeyCode::Escape) => {
+                            *control_flow = ControlFlow::Exit
+                        }
+                        // Resize the real window. Its own Resized event does the rest.
+                        InputEvent::Resized(size) => state.window().set_inner_size(size),
+                        input => state.handle_input(input),
+                    }
+                }
+                if events.is_finished() {
+                    log::info!("Finished replaying input, live input works again");
+                    replay = None;
+                }
+            }
+            state.begin_frame(dt);
+            alpha = run_updates(&mut state, dt, &mut accumulator, fixed_timestep);
+            // Around exactly one render, so the capture has this frame and nothing else.
+            let capture = std::mem::take(&mut state.capture_next_frame);
+            if capture {
+                state.device.start_capture();
+            }
+            let result = state.render(alpha);
+            if capture {
+                state.device.stop_capture();
+                // F12 only asks for a capture when there's something to capture with.
+                if let Some(tool) = state.quirks.frame_capture {
+                    log::info!("Captured a frame with {tool:?}");
+                }
+            }
+            if let Some(bench) = &mut bench {
+                // Only the CPU's side of the frame: updating, recording and submitting.
+                bench.record(now.elapsed());
+                if bench.is_done() {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            match &result {
+                Ok(_) => state.consecutive_render_errors = 0,
                 // Reconfigure the surface if lost
-                Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
+                Err(wgpu::SurfaceError::Lost) => state.resize(state.current_size()),
                 // If OOM, quit.
-                Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    log::error!("Ran out of memory getting the next frame, exiting");
+                    *control_flow = ControlFlow::ExitWithCode(EXIT_FAILURE);
+                }
                 // Other errors should be resolved by next frame.
                 Err(e) => eprintln!("{:?}", e),
             }
+            // ...but if they aren't, give up rather than spamming errors forever.
+            if let Err(e) = result {
+                count_render_error(&mut state.consecutive_render_errors, &e, control_flow);
+            }
+        }
+
+        // The last event before the process exits, however the loop was told to stop.
+        Event::LoopDestroyed => {
+            if let Some(recorder) = &mut input_recorder {
+                recorder.flush();
+            }
+            state.shutdown();
+            if let Some(bench) = &bench {
+                print!("{bench}");
+            }
+        }
+
+        // With the main window closed (or hidden), one of the others has to keep the updates
+        // going.
+        Event::RedrawRequested(window_id) => {
+            let Some(per_window) = windows.get_mut(&window_id) else {
+                return;
+            };
+            if !main_window_open || state.occluded {
+                let now = std::time::Instant::now();
+                let dt = now - last_render_time;
+                last_render_time = now;
+                state.begin_frame(dt);
+                alpha = run_updates(&mut state, dt, &mut accumulator, fixed_timestep);
+            }
+            let result = state.render_window(per_window, alpha);
+            match &result {
+                Ok(_) => per_window.consecutive_render_errors = 0,
+                Err(wgpu::SurfaceError::Lost) => {
+                    let size = per_window.window.inner_size();
+                    per_window.resize(&state.device, size);
+                }
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    log::error!("Ran out of memory getting the next frame, exiting");
+                    *control_flow = ControlFlow::ExitWithCode(EXIT_FAILURE);
+                }
+                Err(e) => log::warn!("Couldn't render the second window: {e:?}"),
+            }
+            // The same limit as the main window's, so a broken surface here can't log forever
+            // either.
+            if let Err(e) = result {
+                count_render_error(&mut per_window.consecutive_render_errors, &e, control_flow);
+            }
+        }
+
+        // Waking up after waiting for an event. However long it waited isn't frame time, so don't
+        // let the next update move things by all of it at once.
+        Event::NewEvents(StartCause::WaitCancelled { .. }) => {
+            last_render_time = std::time::Instant::now();
         }
 
         Event::MainEventsCleared => {
+            // With --power-save, a still scene isn't redrawn. The loop sleeps until an event
+            // comes in, and whatever handles it asks for a redraw.
+            let still = !state.is_animating() && replay.is_none() && bench.is_none();
+            // Nobody can see anything, so there's nothing to draw until that changes.
+            let main_visible = main_window_open && !state.occluded;
+            let all_hidden = !main_visible && windows.is_empty();
+            if (power_save && still) || all_hidden {
+                *control_flow = ControlFlow::Wait;
+                return;
+            }
+            *control_flow = ControlFlow::Poll;
             // RedrawRequested will only trigger once, unless we manually request it.
-            state.window().request_redraw();
+            if main_visible {
+                state.window().request_redraw();
+            }
+            for per_window in windows.values() {
+                per_window.window.request_redraw();
+            }
         }
 
+        // The other windows only need to know about resizing and closing.
         Event::WindowEvent {
             ref event,
             window_id,
-        } if window_id == state.window().id() => {
-            if !state.input(event) {
-                match event {
-                    // Detect window close.
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => *control_flow = ControlFlow::Exit,
-
-                    WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Space),
-                                ..
-                            },
-                        ..
-                    } => {
-                        state.active_texture += 1;
-                        state.active_texture %= state.render_pipelines.len();
-                    }
-
-                    // Resize events.
-                    WindowEvent::Resized(physical_size) => {
-                        state.resize(*physical_size);
-                    }
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        state.resize(**new_inner_size);
-                    }
+        } if windows.contains_key(&window_id) => match event {
+            WindowEvent::Resized(size) => {
+                if let Some(per_window) = windows.get_mut(&window_id) {
+                    per_window.resize(&state.device, *size);
+                    per_window.window.request_redraw();
+                }
+            }
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                if let Some(per_window) = windows.get_mut(&window_id) {
+                    per_window.resize(&state.device, **new_inner_size);
+                    per_window.window.request_redraw();
+                }
+            }
+            WindowEvent::CloseRequested => {
+                windows.remove(&window_id);
+                if windows.is_empty() && !main_window_open {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            _ => {}
+        },
 
-                    // Mouse movement
-                    WindowEvent::CursorMoved { position, .. } => {
-                        let percent_of_screen_width = position.x / state.size.width as f64;
-                        let percent_of_screen_height = position.y / state.size.height as f64;
-                        state.color = wgpu::Color {
-                            r: percent_of_screen_width,
-                            g: percent_of_screen_height,
-                            ..state.color
-                        };
-                    }
+        Event::WindowEvent {
+            event: WindowEvent::Occluded(occluded),
+            window_id,
+        } if window_id == state.window().id() => {
+            state.occluded = occluded;
+            if occluded {
+                log::info!("The window is hidden, so not drawing it");
+            } else {
+                log::info!("The window is visible again");
+                // Otherwise it could stay black until something else asks for a redraw.
+                state.window().request_redraw();
+            }
+        }
 
-                    _ => {}
+        Event::WindowEvent {
+            ref event,
+            window_id,
+        } if window_id == state.window().id() && !state.input(event) => {
+            let Some(input) = InputEvent::from_window_event(event) else {
+                return;
+            };
+            // While replaying, live input is ignored. Except for quitting, and the window
+            // actually resizing, because the surface has to match the window.
+            let allowed_during_replay = matches!(
+                input,
+                InputEvent::CloseRequested
+                    | InputEvent::KeyPressed(VirtualKeyCode::Escape)
+                    | InputEvent::Resized(_)
+            );
+            if replay.is_some() && !allowed_during_replay {
+                return;
+            }
+            if let Some(recorder) = &mut input_recorder {
+                recorder.record(start.elapsed(), &input);
+            }
+            match input {
+                InputEvent::CloseRequested if !windows.is_empty() => {
+                    state.window().set_visible(false);
+                    main_window_open = false;
+                    log::info!("Closed the main window, the others are still open");
                 }
+                InputEvent::CloseRequested | InputEvent::KeyPressed(VirtualKeyCode::Escape) => {
+                    *control_flow = ControlFlow::Exit
+                }
+                input => state.handle_input(input),
+            }
+            // Only matters with --power-save. Otherwise every frame gets redrawn anyway.
+            state.window().request_redraw();
+            for per_window in windows.values() {
+                per_window.window.request_redraw();
             }
         }
         // TODO: Support window resize events
         _ => {}
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rerolling_the_logged_seed_gives_the_same_layout() {
+        // Set up like `State::new` does for --scatter.
+        let scatter = ScatterBuilder::new(20).texture_layers(TEXTURE_LAYERS.len() as u32);
+        // As if N rerolled to this, and it was copied out of the log.
+        let logged_seed = rand::random();
+        let first = scatter_layout(&scatter, logged_seed);
+        // Reroll a few more times, then go back with --seed (or `set_seed`).
+        for _ in 0..3 {
+            scatter_layout(&scatter, rand::random());
+        }
+        assert_eq!(scatter_layout(&scatter, logged_seed), first);
+        // Models' texture layer goes on top, the same both times.
+        let mut again = scatter_layout(&scatter, logged_seed);
+        let mut first_with_model = first.clone();
+        use_model_texture(&mut first_with_model, Some(2));
+        use_model_texture(&mut again, Some(2));
+        assert_eq!(again, first_with_model);
+    }
+
+    /// Depth of `point` after projecting it with `camera` and dividing by w.
+    fn depth(camera: &Camera, point: cgmath::Point3<f32>) -> f32 {
+        let clip = camera.build_view_projection_matrix() * point.to_homogeneous();
+        clip.z / clip.w
+    }
+
+    /// Whether a fragment at `new` depth would be drawn over one at `old`.
+    fn passes(compare: wgpu::CompareFunction, new: f32, old: f32) -> bool {
+        match compare {
+            wgpu::CompareFunction::Less => new < old,
+            wgpu::CompareFunction::LessEqual => new <= old,
+            wgpu::CompareFunction::Greater => new > old,
+            wgpu::CompareFunction::GreaterEqual => new >= old,
+            _ => unreachable!("only the scene's depth tests are checked"),
+        }
+    }
+
+    #[test]
+    fn near_fragments_win_with_either_z() {
+        // The camera looks down -Z from z = 2.4, so these are 1.4 and 50 units in front of it.
+        let near = cgmath::Point3::new(0.0, 0.0, 1.0);
+        let far = cgmath::Point3::new(0.0, 0.0, -47.6);
+        for reverse_z in [false, true] {
+            for unclipped_depth in [false, true] {
+                let compare = depth_compare(PipelineOptions {
+                    target_format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    sample_count: 1,
+                    cull_mode: None,
+                    vertex_layout: VertexLayout::Interleaved,
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    normals_target: false,
+                    reverse_z,
+                    alpha_to_coverage: false,
+                    unclipped_depth,
+                    sample_shading: false,
+                });
+                let cleared = far_depth(reverse_z);
+                let camera = Camera {
+                    reverse_z,
+                    ..Camera::new(1.0)
+                };
+                let (near, far) = (depth(&camera, near), depth(&camera, far));
+                let mode = format!("reverse_z {reverse_z}, unclipped_depth {unclipped_depth}");
+                assert!((0.0..=1.0).contains(&near), "{near}");
+                assert!((0.0..=1.0).contains(&far), "{far}");
+                assert!(passes(compare, near, cleared), "{mode}");
+                assert!(passes(compare, far, cleared), "{mode}");
+                assert!(passes(compare, near, far), "{mode}");
+                assert!(!passes(compare, far, near), "{mode}");
+                // Anything past the far plane gets clamped onto it, and still has to be drawn.
+                assert_eq!(passes(compare, cleared, cleared), unclipped_depth, "{mode}");
+            }
+        }
+    }
+
+    #[test]
+    fn spin_rate_is_the_same_at_any_frame_rate() {
+        let spin_for_a_second = |fps: u32| {
+            (0..fps).fold(Rad(0.0), |angle, _| {
+                advance_spin(angle, Duration::from_secs(1) / fps)
+            })
+        };
+        let (at_30, at_144) = (spin_for_a_second(30), spin_for_a_second(144));
+        assert!((at_30.0 - at_144.0).abs() < 1e-4, "{at_30:?} != {at_144:?}");
+        assert!((at_30.0 - SPIN_RADIANS_PER_SEC).abs() < 1e-4, "{at_30:?}");
+    }
+
+    #[test]
+    fn demo_orbit_is_the_same_at_any_frame_rate() {
+        let orbit_for_two_seconds = |fps: u32| {
+            let mut camera = Camera::new(1.0);
+            let start = camera.eye;
+            for _ in 0..fps * 2 {
+                demo_orbit(&mut camera, Duration::from_secs(1) / fps);
+                // Smooth: it stays on the circle, taking small steps.
+                let radius = (start - camera.target).magnitude();
+                assert!(((camera.eye - camera.target).magnitude() - radius).abs() < 1e-4);
+            }
+            camera.eye
+        };
+        let (at_30, at_144) = (orbit_for_two_seconds(30), orbit_for_two_seconds(144));
+        assert!(
+            (at_30 - at_144).magnitude() < 1e-4,
+            "{at_30:?} != {at_144:?}"
+        );
+        assert!((at_30 - Camera::new(1.0).eye).magnitude() > 0.1);
+    }
+}
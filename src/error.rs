@@ -0,0 +1,165 @@
+use std::fmt;
+
+/// Everything that can go wrong while setting up the GPU in `State::new`.
+#[derive(Debug)]
+pub enum StateError {
+    CreateSurface(wgpu::CreateSurfaceError),
+    /// No graphics card could draw to our window.
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The surface doesn't support any texture formats, so there's nothing to render into.
+    /// Happens with some headless or misconfigured adapters.
+    NoSurfaceFormat,
+    /// --format asked for a format the surface can't use.
+    UnsupportedSurfaceFormat {
+        requested: String,
+        available: Vec<String>,
+    },
+    NoPresentMode,
+    NoAlphaMode,
+    /// One of the embedded textures couldn't be loaded.
+    Texture(anyhow::Error),
+    /// The embedded font for on-screen text couldn't be parsed.
+    Font(wgpu_text::font::InvalidFont),
+    /// The built-in scene shader didn't compile.
+    Shader(crate::shader::ShaderError),
+    /// The --gltf file couldn't be loaded.
+    Model(anyhow::Error),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreateSurface(e) => write!(f, "could not create a surface for the window: {e}"),
+            Self::NoAdapter => write!(f, "no suitable graphics card available"),
+            Self::RequestDevice(e) => write!(f, "could not open the graphics device: {e}"),
+            Self::NoSurfaceFormat => write!(f, "the surface doesn't support any texture formats"),
+            Self::UnsupportedSurfaceFormat {
+                requested,
+                available,
+            } => write!(
+                f,
+                "the surface doesn't support format {requested}, try one of: {}",
+                available.join(", ")
+            ),
+            Self::NoPresentMode => write!(f, "the surface doesn't support any present modes"),
+            Self::NoAlphaMode => write!(f, "the surface doesn't support any alpha modes"),
+            Self::Texture(e) => write!(f, "could not load texture: {e}"),
+            Self::Font(e) => write!(f, "could not load font: {e}"),
+            Self::Shader(e) => write!(f, "could not load shader: {e}"),
+            Self::Model(e) => write!(f, "could not load model: {e:#}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::CreateSurface(e) => Some(e),
+            Self::RequestDevice(e) => Some(e),
+            Self::Texture(e) => Some(e.as_ref()),
+            Self::Font(e) => Some(e),
+            Self::Shader(e) => Some(e),
+            Self::Model(e) => Some(e.as_ref()),
+            Self::NoAdapter
+            | Self::NoSurfaceFormat
+            | Self::UnsupportedSurfaceFormat { .. }
+            | Self::NoPresentMode
+            | Self::NoAlphaMode => None,
+        }
+    }
+}
+
+/// Exit codes, so scripts can tell what went wrong without reading the log. Anything not covered
+/// by a more specific code exits with 1.
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_NO_ADAPTER: i32 = 2;
+pub const EXIT_CONFIG: i32 = 3;
+
+/// Everything that can stop the demo from starting, for `draw::run` to hand back to `main`.
+#[derive(Debug)]
+pub enum AppError {
+    /// With --strict, these flags were unknown or had invalid values.
+    Args {
+        rejected: Vec<String>,
+    },
+    /// The OS wouldn't open the window.
+    Window(winit::error::OsError),
+    State(StateError),
+    /// The --record-input file couldn't be created.
+    RecordInput(anyhow::Error),
+    /// The --replay-input file couldn't be read, or isn't an input log.
+    ReplayInput(anyhow::Error),
+}
+
+impl AppError {
+    /// What the process should exit with: 2 if there's no graphics card to use, 3 if the flags
+    /// asked for something which can't work (e.g. a file that won't load, a format the surface
+    /// doesn't have, or with --strict, a bad value), 1 for anything else.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::State(StateError::NoAdapter) => EXIT_NO_ADAPTER,
+            Self::State(StateError::UnsupportedSurfaceFormat { .. } | StateError::Model(_))
+            | Self::Args { .. }
+            | Self::ReplayInput(_) => EXIT_CONFIG,
+            Self::Window(_) | Self::State(_) | Self::RecordInput(_) => EXIT_FAILURE,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Args { rejected } => write!(
+                f,
+                "--strict, and these flags were unknown or invalid: {}",
+                rejected.join(", ")
+            ),
+            Self::Window(e) => write!(f, "could not open the window: {e}"),
+            Self::State(e) => e.fmt(f),
+            Self::RecordInput(e) => write!(f, "could not record input: {e:#}"),
+            Self::ReplayInput(e) => write!(f, "could not replay input: {e:#}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Window(e) => Some(e),
+            Self::State(e) => e.source(),
+            Self::RecordInput(e) | Self::ReplayInput(e) => Some(e.as_ref()),
+            Self::Args { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejected_flags_are_a_config_error() {
+        let error = AppError::Args {
+            rejected: vec!["--msaa".to_owned()],
+        };
+        assert_eq!(error.exit_code(), EXIT_CONFIG);
+    }
+
+    #[test]
+    fn state_errors_exit_with_their_own_codes() {
+        assert_eq!(
+            AppError::State(StateError::NoAdapter).exit_code(),
+            EXIT_NO_ADAPTER
+        );
+        let unsupported = StateError::UnsupportedSurfaceFormat {
+            requested: "bogus".to_owned(),
+            available: Vec::new(),
+        };
+        assert_eq!(AppError::State(unsupported).exit_code(), EXIT_CONFIG);
+        assert_eq!(
+            AppError::State(StateError::NoPresentMode).exit_code(),
+            EXIT_FAILURE
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::{collections::VecDeque, time::Duration};
+
+use wgpu::util::DeviceExt;
+
+use crate::uniform::Uniform;
+
+/// How many frames the graph shows, newest on the right.
+const HISTORY: usize = 120;
+
+/// Size of the graph, and its gap from the bottom-left corner of the window, in logical pixels.
+/// Multiplied by the window's scale factor, like the HUD's text.
+const GRAPH_WIDTH: f32 = 240.0;
+const GRAPH_HEIGHT: f32 = 80.0;
+const MARGIN: f32 = 10.0;
+
+/// Corresponds to GraphUniform in frame_graph.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GraphUniform {
+    /// Moves points from pixels (0, 0 in the top-left corner, y pointing down) into clip space.
+    proj: [[f32; 4]; 4],
+}
+
+impl GraphUniform {
+    fn new(width: u32, height: u32) -> Self {
+        // Orthographic, so a pixel is the same size everywhere, and flipped so y points down.
+        let proj = cgmath::ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
+        Self { proj: proj.into() }
+    }
+}
+
+/// A scrolling graph of the last HISTORY frame times, drawn as a line strip in the bottom-left
+/// corner of the window. The tallest frame reaches the top, so a hitch squashes everything else
+/// flat. G toggles it.
+///
+/// Drawn in its own pass, straight onto the surface, so the scene's camera and resolution don't
+/// affect it.
+pub struct FrameGraph {
+    pub enabled: bool,
+    /// Seconds, oldest first.
+    times: VecDeque<f32>,
+    /// Physical pixels per logical pixel.
+    scale_factor: f32,
+    window_height: f32,
+    /// One point per frame in `times`, in pixels. Rewritten every frame, because every point
+    /// moves left one place and the scale may change.
+    vertex_buffer: wgpu::Buffer,
+    proj: Uniform<GraphUniform>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl FrameGraph {
+    /// `target_format` is the format of the texture the graph gets drawn onto, i.e. the surface.
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        target_format: wgpu::TextureFormat,
+        scale_factor: f64,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("frame_graph_vertex_buffer"),
+            contents: bytemuck::cast_slice(&[[0.0f32; 2]; HISTORY]),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let proj = Uniform::new(
+            device,
+            GraphUniform::new(width, height),
+            wgpu::ShaderStages::VERTEX,
+            "frame_graph_uniform",
+        );
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Frame Graph Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("frame_graph.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Frame Graph Pipeline Layout"),
+            bind_group_layouts: &[&proj.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Frame Graph Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                // Each point joins onto the one before, so HISTORY points make HISTORY - 1 lines.
+                topology: wgpu::PrimitiveTopology::LineStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Self {
+            enabled: false,
+            times: VecDeque::with_capacity(HISTORY),
+            scale_factor: scale_factor as f32,
+            window_height: height as f32,
+            vertex_buffer,
+            proj,
+            pipeline,
+        }
+    }
+
+    /// Same as `Hud::resize`: points are in pixels, so it needs to know the new window size.
+    pub fn resize(&mut self, queue: &wgpu::Queue, width: u32, height: u32, scale_factor: f64) {
+        self.scale_factor = scale_factor as f32;
+        self.window_height = height as f32;
+        self.proj.set(GraphUniform::new(width, height));
+        self.proj.flush(queue);
+    }
+
+    /// Add this frame's time and upload the new points. Call once per frame, before `draw`.
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: Duration) {
+        if self.times.len() == HISTORY {
+            self.times.pop_front();
+        }
+        self.times.push_back(dt.as_secs_f32());
+        if !self.enabled {
+            return;
+        }
+
+        // Scale so the slowest frame just reaches the top. The max is never 0, so no dividing by 0.
+        let max = self.times.iter().copied().fold(f32::EPSILON, f32::max);
+        let width = GRAPH_WIDTH * self.scale_factor;
+        let height = GRAPH_HEIGHT * self.scale_factor;
+        let margin = MARGIN * self.scale_factor;
+        let bottom = self.window_height - margin;
+        let step = width / (HISTORY - 1) as f32;
+        // Right-aligned, so the newest frame is always in the same place while the graph fills up.
+        let first_x = margin + (HISTORY - self.times.len()) as f32 * step;
+        let points: Vec<[f32; 2]> = self
+            .times
+            .iter()
+            .enumerate()
+            .map(|(i, &time)| [first_x + i as f32 * step, bottom - time / max * height])
+            .collect();
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&points));
+    }
+
+    /// Draw the graph onto `view`, over whatever's already there, if it's enabled.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        // A strip needs at least two points to make a line.
+        if !self.enabled || self.times.len() < 2 {
+            return;
+        }
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Frame Graph Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, self.proj.bind_group(), &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(0..self.times.len() as u32, 0..1);
+    }
+}
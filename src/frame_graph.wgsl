@@ -0,0 +1,19 @@
+// The frame-time graph: one point per frame, joined up as a line strip.
+
+// Corresponds to GraphUniform in the Rust code.
+struct GraphUniform {
+    proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> graph: GraphUniform;
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return graph.proj * vec4<f32>(position, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    // Bright green shows up against most scenes.
+    return vec4<f32>(0.2, 1.0, 0.3, 1.0);
+}
@@ -0,0 +1,75 @@
+use cgmath::{InnerSpace, Matrix, Matrix4, Point3, Vector4};
+
+/// The space a camera can see, between its near and far planes and inside the edges of the
+/// screen, for skipping objects which would end up entirely off screen anyway.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near, far. Each plane is (a, b, c, d), with (a, b, c) normalized
+    /// and pointing inwards, so a*x + b*y + c*z + d is how far inside that plane (x, y, z) is.
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// The frustum `view_proj` maps into clip space. The planes fall straight out of the matrix's
+    /// rows (Gribb and Hartmann's method): a point is inside when its clip space position has
+    /// -w <= x <= w, -w <= y <= w and 0 <= z <= w, and each of those comparisons is a plane in
+    /// world space. wgpu's z goes from 0 to w rather than -w to w like OpenGL's, which only
+    /// changes the near plane. Works with reversed Z too, which just swaps near and far.
+    pub fn from_view_proj(view_proj: Matrix4<f32>) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z]
+            .map(|plane| plane / plane.truncate().magnitude());
+        Self { planes }
+    }
+
+    /// False only if the sphere is entirely outside one of the planes. A sphere near a corner
+    /// can be outside the frustum without being outside any one plane, so this sometimes says
+    /// yes when the answer's no, which only costs drawing something that's off screen.
+    pub fn contains_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+        let center = center.to_homogeneous();
+        self.planes.iter().all(|plane| plane.dot(center) >= -radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// With no transform, the frustum is clip space itself: x and y from -1 to 1, z from 0 to 1.
+    fn clip_space() -> Frustum {
+        Frustum::from_view_proj(Matrix4::from_scale(1.0))
+    }
+
+    #[test]
+    fn sphere_inside_is_contained() {
+        assert!(clip_space().contains_sphere(Point3::new(0.0, 0.0, 0.5), 0.1));
+        assert!(clip_space().contains_sphere(Point3::new(-0.8, 0.8, 0.9), 0.1));
+    }
+
+    #[test]
+    fn sphere_outside_is_not_contained() {
+        let frustum = clip_space();
+        assert!(!frustum.contains_sphere(Point3::new(3.0, 0.0, 0.5), 0.5));
+        assert!(!frustum.contains_sphere(Point3::new(0.0, -3.0, 0.5), 0.5));
+        // Behind the near plane, and past the far one.
+        assert!(!frustum.contains_sphere(Point3::new(0.0, 0.0, -1.0), 0.5));
+        assert!(!frustum.contains_sphere(Point3::new(0.0, 0.0, 2.0), 0.5));
+    }
+
+    #[test]
+    fn sphere_straddling_the_boundary_is_contained() {
+        let frustum = clip_space();
+        // The centre's outside, but the sphere reaches back in.
+        assert!(frustum.contains_sphere(Point3::new(1.2, 0.0, 0.5), 0.5));
+        assert!(frustum.contains_sphere(Point3::new(0.0, 0.0, -0.2), 0.5));
+    }
+
+    #[test]
+    fn distances_are_in_world_units() {
+        // Doubling everything shrinks the frustum to half the size in world space. The planes are
+        // normalized, so the radius is still compared in world units.
+        let frustum = Frustum::from_view_proj(Matrix4::from_nonuniform_scale(2.0, 2.0, 1.0));
+        assert!(!frustum.contains_sphere(Point3::new(0.6, 0.0, 0.5), 0.05));
+        assert!(frustum.contains_sphere(Point3::new(0.6, 0.0, 0.5), 0.15));
+    }
+}
@@ -0,0 +1,156 @@
+use wgpu::util::DeviceExt;
+
+use crate::texture::Texture;
+
+/// How far the grid reaches from the origin along X and Z, and how far apart its lines are, in
+/// world units. The pentagon is about 1 across, so this gives a few lines under it.
+const GRID_EXTENT: f32 = 5.0;
+const GRID_SPACING: f32 = 0.5;
+
+const GRID_COLOR: [f32; 3] = [0.35, 0.35, 0.35];
+const X_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+const Y_COLOR: [f32; 3] = [0.0, 1.0, 0.0];
+const Z_COLOR: [f32; 3] = [0.0, 0.0, 1.0];
+
+/// Corresponds to the vertex inputs in grid.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+/// The lines making up the gizmo, two vertices each: the X, Y and Z axes (red, green and blue),
+/// reaching `extent` from the origin in the positive direction, then a grid on the XZ plane from
+/// `-extent` to `extent`, with lines every `spacing`.
+///
+/// The axes come first. Where they lie on top of a grid line, the depth test only lets the first
+/// one drawn through, so the axes stay visible.
+fn grid_lines(extent: f32, spacing: f32) -> Vec<GridVertex> {
+    let line = |from: [f32; 3], to: [f32; 3], color| {
+        [
+            GridVertex {
+                position: from,
+                color,
+            },
+            GridVertex {
+                position: to,
+                color,
+            },
+        ]
+    };
+    let mut vertices = Vec::new();
+    vertices.extend(line([0.0; 3], [extent, 0.0, 0.0], X_COLOR));
+    vertices.extend(line([0.0; 3], [0.0, extent, 0.0], Y_COLOR));
+    vertices.extend(line([0.0; 3], [0.0, 0.0, extent], Z_COLOR));
+    // Round down, so a spacing which doesn't divide the extent exactly doesn't poke past the edge.
+    let steps = (extent / spacing).floor() as i32;
+    for i in -steps..=steps {
+        let offset = i as f32 * spacing;
+        // Parallel to X, then parallel to Z.
+        vertices.extend(line(
+            [-extent, 0.0, offset],
+            [extent, 0.0, offset],
+            GRID_COLOR,
+        ));
+        vertices.extend(line(
+            [offset, 0.0, -extent],
+            [offset, 0.0, extent],
+            GRID_COLOR,
+        ));
+    }
+    vertices
+}
+
+/// A grid on the XZ plane, plus the X, Y and Z axes, for getting your bearings when the camera's
+/// somewhere unfamiliar. Q toggles it.
+///
+/// Drawn in the scene pass with the scene's pipeline layout, so it uses whichever camera bind group
+/// the scene does, and the depth test hides the lines behind objects.
+pub struct Grid {
+    pub enabled: bool,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Grid {
+    /// Like the particles, the grid is drawn in the scene pass, so it has to match the scene's
+    /// format, sample count and targets, and test depth the same way.
+    pub fn new(
+        device: &wgpu::Device,
+        scene_layout: &wgpu::PipelineLayout,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+        normals_target: bool,
+        depth_compare: wgpu::CompareFunction,
+    ) -> Self {
+        let vertices = grid_lines(GRID_EXTENT, GRID_SPACING);
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("grid_vertex_buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Grid Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("grid.wgsl").into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Grid Pipeline"),
+            layout: Some(scene_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GridVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &crate::normals::scene_targets(
+                    target_format,
+                    wgpu::BlendState::REPLACE,
+                    normals_target,
+                ),
+            }),
+            primitive: wgpu::PrimitiveState {
+                // Every two vertices make a separate line.
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            // Writes depth like an opaque object, so transparent objects behind a line don't get
+            // drawn over it.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+        Self {
+            enabled: false,
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+            pipeline,
+        }
+    }
+
+    /// Draw the grid, if it's enabled. Expects the scene's bind groups to be set already, and
+    /// leaves them set, so the scene can carry on drawing afterwards.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.enabled {
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.draw(0..self.vertex_count, 0..1);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+// The orientation grid and axes: coloured lines in world space, drawn in the scene pass.
+
+// Same as in common.wgsl. The grid uses the scene's pipeline layout, so the camera is in the same
+// group as for the scene shaders.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    eye_position: vec4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) color: vec3<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+// The normal is for --normals-target. Lines have no surface, so like the background they get a
+// zero normal.
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(in.color, 1.0);
+    out.normal = vec4<f32>(0.5, 0.5, 0.5, 0.0);
+    return out;
+}
@@ -0,0 +1,281 @@
+use crate::{
+    blit::{
+        create_bind_group, create_bind_group_layout, create_loupe_bind_group, create_sampler,
+        create_target_view, set_loupe_region,
+    },
+    color::ColorMatrix,
+    loupe::LoupeRegion,
+    uniform::Uniform,
+};
+
+/// Format of the intermediate texture the scene gets drawn into.
+/// 16-bit floats can store colours brighter than 1.0, which the surface can't.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Can this adapter render into (and then sample from) an HDR texture?
+pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_texture_format_features(HDR_FORMAT)
+        .allowed_usages
+        .contains(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+}
+
+/// Which tonemap operator to start with. T cycles through the others.
+const INITIAL_TONEMAP: Tonemap = Tonemap::Reinhard;
+
+/// How to squash HDR colours into the 0..1 range.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tonemap {
+    Reinhard,
+    Aces,
+}
+
+impl Tonemap {
+    fn next(self) -> Self {
+        match self {
+            Self::Reinhard => Self::Aces,
+            Self::Aces => Self::Reinhard,
+        }
+    }
+}
+
+/// What to do to the colours after tonemapping, to check the scene still makes sense to people
+/// who see colour differently. F4 cycles through them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorFilter {
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+    Grayscale,
+}
+
+impl ColorFilter {
+    fn next(self) -> Self {
+        match self {
+            Self::None => Self::Protanopia,
+            Self::Protanopia => Self::Deuteranopia,
+            Self::Deuteranopia => Self::Tritanopia,
+            Self::Tritanopia => Self::Grayscale,
+            Self::Grayscale => Self::None,
+        }
+    }
+
+    fn matrix(self) -> ColorMatrix {
+        use crate::color::{DEUTERANOPIA, GRAYSCALE, PROTANOPIA, TRITANOPIA};
+        match self {
+            Self::None => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            Self::Protanopia => PROTANOPIA,
+            Self::Deuteranopia => DEUTERANOPIA,
+            Self::Tritanopia => TRITANOPIA,
+            Self::Grayscale => GRAYSCALE,
+        }
+    }
+}
+
+/// Corresponds to TonemapUniform in tonemap.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapUniform {
+    /// `ColorFilter::matrix`, as WGSL's mat3x3 wants it: columns rather than rows, each padded
+    /// to 16 bytes like a vec4.
+    color_filter: [[f32; 4]; 3],
+    mode: u32,
+    /// Uniform structs are a multiple of 16 bytes long, so WGSL's has this much padding on the
+    /// end too.
+    _padding: [u32; 3],
+}
+
+impl TonemapUniform {
+    fn new(tonemap: Tonemap, filter: ColorFilter) -> Self {
+        let mode = match tonemap {
+            Tonemap::Reinhard => 0,
+            Tonemap::Aces => 1,
+        };
+        let rows = filter.matrix();
+        Self {
+            color_filter: [0, 1, 2]
+                .map(|column| [rows[0][column], rows[1][column], rows[2][column], 0.0]),
+            mode,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// The scene renders into this HDR texture, then `process` tonemaps it onto the surface, and
+/// filters the colours if there's a `ColorFilter`.
+pub struct HdrPipeline {
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    /// The same texture with a Nearest sampler, for the loupe.
+    loupe_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    tonemap: Tonemap,
+    color_filter: ColorFilter,
+    tonemap_uniform: Uniform<TonemapUniform>,
+}
+
+impl HdrPipeline {
+    /// `width` and `height` are the scene's render resolution, which can differ from the surface's.
+    /// `filter` is how the HDR texture gets scaled to fit the surface if they do differ.
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        filter: wgpu::FilterMode,
+    ) -> Self {
+        let view = create_target_view(device, "hdr_texture", HDR_FORMAT, width, height);
+        let sampler = create_sampler(device, filter, "hdr_sampler");
+        let layout = create_bind_group_layout(device, "hdr_bind_group_layout");
+        let bind_group = create_bind_group(device, &layout, &view, &sampler, "hdr_bind_group");
+        let loupe_bind_group = create_loupe_bind_group(device, &layout, &view, "hdr");
+
+        let tonemap = INITIAL_TONEMAP;
+        let color_filter = ColorFilter::None;
+        let tonemap_uniform = Uniform::new(
+            device,
+            TonemapUniform::new(tonemap, color_filter),
+            wgpu::ShaderStages::FRAGMENT,
+            "tonemap_uniform",
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Tonemap Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("tonemap.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tonemap Pipeline Layout"),
+            bind_group_layouts: &[&layout, &tonemap_uniform.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tonemap Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                // The fullscreen triangle is generated from vertex indices, no buffer needed.
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            view,
+            sampler,
+            layout,
+            bind_group,
+            loupe_bind_group,
+            pipeline,
+            tonemap,
+            color_filter,
+            tonemap_uniform,
+        }
+    }
+
+    /// The HDR texture has to match the render resolution, so recreate it whenever that changes.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.view = create_target_view(device, "hdr_texture", HDR_FORMAT, width, height);
+        self.bind_group = create_bind_group(
+            device,
+            &self.layout,
+            &self.view,
+            &self.sampler,
+            "hdr_bind_group",
+        );
+        self.loupe_bind_group = create_loupe_bind_group(device, &self.layout, &self.view, "hdr");
+    }
+
+    /// Where the scene should render to.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn cycle_tonemap(&mut self, queue: &wgpu::Queue) {
+        self.set_tonemap(queue, self.tonemap.next());
+        log::info!("Tonemapping with {:?}", self.tonemap);
+    }
+
+    pub fn cycle_color_filter(&mut self, queue: &wgpu::Queue) {
+        self.color_filter = self.color_filter.next();
+        self.upload(queue);
+        log::info!("Colour filter: {:?}", self.color_filter);
+    }
+
+    /// Go back to the tonemap operator it started with, and no colour filter.
+    pub fn reset(&mut self, queue: &wgpu::Queue) {
+        self.color_filter = ColorFilter::None;
+        self.set_tonemap(queue, INITIAL_TONEMAP);
+    }
+
+    fn set_tonemap(&mut self, queue: &wgpu::Queue, tonemap: Tonemap) {
+        self.tonemap = tonemap;
+        self.upload(queue);
+    }
+
+    fn upload(&mut self, queue: &wgpu::Queue) {
+        self.tonemap_uniform
+            .set(TonemapUniform::new(self.tonemap, self.color_filter));
+        self.tonemap_uniform.flush(queue);
+    }
+
+    /// Tonemap the HDR texture onto `output`, which is usually the surface.
+    pub fn process(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        self.draw(encoder, output, &self.bind_group, None);
+    }
+
+    /// Draw the loupe's inset onto `output`, over what `process` drew. See `Loupe`.
+    pub fn process_loupe(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        region: &LoupeRegion,
+    ) {
+        self.draw(encoder, output, &self.loupe_bind_group, Some(region));
+    }
+
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        bind_group: &wgpu::BindGroup,
+        region: Option<&LoupeRegion>,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // Every pixel gets overwritten by the fullscreen triangle, so don't bother
+                    // clearing.
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        if let Some(region) = region {
+            set_loupe_region(&mut pass, region);
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.set_bind_group(1, self.tonemap_uniform.bind_group(), &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
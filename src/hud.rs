@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use crate::backend_quirks::FrameCapture;
+
+use wgpu_text::{
+    font::FontRef,
+    section::{Section, Text},
+    BrushBuilder, TextBrush,
+};
+
+/// DejaVu Sans Mono, which is free to redistribute (see https://dejavu-fonts.github.io/License.html).
+/// Monospaced so the columns in the help text line up.
+const FONT: &[u8] = include_bytes!("DejaVuSansMono.ttf");
+
+/// Text height in logical pixels. Multiplied by the window's scale factor, so it's the same size on
+/// high-DPI screens.
+const FONT_SIZE: f32 = 16.0;
+
+/// Gap between the text and the edge of the window, in logical pixels.
+const MARGIN: f32 = 10.0;
+
+/// Shown when H is pressed. Keep this up to date when adding keybindings! `{capture}` is filled in
+/// by `help_text`.
+const HELP_TEXT: &str = "\
+Space   next pipeline
+T       next tonemap operator
+F4      next colour blindness filter
+F5      toggle the loupe around the cursor
+C       next cull mode
+K       toggle alpha to coverage (with MSAA)
+Z       toggle sample-rate shading (with MSAA)
+U       toggle unclipped depth
+R       next sampler address mode
+P       cycle the clear colour's hue
+M       toggle the mouse setting the clear colour
+B       toggle the gradient background
+Y       toggle the skybox
+Q       toggle the grid and axes
+F3      toggle bounding boxes
+F6      toggle the wireframe
+F7/F8   thinner/thicker wireframe
+V       toggle the overdraw heatmap
+J       toggle trails (don't clear between frames)
+E       clear the trails
+N       reroll the scatter with a new seed
+X       toggle the texture coordinates view
+F       flip v in the texture coordinates view
+Tab     select the next object
+O       cycle the selected object's tint
+W/S     move the camera in/out
+A/D     orbit the camera
+-/=     zoom out/in (or scroll)
+I       log what's being drawn
+F12     {capture}
+Click   log the clicked pixel and its ray
+G       toggle the frame time graph
+Arrows  turn the light
+[/]     dim/brighten the light
+L       toggle the light
+1/2     less/more ambient light
+3/4     less/more specular
+5/6     less/more shininess
+Home    reset everything above
+H       toggle this help
+Esc     quit";
+
+/// How quickly the displayed FPS follows the real one. Lower is smoother but slower to react.
+const FPS_SMOOTHING: f32 = 0.1;
+
+/// Text drawn over the top of everything else: an FPS counter, and optionally the controls.
+pub struct Hud {
+    brush: TextBrush<FontRef<'static>>,
+    /// Physical pixels per logical pixel.
+    scale_factor: f32,
+    pub show_help: bool,
+    /// `HELP_TEXT`, filled in for this backend.
+    help_text: String,
+    /// Smoothed, otherwise the number changes too fast to read.
+    fps: f32,
+}
+
+impl Hud {
+    /// `target_format` is the format of the texture the text gets drawn onto, i.e. the surface.
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        target_format: wgpu::TextureFormat,
+        scale_factor: f64,
+        frame_capture: Option<FrameCapture>,
+    ) -> Result<Self, wgpu_text::font::InvalidFont> {
+        let mut brush = BrushBuilder::using_font_bytes(FONT)?.build_custom(
+            device,
+            width,
+            height,
+            target_format,
+        );
+        // Draw on top of the finished scene, rather than clearing it.
+        brush.set_load_op(wgpu::LoadOp::Load);
+        Ok(Self {
+            brush,
+            scale_factor: scale_factor as f32,
+            show_help: false,
+            help_text: help_text(frame_capture),
+            fps: 0.0,
+        })
+    }
+
+    /// Text is positioned in pixels, so it needs to know the new window size. The scale factor
+    /// changes if the window moves to a screen with a different DPI.
+    pub fn resize(&mut self, queue: &wgpu::Queue, width: u32, height: u32, scale_factor: f64) {
+        self.scale_factor = scale_factor as f32;
+        self.brush.resize_view(width as f32, height as f32, queue);
+    }
+
+    /// Lay out this frame's text and upload it. Call once per frame, before `draw`.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, dt: Duration) {
+        let secs = dt.as_secs_f32();
+        if secs > 0.0 {
+            self.fps += (1.0 / secs - self.fps) * FPS_SMOOTHING;
+        }
+
+        let size = FONT_SIZE * self.scale_factor;
+        let margin = MARGIN * self.scale_factor;
+        let fps_text = format!("{:.0} FPS (H for help)", self.fps);
+        self.brush.queue(
+            Section::default()
+                .add_text(
+                    Text::new(&fps_text)
+                        .with_scale(size)
+                        .with_color([1.0, 1.0, 1.0, 1.0]),
+                )
+                .with_screen_position((margin, margin)),
+        );
+        if self.show_help {
+            self.brush.queue(
+                Section::default()
+                    .add_text(
+                        Text::new(&self.help_text)
+                            .with_scale(size)
+                            .with_color([1.0, 1.0, 0.6, 1.0]),
+                    )
+                    // Leave a blank line under the FPS counter.
+                    .with_screen_position((margin, margin + size * 2.0)),
+            );
+        }
+        if let Err(e) = self.brush.process_queued(device, queue) {
+            log::error!("Couldn't lay out the HUD text: {e}");
+        }
+    }
+
+    /// Draws the text in its own render pass, loading (not clearing) whatever's in `view`.
+    pub fn draw(&mut self, device: &wgpu::Device, view: &wgpu::TextureView) -> wgpu::CommandBuffer {
+        self.brush.draw(device, view)
+    }
+}
+
+/// `HELP_TEXT`, saying what F12 captures frames with on this backend, if anything.
+fn help_text(frame_capture: Option<FrameCapture>) -> String {
+    let capture = match frame_capture {
+        Some(FrameCapture::RenderDoc) => "capture a frame in RenderDoc",
+        Some(FrameCapture::Xcode) => "capture a frame with Xcode",
+        None => "capture a frame (not on this backend)",
+    };
+    HELP_TEXT.replace("{capture}", capture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_names_the_capture_tool() {
+        let f12 = |frame_capture| {
+            help_text(frame_capture)
+                .lines()
+                .find(|line| line.starts_with("F12"))
+                .unwrap()
+                .to_owned()
+        };
+        assert!(f12(Some(FrameCapture::RenderDoc)).ends_with("in RenderDoc"));
+        assert!(f12(Some(FrameCapture::Xcode)).ends_with("with Xcode"));
+        assert!(f12(None).ends_with("(not on this backend)"));
+    }
+}
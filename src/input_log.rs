@@ -0,0 +1,268 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+    },
+};
+
+/// Keys the demo does something with, i.e. the only ones worth recording. winit can't turn a
+/// key's name back into a `VirtualKeyCode`, so this is also how replaying finds them.
+const KEYS: &[VirtualKeyCode] = &[
+    VirtualKeyCode::Escape,
+    VirtualKeyCode::Space,
+    VirtualKeyCode::Tab,
+    VirtualKeyCode::Equals,
+    VirtualKeyCode::Home,
+    VirtualKeyCode::Minus,
+    VirtualKeyCode::A,
+    VirtualKeyCode::B,
+    VirtualKeyCode::C,
+    VirtualKeyCode::D,
+    VirtualKeyCode::E,
+    VirtualKeyCode::F,
+    VirtualKeyCode::G,
+    VirtualKeyCode::H,
+    VirtualKeyCode::I,
+    VirtualKeyCode::J,
+    VirtualKeyCode::K,
+    VirtualKeyCode::M,
+    VirtualKeyCode::N,
+    VirtualKeyCode::O,
+    VirtualKeyCode::P,
+    VirtualKeyCode::Q,
+    VirtualKeyCode::R,
+    VirtualKeyCode::S,
+    VirtualKeyCode::T,
+    VirtualKeyCode::U,
+    VirtualKeyCode::V,
+    VirtualKeyCode::W,
+    VirtualKeyCode::X,
+    VirtualKeyCode::Y,
+    VirtualKeyCode::Z,
+    VirtualKeyCode::L,
+    VirtualKeyCode::Left,
+    VirtualKeyCode::Right,
+    VirtualKeyCode::Up,
+    VirtualKeyCode::Down,
+    VirtualKeyCode::LBracket,
+    VirtualKeyCode::RBracket,
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::F3,
+    VirtualKeyCode::F4,
+    VirtualKeyCode::F5,
+    VirtualKeyCode::F6,
+    VirtualKeyCode::F7,
+    VirtualKeyCode::F8,
+    VirtualKeyCode::F12,
+];
+
+/// The window events the demo handles, in a form that can be written to a file and read back.
+/// winit's own events can't be, and most of what's in them is irrelevant anyway.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    CloseRequested,
+    KeyPressed(VirtualKeyCode),
+    KeyReleased(VirtualKeyCode),
+    /// The window's new size, in physical pixels.
+    Resized(PhysicalSize<u32>),
+    CursorMoved(PhysicalPosition<f64>),
+    /// The left mouse button went down, wherever the cursor last moved to.
+    LeftClicked,
+    /// Lines (notches) for a mouse wheel.
+    ScrolledLines(f32),
+    /// Pixels for a touchpad.
+    ScrolledPixels(f64),
+    FocusLost,
+}
+
+impl InputEvent {
+    /// None for events the demo ignores.
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::CloseRequested => Self::CloseRequested,
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } if KEYS.contains(key) => match state {
+                ElementState::Pressed => Self::KeyPressed(*key),
+                ElementState::Released => Self::KeyReleased(*key),
+            },
+            WindowEvent::Resized(size) => Self::Resized(*size),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                Self::Resized(**new_inner_size)
+            }
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved(*position),
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => Self::LeftClicked,
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(_, y) => Self::ScrolledLines(*y),
+                MouseScrollDelta::PixelDelta(position) => Self::ScrolledPixels(position.y),
+            },
+            WindowEvent::Focused(false) => Self::FocusLost,
+            _ => return None,
+        })
+    }
+
+    /// Whether someone pressed a key or used the mouse, rather than the window system telling the
+    /// demo something. --demo pauses its orbit for these.
+    pub fn is_user_input(&self) -> bool {
+        // No wildcard, so a new event has to be thought about here.
+        match self {
+            Self::KeyPressed(_)
+            | Self::KeyReleased(_)
+            | Self::CursorMoved(_)
+            | Self::LeftClicked
+            | Self::ScrolledLines(_)
+            | Self::ScrolledPixels(_) => true,
+            Self::CloseRequested | Self::Resized(_) | Self::FocusLost => false,
+        }
+    }
+}
+
+/// One event per line, as the event name and its values separated by spaces, e.g.
+/// `resize 800 600`. `FromStr` reads it back.
+impl fmt::Display for InputEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CloseRequested => write!(f, "close"),
+            Self::KeyPressed(key) => write!(f, "key_down {key:?}"),
+            Self::KeyReleased(key) => write!(f, "key_up {key:?}"),
+            Self::Resized(size) => write!(f, "resize {} {}", size.width, size.height),
+            Self::CursorMoved(position) => write!(f, "cursor {} {}", position.x, position.y),
+            Self::LeftClicked => write!(f, "left_click"),
+            Self::ScrolledLines(lines) => write!(f, "scroll_lines {lines}"),
+            Self::ScrolledPixels(pixels) => write!(f, "scroll_pixels {pixels}"),
+            Self::FocusLost => write!(f, "focus_lost"),
+        }
+    }
+}
+
+impl std::str::FromStr for InputEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut words = s.split_whitespace();
+        let name = words.next().ok_or_else(|| anyhow!("empty event"))?;
+        let mut next = |what: &str| {
+            words
+                .next()
+                .ok_or_else(|| anyhow!("{name} is missing its {what}"))
+        };
+        let key = |name: &str| {
+            KEYS.iter()
+                .copied()
+                .find(|key| format!("{key:?}") == name)
+                .ok_or_else(|| anyhow!("unknown key {name}"))
+        };
+        Ok(match name {
+            "close" => Self::CloseRequested,
+            "key_down" => Self::KeyPressed(key(next("key")?)?),
+            "key_up" => Self::KeyReleased(key(next("key")?)?),
+            "resize" => Self::Resized(PhysicalSize::new(
+                next("width")?.parse()?,
+                next("height")?.parse()?,
+            )),
+            "cursor" => Self::CursorMoved(PhysicalPosition::new(
+                next("x")?.parse()?,
+                next("y")?.parse()?,
+            )),
+            "left_click" => Self::LeftClicked,
+            "scroll_lines" => Self::ScrolledLines(next("lines")?.parse()?),
+            "scroll_pixels" => Self::ScrolledPixels(next("pixels")?.parse()?),
+            "focus_lost" => Self::FocusLost,
+            other => bail!("unknown event {other}"),
+        })
+    }
+}
+
+/// Writes events to a file for --record-input, each with how long after startup it happened, in
+/// microseconds.
+pub struct InputRecorder {
+    file: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("couldn't create {}", path.display()))?;
+        Ok(Self {
+            file: BufWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, time: Duration, event: &InputEvent) {
+        if let Err(e) = writeln!(self.file, "{} {event}", time.as_micros()) {
+            log::error!("Couldn't record input event: {e}");
+        }
+    }
+
+    /// Buffered events only reach the file when this is called, so call it before exiting.
+    pub fn flush(&mut self) {
+        if let Err(e) = self.file.flush() {
+            log::error!("Couldn't save recorded input: {e}");
+        }
+    }
+}
+
+/// Plays back a file from `InputRecorder`, for --replay-input.
+pub struct InputReplay {
+    /// Every event in the file, latest first, so the next one can be popped off the end.
+    events: Vec<(Duration, InputEvent)>,
+}
+
+impl InputReplay {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("couldn't open {}", path.display()))?;
+        let mut events = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (time, event) = line.split_once(' ').unwrap_or((line, ""));
+            let parse =
+                || -> Result<_> { Ok((Duration::from_micros(time.parse()?), event.parse()?)) };
+            events.push(parse().with_context(|| format!("{}:{}", path.display(), i + 1))?);
+        }
+        // The sort is stable, so events at the same time still pop off in the order they were
+        // recorded.
+        events.sort_by_key(|&(time, _)| time);
+        events.reverse();
+        Ok(Self { events })
+    }
+
+    /// The next event, if it's due by `now` (the time since startup).
+    pub fn next_due(&mut self, now: Duration) -> Option<InputEvent> {
+        match self.events.last() {
+            Some(&(time, _)) if time <= now => self.events.pop().map(|(_, event)| event),
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
@@ -0,0 +1,150 @@
+use cgmath::{Angle, Deg, EuclideanSpace, Matrix4, Point3, Vector3};
+
+use crate::camera::OPENGL_TO_WGPU_MATRIX;
+
+/// How much each press of an arrow key turns the light.
+const TURN_STEP: Deg<f32> = Deg(15.0);
+
+/// How much each press of [ or ] changes the light's brightness, and how far it can go.
+const INTENSITY_STEP: f32 = 0.25;
+const MAX_INTENSITY: f32 = 4.0;
+
+/// How much each press of 1/2 (ambient) or 3/4 (specular) changes those strengths.
+const STRENGTH_STEP: f32 = 0.05;
+
+/// Each press of 5/6 halves or doubles the shininess, within this range. Below 1 the highlight
+/// covers most of the surface, and above a few hundred it's too small to see.
+const MIN_SHININESS: f32 = 1.0;
+const MAX_SHININESS: f32 = 512.0;
+
+/// How far the shadow map reaches from the origin, in world units. Anything further out never
+/// gets shadows. The --shadows scene fits comfortably.
+const SHADOW_EXTENT: f32 = 3.0;
+
+/// Stop just short of straight up or down. Past there, turning left and right stops making sense.
+const MAX_PITCH: Deg<f32> = Deg(89.0);
+
+/// A directional light, like the sun: every surface gets lit from the same direction, however far
+/// away it is.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Light {
+    /// Which way the light is, turning around the Y axis. 0 is straight behind the default
+    /// camera, positive is to its right.
+    pub yaw: Deg<f32>,
+    /// How far above the horizon the light is.
+    pub pitch: Deg<f32>,
+    /// Brightness, from 0 (off) to MAX_INTENSITY.
+    pub intensity: f32,
+    /// When off, only the ambient light is left.
+    pub enabled: bool,
+    /// How much light everything gets, whichever way it faces, from 0 to 1.
+    pub ambient_strength: f32,
+    /// How bright the highlights are, from 0 to 1.
+    pub specular_strength: f32,
+    /// How small and sharp the highlights are.
+    pub shininess: f32,
+}
+
+impl Default for Light {
+    /// Over the default camera's right shoulder, so the pentagon's face is lit but not flatly.
+    fn default() -> Self {
+        Self {
+            yaw: Deg(20.0),
+            pitch: Deg(30.0),
+            intensity: 1.0,
+            enabled: true,
+            ambient_strength: 0.1,
+            specular_strength: 0.5,
+            shininess: 32.0,
+        }
+    }
+}
+
+impl Light {
+    /// The unit vector pointing from the scene towards the light.
+    pub fn direction(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    /// Where things land in the shadow map: the world seen from the light. The light's rays are
+    /// all parallel, so it's an orthographic projection, of a box SHADOW_EXTENT from the origin
+    /// in every direction.
+    pub fn view_proj(&self) -> Matrix4<f32> {
+        let eye = Point3::origin() + self.direction() * SHADOW_EXTENT * 2.0;
+        // Up can be Y, because MAX_PITCH stops the light ever pointing straight down Y.
+        let view = Matrix4::look_at_rh(eye, Point3::origin(), Vector3::unit_y());
+        let e = SHADOW_EXTENT;
+        // The box is from e to 3e away from the eye.
+        let proj = cgmath::ortho(-e, e, -e, e, e, 3.0 * e);
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+
+    /// Turn the light by this many steps right (or left, if negative).
+    pub fn turn(&mut self, steps: f32) {
+        self.yaw = (self.yaw + TURN_STEP * steps).normalize();
+    }
+
+    /// Raise the light by this many steps (or lower it, if negative).
+    pub fn raise(&mut self, steps: f32) {
+        self.pitch = Deg((self.pitch + TURN_STEP * steps)
+            .0
+            .clamp(-MAX_PITCH.0, MAX_PITCH.0));
+    }
+
+    /// Change the ambient strength by this many steps.
+    pub fn change_ambient(&mut self, steps: f32) {
+        self.ambient_strength = (self.ambient_strength + STRENGTH_STEP * steps).clamp(0.0, 1.0);
+    }
+
+    /// Change the specular strength by this many steps.
+    pub fn change_specular(&mut self, steps: f32) {
+        self.specular_strength = (self.specular_strength + STRENGTH_STEP * steps).clamp(0.0, 1.0);
+    }
+
+    /// Double the shininess this many times (or halve it, if negative).
+    pub fn change_shininess(&mut self, steps: f32) {
+        self.shininess = (self.shininess * 2f32.powf(steps)).clamp(MIN_SHININESS, MAX_SHININESS);
+    }
+
+    /// Brighten the light by this many steps (or dim it, if negative).
+    pub fn brighten(&mut self, steps: f32) {
+        self.intensity = (self.intensity + INTENSITY_STEP * steps).clamp(0.0, MAX_INTENSITY);
+    }
+}
+
+/// Corresponds to LightUniform in common.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    /// `Light::view_proj`, for drawing and reading the shadow map.
+    view_proj: [[f32; 4]; 4],
+    /// Towards the light, normalized. A vec3 followed by a float packs into 16 bytes, with no
+    /// padding needed.
+    direction: [f32; 3],
+    /// Zero when the light is off.
+    intensity: f32,
+    ambient_strength: f32,
+    specular_strength: f32,
+    shininess: f32,
+    /// Uniform structs are a multiple of 16 bytes long, so WGSL's has this much padding on the
+    /// end too.
+    _padding: f32,
+}
+
+impl From<&Light> for LightUniform {
+    fn from(light: &Light) -> Self {
+        Self {
+            view_proj: light.view_proj().into(),
+            direction: light.direction().into(),
+            intensity: if light.enabled { light.intensity } else { 0.0 },
+            ambient_strength: light.ambient_strength,
+            specular_strength: light.specular_strength,
+            shininess: light.shininess,
+            _padding: 0.0,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+/// How much the loupe magnifies, and how wide its inset is in pixels, unless --loupe-zoom and
+/// --loupe-size say otherwise.
+pub const DEFAULT_ZOOM: f32 = 8.0;
+pub const DEFAULT_SIZE: u32 = 256;
+
+/// Gap between the inset and the corner of the window, in pixels.
+const MARGIN: u32 = 16;
+
+/// A magnified view of the pixels around the cursor, in the top right corner of the window, for
+/// seeing exactly what texture filtering or MSAA did to them. F5 toggles it.
+///
+/// Only works when the scene's drawn into an offscreen texture, i.e. with HDR or a different
+/// render resolution. The pass which puts that texture on the surface (`HdrPipeline` or
+/// `BlitPipeline`) gets drawn a second time, into a viewport `zoom` times the size of the window,
+/// offset so the pixel under the cursor lands in the middle of the inset, and scissored down to
+/// the inset. So the inset is tonemapped and filtered exactly like the rest of the frame, but
+/// sampled with Nearest, so each of the scene's pixels is a crisp square.
+pub struct Loupe {
+    pub enabled: bool,
+    zoom: f32,
+    size: u32,
+}
+
+/// Where to draw the inset on the surface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoupeRegion {
+    /// x, y, width and height, for `RenderPass::set_viewport`. Mostly off the surface.
+    pub viewport: [f32; 4],
+    /// x, y, width and height, for `RenderPass::set_scissor_rect`.
+    pub scissor: [u32; 4],
+}
+
+impl Loupe {
+    pub fn new(zoom: f32, size: u32) -> Self {
+        Self {
+            enabled: false,
+            zoom,
+            size,
+        }
+    }
+
+    /// Where to draw the inset, magnifying the pixels around `cursor`, on a surface `width` by
+    /// `height`. The inset shrinks to fit a small window, and the zoom is reduced if the viewport
+    /// would be bigger than `max_viewport` (see `Limits::max_texture_dimension_2d`). None if the
+    /// window's too small for an inset at all.
+    pub fn region(
+        &self,
+        cursor: [f32; 2],
+        width: u32,
+        height: u32,
+        max_viewport: u32,
+    ) -> Option<LoupeRegion> {
+        let size = self
+            .size
+            .min(width.checked_sub(2 * MARGIN)?)
+            .min(height.checked_sub(2 * MARGIN)?);
+        if size == 0 {
+            return None;
+        }
+        let zoom = self
+            .zoom
+            .min(max_viewport as f32 / width.max(height) as f32);
+        let [x, y] = [width - MARGIN - size, MARGIN];
+        let center = [x as f32 + size as f32 / 2.0, y as f32 + size as f32 / 2.0];
+        Some(LoupeRegion {
+            viewport: [
+                center[0] - cursor[0] * zoom,
+                center[1] - cursor[1] * zoom,
+                width as f32 * zoom,
+                height as f32 * zoom,
+            ],
+            scissor: [x, y, size, size],
+        })
+    }
+}
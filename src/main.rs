@@ -1,8 +1,44 @@
+mod args;
+mod backend_quirks;
+mod background;
+mod bench;
+mod blit;
+mod bounding_boxes;
+mod camera;
+mod caps;
+mod color;
 mod draw;
+mod error;
+mod frame_graph;
+mod frustum;
+mod grid;
+mod hdr;
+mod hud;
+mod input_log;
+mod light;
+mod loupe;
+mod mesh;
+mod model;
+mod normals;
+mod overdraw;
+mod particles;
+mod point_sprites;
+mod recorder;
+mod renderdoc;
+mod scatter;
+mod scene;
+mod shader;
+mod shadow;
+mod skybox;
 mod texture;
+mod uniform;
+mod wireframe;
 
 fn main() {
     // Reminder, never use block_on inside an async fn if you're running in WASM.
     // Why? Futures have to be run on the browser's executor. So you can't BYO.
-    pollster::block_on(draw::run());
+    if let Err(e) = pollster::block_on(draw::run()) {
+        log::error!("Couldn't start the demo: {e}");
+        std::process::exit(e.exit_code());
+    }
 }
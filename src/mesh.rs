@@ -0,0 +1,579 @@
+use std::{collections::HashMap, str::FromStr};
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    /// Which way the surface faces at this vertex. Lighting needs it.
+    pub normal: [f32; 3],
+    /// Linear RGB, multiplied with the texture, so colours blend across each triangle on top of
+    /// it. `WHITE` leaves the texture as it is.
+    pub color: [f32; 3],
+}
+
+impl Vertex {
+    pub const WHITE: [f32; 3] = [1.0; 3];
+
+    /// How does the vertex buffer's internal layout correspond to a set of these Vertices?
+    /// Note this is pretty verbose, a macro `vertex_attr_array` exists to help.
+    pub fn descriptor<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            // How many bytes are in each element of the array
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            // Do you increment the array index per-vertex or per-instance?
+            // I don't know what instances are yet, so, vertices here.
+            step_mode: wgpu::VertexStepMode::Vertex,
+            // Maps attributes of the struct to locations in each element of the buffer.
+            attributes: &[
+                wgpu::VertexAttribute {
+                    // Where the attribute starts.
+                    offset: 0,
+                    // In WGSL each attribute has a 'location' (analogous to protobuf's field number)
+                    // This describes which location number the given attribute corresponds to.
+                    shader_location: 0,
+                    // Internal format of the attribute
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    // Offset after the [f32; 3] used for the previous attribute
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    // Store in @location(1)
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    // After the position and tex_coords.
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    // After the position, tex_coords and normal.
+                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+/// How a mesh's vertices are laid out in GPU memory. Both draw exactly the same thing, the
+/// difference is only in how the vertex fetch reads memory, which can matter for performance.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VertexLayout {
+    /// Array of structs: one buffer, with each vertex's position, tex_coords, normal and colour
+    /// next to each other. This is what `Vertex` looks like in memory.
+    #[default]
+    Interleaved,
+    /// Struct of arrays: one buffer per attribute, i.e. all the positions, then all the
+    /// tex_coords in another buffer, then all the normals in a third, and all the colours in a
+    /// fourth.
+    Separate,
+}
+
+impl VertexLayout {
+    /// One layout per vertex buffer slot, starting at slot 0.
+    pub fn buffer_layouts(self) -> Vec<wgpu::VertexBufferLayout<'static>> {
+        // Same shader locations as `Vertex::descriptor`, so the shaders can't tell the difference.
+        const POSITION: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
+        const TEX_COORDS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![1 => Float32x2];
+        const NORMAL: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![2 => Float32x3];
+        const COLOR: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![3 => Float32x3];
+        let separate =
+            |attributes: &'static [wgpu::VertexAttribute], size: usize| wgpu::VertexBufferLayout {
+                array_stride: size as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes,
+            };
+        match self {
+            Self::Interleaved => vec![Vertex::descriptor()],
+            Self::Separate => vec![
+                separate(&POSITION, std::mem::size_of::<[f32; 3]>()),
+                separate(&TEX_COORDS, std::mem::size_of::<[f32; 2]>()),
+                separate(&NORMAL, std::mem::size_of::<[f32; 3]>()),
+                separate(&COLOR, std::mem::size_of::<[f32; 3]>()),
+            ],
+        }
+    }
+}
+
+impl FromStr for VertexLayout {
+    type Err = ();
+
+    /// `aos` (or `interleaved`) and `soa` (or `separate`), as --vertex-layout takes them.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "aos" | "interleaved" => Ok(Self::Interleaved),
+            "soa" | "separate" => Ok(Self::Separate),
+            _ => Err(()),
+        }
+    }
+}
+
+/// In a strip's index buffer, ends one strip so the next index starts a new one (primitive
+/// restart). It's always the largest index, so for the u32 indices used here, `u32::MAX`.
+pub const RESTART_INDEX: u32 = u32::MAX;
+
+/// The smallest box, lined up with the axes, which holds every vertex of a mesh.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl BoundingBox {
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    /// Width, height and depth.
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// The box's 12 edges, as pairs of corners, for drawing it with `PrimitiveTopology::LineList`.
+    pub fn edges(&self) -> [[f32; 3]; 24] {
+        // Corner i has the max x if bit 0 is set, max y if bit 1 is, and max z if bit 2 is. Each
+        // edge joins two corners which only differ in one bit.
+        let corner = |i: usize| {
+            let pick = |bit: usize, axis: usize| {
+                if i & bit == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                }
+            };
+            [pick(1, 0), pick(2, 1), pick(4, 2)]
+        };
+        let mut edges = [[0.0; 3]; 24];
+        let mut n = 0;
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                // Only from the corner without the bit, so each edge is only added once.
+                if i & bit == 0 {
+                    edges[n] = corner(i);
+                    edges[n + 1] = corner(i | bit);
+                    n += 2;
+                }
+            }
+        }
+        edges
+    }
+
+    /// Half the distance between opposite corners, i.e. the radius of the smallest sphere around
+    /// the center which holds the whole box.
+    pub fn radius(&self) -> f32 {
+        self.size().magnitude() / 2.0
+    }
+}
+
+/// Some geometry: vertices, and indices saying which vertices make up each triangle.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    /// Every 3 indices is one triangle.
+    pub indices: Vec<u32>,
+    /// How the indices are stored on the GPU. They're always u32 here, but meshes whose files
+    /// had smaller ones can keep them 16-bit there, which halves the index buffer. See
+    /// `index_data`.
+    pub index_format: wgpu::IndexFormat,
+}
+
+impl Mesh {
+    /// With 32-bit indices on the GPU.
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Self {
+            vertices,
+            indices,
+            index_format: wgpu::IndexFormat::Uint32,
+        }
+    }
+
+    /// A 1x1 square in the XY plane, centred on the origin and facing +Z (towards the default
+    /// camera). The texture is stretched over it exactly once.
+    pub fn quad() -> Self {
+        let normal = [0.0, 0.0, 1.0];
+        let corner = |x: f32, y: f32| Vertex {
+            position: [x - 0.5, y - 0.5, 0.0],
+            // Texture coordinates have y going down, positions have y going up.
+            tex_coords: [x, 1.0 - y],
+            normal,
+            color: Vertex::WHITE,
+        };
+        Self::new(
+            vec![
+                corner(0.0, 0.0),
+                corner(1.0, 0.0),
+                corner(1.0, 1.0),
+                corner(0.0, 1.0),
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        )
+    }
+
+    /// A flat `size` x `size` square on the XZ plane, centred on the origin and facing up (+Y),
+    /// split into `subdivisions` x `subdivisions` smaller squares (at least 1). More squares
+    /// don't change how it looks with per-fragment lighting, but give vertex effects something to
+    /// work with. The texture is stretched over the whole plane once.
+    pub fn plane(size: f32, subdivisions: u32) -> Self {
+        let n = subdivisions.max(1);
+        let row = n + 1;
+        let mut vertices = Vec::with_capacity((row * row) as usize);
+        for j in 0..row {
+            for i in 0..row {
+                let (u, v) = (i as f32 / n as f32, j as f32 / n as f32);
+                vertices.push(Vertex {
+                    position: [(u - 0.5) * size, 0.0, (v - 0.5) * size],
+                    tex_coords: [u, v],
+                    normal: [0.0, 1.0, 0.0],
+                    color: Vertex::WHITE,
+                });
+            }
+        }
+        let mut indices = Vec::with_capacity((n * n * 6) as usize);
+        for j in 0..n {
+            for i in 0..n {
+                // Corners of one square. Going a, b, c then a, c, d is counter-clockwise seen
+                // from above.
+                let a = j * row + i;
+                let b = a + row;
+                let (c, d) = (b + 1, a + 1);
+                indices.extend([a, b, c, a, c, d]);
+            }
+        }
+        Self::new(vertices, indices)
+    }
+
+    /// A 1x1x1 cube, centred on the origin, with each face's normal pointing straight out of it.
+    /// Each face is a `quad`, with the texture stretched over it once.
+    pub fn cube() -> Self {
+        // Each face's normal, then which ways the quad's x and y go. x cross y is the normal, so
+        // the front of each face is on the outside.
+        const FACES: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+            ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+            ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+            ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+            ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ];
+        let quad = Self::quad();
+        let mut cube = Self::new(Vec::new(), Vec::new());
+        for (normal, x_axis, y_axis) in FACES {
+            let [normal, x_axis, y_axis] = [normal, x_axis, y_axis].map(Vector3::from);
+            let first = cube.vertices.len() as u32;
+            cube.vertices.extend(quad.vertices.iter().map(|v| {
+                let [x, y, _] = v.position;
+                Vertex {
+                    position: (normal * 0.5 + x_axis * x + y_axis * y).into(),
+                    normal: normal.into(),
+                    ..*v
+                }
+            }));
+            cube.indices.extend(quad.indices.iter().map(|i| first + i));
+        }
+        cube
+    }
+
+    /// The bytes to put in each vertex buffer, one per slot in `layout.buffer_layouts()`.
+    pub fn vertex_data(&self, layout: VertexLayout) -> Vec<Vec<u8>> {
+        match layout {
+            VertexLayout::Interleaved => vec![bytemuck::cast_slice(&self.vertices).to_vec()],
+            VertexLayout::Separate => {
+                let positions: Vec<_> = self.vertices.iter().map(|v| v.position).collect();
+                let tex_coords: Vec<_> = self.vertices.iter().map(|v| v.tex_coords).collect();
+                let normals: Vec<_> = self.vertices.iter().map(|v| v.normal).collect();
+                let colors: Vec<_> = self.vertices.iter().map(|v| v.color).collect();
+                vec![
+                    bytemuck::cast_slice(&positions).to_vec(),
+                    bytemuck::cast_slice(&tex_coords).to_vec(),
+                    bytemuck::cast_slice(&normals).to_vec(),
+                    bytemuck::cast_slice(&colors).to_vec(),
+                ]
+            }
+        }
+    }
+
+    /// Every triangle's corners, in order, without sharing any between triangles. So the corners
+    /// of triangle n are at 3n, 3n + 1 and 3n + 2, which is how the wireframe shader tells them
+    /// apart.
+    pub fn triangle_positions(&self) -> Vec<[f32; 3]> {
+        self.indices
+            .iter()
+            .map(|&i| self.vertices[i as usize].position)
+            .collect()
+    }
+
+    /// The index buffer for drawing this mesh with `topology`. `indices` is a triangle list, so
+    /// for anything else it gets rearranged, so each topology still shows the same triangles:
+    ///
+    /// - Points: every vertex of every triangle.
+    /// - Lines: each triangle's three edges.
+    /// - Strips: each triangle on its own, as a strip of three (or a closed loop of four for
+    ///   lines), with `RESTART_INDEX` between them to start a new strip.
+    pub fn indices_for(&self, topology: wgpu::PrimitiveTopology) -> Vec<u32> {
+        use wgpu::PrimitiveTopology::*;
+        let triangles = self.indices.chunks_exact(3);
+        match topology {
+            PointList | TriangleList => self.indices.clone(),
+            LineList => triangles
+                .flat_map(|t| [t[0], t[1], t[1], t[2], t[2], t[0]])
+                .collect(),
+            LineStrip => triangles
+                .flat_map(|t| [t[0], t[1], t[2], t[0], RESTART_INDEX])
+                .collect(),
+            TriangleStrip => triangles
+                .flat_map(|t| [t[0], t[1], t[2], RESTART_INDEX])
+                .collect(),
+        }
+    }
+
+    /// `indices_for(topology)`, as the bytes of an index buffer, and the format they're in. That's
+    /// `index_format`, except that strips are always 32-bit, because their pipelines' restart
+    /// index is (see `RESTART_INDEX`), and so are meshes with too many vertices for 16 bits.
+    pub fn index_data(&self, topology: wgpu::PrimitiveTopology) -> (Vec<u8>, wgpu::IndexFormat) {
+        let indices = self.indices_for(topology);
+        let fits_u16 = self.vertices.len() <= u16::MAX as usize + 1;
+        match self.index_format {
+            wgpu::IndexFormat::Uint16 if fits_u16 && !topology.is_strip() => {
+                let indices: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+                (
+                    bytemuck::cast_slice(&indices).to_vec(),
+                    wgpu::IndexFormat::Uint16,
+                )
+            }
+            _ => (
+                bytemuck::cast_slice(&indices).to_vec(),
+                wgpu::IndexFormat::Uint32,
+            ),
+        }
+    }
+
+    /// None if there are no vertices.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let first = Point3::from(self.vertices.first()?.position);
+        let bbox = self.vertices.iter().fold(
+            BoundingBox {
+                min: first,
+                max: first,
+            },
+            |bbox, v| BoundingBox {
+                min: Point3::from([0, 1, 2].map(|i| bbox.min[i].min(v.position[i]))),
+                max: Point3::from([0, 1, 2].map(|i| bbox.max[i].max(v.position[i]))),
+            },
+        );
+        Some(bbox)
+    }
+
+    /// Move and scale the mesh so it's centred on the origin and its longest side is 1, i.e. it
+    /// just fits in the unit cube from -0.5 to 0.5. Models come in all sorts of sizes and places,
+    /// and one that's a thousand units across, or far from the origin, is invisible to a camera
+    /// set up for the pentagon.
+    ///
+    /// Returns where the mesh was before, or None if it has no vertices. A mesh that's a single
+    /// point gets centred, but can't be scaled up.
+    pub fn normalize_to_unit(&mut self) -> Option<BoundingBox> {
+        let bbox = self.bounding_box()?;
+        let size = bbox.size();
+        let longest = size.x.max(size.y).max(size.z);
+        let scale = if longest > 0.0 { 1.0 / longest } else { 1.0 };
+        let center = bbox.center().to_vec();
+        for vertex in &mut self.vertices {
+            // Scaling the same amount in every direction doesn't change which way normals point.
+            let position = (Vector3::from(vertex.position) - center) * scale;
+            vertex.position = position.into();
+        }
+        Some(bbox)
+    }
+
+    /// Move the mesh up or down so its lowest point is at y = 0, i.e. it's standing on the floor
+    /// rather than sunk halfway into it. Does nothing to a mesh with no vertices.
+    pub fn place_on_floor(&mut self) {
+        let Some(bbox) = self.bounding_box() else {
+            return;
+        };
+        for vertex in &mut self.vertices {
+            vertex.position[1] -= bbox.min.y;
+        }
+    }
+
+    /// Replace the normals with flat ones, so each triangle is lit as one flat face.
+    /// Vertices shared between triangles get duplicated, because a vertex can only have one
+    /// normal, and each triangle needs its own. Except between triangles facing the same way,
+    /// which can still share.
+    pub fn with_computed_flat_normals(self) -> Self {
+        let mut vertices = Vec::with_capacity(self.indices.len());
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize]);
+            let normal = face_normal(a.position, b.position, c.position);
+            vertices.extend([a, b, c].map(|v| Vertex { normal, ..v }));
+        }
+        // Now every triangle has its own vertices, in order.
+        let indices = (0..vertices.len() as u32).collect();
+        let mut mesh = Self {
+            index_format: self.index_format,
+            ..Self::new(vertices, indices)
+        };
+        mesh.dedup_vertices();
+        mesh
+    }
+
+    /// Merge vertices which are exactly the same, and point the indices at the one that's kept.
+    /// The triangles are the same as before, so it draws the same, but from a smaller vertex
+    /// buffer, and the GPU can reuse more of the vertex shader's work. Vertices stay in the order
+    /// they first appear. Returns how many were removed.
+    ///
+    /// "Exactly" means bit for bit: every field's compared by its bits rather than with `==`. So
+    /// it's deterministic, and can use a hash map. The catch is 0.0 and -0.0 are different, and
+    /// so are NaNs with different bits, which only means a few vertices aren't merged when they
+    /// could have been. Nearly equal vertices aren't merged at all, because that would change the
+    /// mesh.
+    pub fn dedup_vertices(&mut self) -> usize {
+        let old_vertices = std::mem::take(&mut self.vertices);
+        // Keyed on each vertex's bytes, which are its fields' bits, because `Vertex` is Pod.
+        let mut kept: HashMap<&[u8], u32> = HashMap::with_capacity(old_vertices.len());
+        let new_index: Vec<u32> = old_vertices
+            .iter()
+            .map(|vertex| {
+                *kept.entry(bytemuck::bytes_of(vertex)).or_insert_with(|| {
+                    self.vertices.push(*vertex);
+                    self.vertices.len() as u32 - 1
+                })
+            })
+            .collect();
+        for index in &mut self.indices {
+            *index = new_index[*index as usize];
+        }
+        old_vertices.len() - self.vertices.len()
+    }
+}
+
+/// The unit vector pointing out of the front of a triangle. Front is the side where the vertices
+/// go counter-clockwise, matching `FrontFace::Ccw`. Degenerate (zero-area) triangles don't face
+/// anywhere, so they get a zero normal.
+fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+    let [a, b, c] = [a, b, c].map(Vector3::from);
+    let cross = (b - a).cross(c - a);
+    if cross.magnitude2() == 0.0 {
+        return [0.0; 3];
+    }
+    cross.normalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mesh of just the one triangle, with a deliberately wrong normal.
+    fn triangle(positions: [[f32; 3]; 3]) -> Mesh {
+        let vertices = positions
+            .map(|position| Vertex {
+                position,
+                tex_coords: [0.0; 2],
+                normal: [0.0; 3],
+                color: Vertex::WHITE,
+            })
+            .to_vec();
+        Mesh::new(vertices, vec![0, 1, 2])
+    }
+
+    #[test]
+    fn flat_normals_face_the_counter_clockwise_side() {
+        // Counter-clockwise seen from +Z, and big, so the normal has to be normalized.
+        let mesh = triangle([[0.0, 0.0, 0.0], [3.0, 0.0, 0.0], [0.0, 5.0, 0.0]])
+            .with_computed_flat_normals();
+        assert_eq!(mesh.vertices.len(), 3);
+        for vertex in &mesh.vertices {
+            assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+        }
+        // The other way round faces the other way.
+        let mesh = triangle([[0.0, 0.0, 0.0], [0.0, 2.0, 0.0], [2.0, 0.0, 0.0]])
+            .with_computed_flat_normals();
+        assert_eq!(mesh.vertices[0].normal, [0.0, 0.0, -1.0]);
+        // And at an angle, it's still unit length.
+        let mesh = triangle([[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 1.0]])
+            .with_computed_flat_normals();
+        let normal = Vector3::from(mesh.vertices[0].normal);
+        assert!((normal.magnitude() - 1.0).abs() < 1e-6, "{normal:?}");
+        assert!((normal - Vector3::new(1.0, -1.0, 1.0).normalize()).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn normalize_to_unit_centres_and_fits() {
+        // A 4 x 2 x 1 box, well away from the origin.
+        let mut mesh = triangle([[10.0, 20.0, 30.0], [14.0, 20.0, 30.0], [10.0, 22.0, 31.0]]);
+        let original = mesh.normalize_to_unit().unwrap();
+        assert_eq!(original.center(), Point3::new(12.0, 21.0, 30.5));
+        let bbox = mesh.bounding_box().unwrap();
+        assert_eq!(bbox.center(), Point3::new(0.0, 0.0, 0.0));
+        // The longest side just fits, the others keep their proportions.
+        assert_eq!(bbox.size(), Vector3::new(1.0, 0.5, 0.25));
+    }
+
+    #[test]
+    fn normalize_to_unit_centres_a_single_point() {
+        let mut mesh = triangle([[3.0, 4.0, 5.0]; 3]);
+        mesh.normalize_to_unit().unwrap();
+        assert!(mesh.vertices.iter().all(|v| v.position == [0.0; 3]));
+        assert!(Mesh::new(vec![], vec![]).normalize_to_unit().is_none());
+    }
+
+    #[test]
+    fn plane_counts() {
+        for (subdivisions, vertices, triangles) in [(1, 4, 2), (3, 16, 18), (10, 121, 200)] {
+            let plane = Mesh::plane(2.0, subdivisions);
+            assert_eq!(
+                plane.vertices.len(),
+                vertices,
+                "{subdivisions} subdivisions"
+            );
+            assert_eq!(
+                plane.indices.len(),
+                triangles * 3,
+                "{subdivisions} subdivisions"
+            );
+            assert!(plane.indices.iter().all(|&i| (i as usize) < vertices));
+        }
+        // Zero is treated as one.
+        assert_eq!(Mesh::plane(2.0, 0).vertices.len(), 4);
+        let bbox = Mesh::plane(2.0, 3).bounding_box().unwrap();
+        assert_eq!(bbox.size(), Vector3::new(2.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn dedup_merges_a_cube_of_duplicated_corners() {
+        // Every triangle with its own copies of its corners, like a mesh straight out of some
+        // exporters: 36 vertices, but only 8 different ones.
+        let cube = Mesh::cube();
+        let vertices: Vec<_> = cube
+            .indices
+            .iter()
+            .map(|&i| Vertex {
+                position: cube.vertices[i as usize].position,
+                tex_coords: [0.0; 2],
+                normal: [0.0; 3],
+                color: Vertex::WHITE,
+            })
+            .collect();
+        let indices = (0..vertices.len() as u32).collect();
+        let mut mesh = Mesh::new(vertices, indices);
+        let triangles_before = mesh.triangle_positions();
+        assert_eq!(mesh.dedup_vertices(), 28);
+        assert_eq!(mesh.vertices.len(), 8);
+        assert_eq!(mesh.indices.len(), 36);
+        // Still the same triangles, corners in the same order.
+        assert_eq!(mesh.triangle_positions(), triangles_before);
+    }
+
+    #[test]
+    fn dedup_keeps_vertices_that_differ() {
+        // The built-in cube's corners are shared by three faces each, but with different normals.
+        let mut cube = Mesh::cube();
+        assert_eq!(cube.dedup_vertices(), 0);
+        assert_eq!(cube.vertices.len(), 24);
+    }
+}
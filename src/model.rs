@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::mesh::{Mesh, Vertex};
+
+/// What the demo takes out of a glTF file: one mesh, and the image it's painted with.
+pub struct GltfModel {
+    pub mesh: Mesh,
+    /// The base colour texture, if the file embeds one (or has it next to it).
+    pub base_color: Option<image::DynamicImage>,
+}
+
+/// Load the first primitive of the first mesh in a `.gltf` (with its `.bin`) or `.glb` file.
+/// Anything else in the file (other meshes, animations, cameras...) is ignored.
+pub fn load_gltf(path: &Path) -> Result<GltfModel> {
+    // `import` follows the file's references to other files, relative to its own directory, so
+    // .gltf files can find their .bin and textures. .glb files usually have everything inside.
+    let (document, buffers, images) =
+        gltf::import(path).with_context(|| format!("couldn't read {}", path.display()))?;
+    let mesh = document
+        .meshes()
+        .next()
+        .ok_or_else(|| anyhow!("{} has no meshes", path.display()))?;
+    let primitive = mesh
+        .primitives()
+        .next()
+        .ok_or_else(|| anyhow!("{}'s first mesh has no primitives", path.display()))?;
+    if primitive.mode() != gltf::mesh::Mode::Triangles {
+        anyhow::bail!(
+            "{}'s first primitive is {:?}, only triangles are supported",
+            path.display(),
+            primitive.mode()
+        );
+    }
+
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .ok_or_else(|| anyhow!("{}'s first primitive has no positions", path.display()))?
+        .collect();
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(Iterator::collect);
+    let tex_coords: Option<Vec<[f32; 2]>> = reader
+        .read_tex_coords(0)
+        .map(|coords| coords.into_f32().collect());
+    let has_normals = normals.is_some();
+
+    let vertices = positions
+        .iter()
+        .enumerate()
+        .map(|(i, &position)| Vertex {
+            position,
+            tex_coords: tex_coords.as_ref().map_or([0.0; 2], |t| t[i]),
+            normal: normals.as_ref().map_or([0.0; 3], |n| n[i]),
+            color: Vertex::WHITE,
+        })
+        .collect();
+    // Indices can be u8, u16 or u32 in the file. Mesh works with u32, but keeps smaller ones
+    // 16-bit on the GPU (which has no 8-bit indices). Unindexed primitives just use each vertex
+    // once, in order.
+    use gltf::mesh::util::ReadIndices;
+    let (indices, index_format) = match reader.read_indices() {
+        Some(ReadIndices::U8(indices)) => {
+            (indices.map(u32::from).collect(), wgpu::IndexFormat::Uint16)
+        }
+        Some(ReadIndices::U16(indices)) => {
+            (indices.map(u32::from).collect(), wgpu::IndexFormat::Uint16)
+        }
+        Some(ReadIndices::U32(indices)) => (indices.collect(), wgpu::IndexFormat::Uint32),
+        None => (
+            (0..positions.len() as u32).collect(),
+            wgpu::IndexFormat::Uint32,
+        ),
+    };
+    let mut mesh = Mesh {
+        index_format,
+        ..Mesh::new(vertices, indices)
+    };
+    if !has_normals {
+        // The spec says to use flat normals when there aren't any.
+        mesh = mesh.with_computed_flat_normals();
+    }
+    // Files often repeat vertices, e.g. ones exported without an index buffer.
+    let merged = mesh.dedup_vertices();
+    if merged > 0 {
+        log::info!(
+            "Merged {merged} duplicate vertices in {}, leaving {}",
+            path.display(),
+            mesh.vertices.len()
+        );
+    }
+
+    let base_color = primitive
+        .material()
+        .pbr_metallic_roughness()
+        .base_color_texture()
+        .and_then(|info| images.get(info.texture().source().index()))
+        .and_then(to_dynamic_image);
+
+    Ok(GltfModel { mesh, base_color })
+}
+
+/// gltf decodes images itself, into raw pixels. Wrap them back up so they can go in a texture.
+fn to_dynamic_image(data: &gltf::image::Data) -> Option<image::DynamicImage> {
+    use gltf::image::Format;
+    let (width, height, pixels) = (data.width, data.height, data.pixels.clone());
+    match data.format {
+        Format::R8G8B8A8 => {
+            image::RgbaImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgba8)
+        }
+        Format::R8G8B8 => {
+            image::RgbImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgb8)
+        }
+        other => {
+            log::warn!("Ignoring base colour texture in unsupported format {other:?}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube_glb() -> &'static Path {
+        Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/cube.glb"))
+    }
+
+    #[test]
+    fn loads_the_cube_glb() {
+        let model = load_gltf(cube_glb()).unwrap();
+        // 4 corners for each of the 6 faces, since each face has its own normal, so nothing
+        // merges.
+        assert_eq!(model.mesh.vertices.len(), 24);
+        assert_eq!(model.mesh.indices.len(), 36);
+        assert!(model
+            .mesh
+            .indices
+            .iter()
+            .all(|&i| (i as usize) < model.mesh.vertices.len()));
+    }
+
+    #[test]
+    fn keeps_the_cube_glb_indices_16_bit() {
+        let document = gltf::Gltf::open(cube_glb()).unwrap();
+        let primitive = document
+            .meshes()
+            .next()
+            .unwrap()
+            .primitives()
+            .next()
+            .unwrap();
+        assert_eq!(
+            primitive.indices().unwrap().data_type(),
+            gltf::accessor::DataType::U16
+        );
+
+        let model = load_gltf(cube_glb()).unwrap();
+        assert_eq!(model.mesh.index_format, wgpu::IndexFormat::Uint16);
+        let (data, format) = model.mesh.index_data(wgpu::PrimitiveTopology::TriangleList);
+        assert_eq!(format, wgpu::IndexFormat::Uint16);
+        assert_eq!(data.len(), 36 * 2);
+        // Strips' restart index is always 32-bit.
+        let (_, format) = model
+            .mesh
+            .index_data(wgpu::PrimitiveTopology::TriangleStrip);
+        assert_eq!(format, wgpu::IndexFormat::Uint32);
+    }
+}
@@ -0,0 +1,36 @@
+/// Format of the optional second colour target (see --normals-target), which the scene shaders
+/// write each pixel's world-space normal into. Normals go from -1 to 1 but this format stores 0
+/// to 1, so they're stored as `normal * 0.5 + 0.5`.
+pub const NORMALS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// What the normals target is cleared to: a zero normal, i.e. nothing drawn here.
+pub const CLEAR_NORMAL: wgpu::Color = wgpu::Color {
+    r: 0.5,
+    g: 0.5,
+    b: 0.5,
+    a: 0.0,
+};
+
+/// The colour targets for a pipeline drawn in the scene pass. The scene colour is always
+/// `@location(0)`. With a normals target, normals are `@location(1)`.
+///
+/// Both targets get the same blend state and write mask, because different ones per target need
+/// the INDEPENDENT_BLEND downlevel flag, which WebGL doesn't have.
+pub fn scene_targets(
+    format: wgpu::TextureFormat,
+    blend: wgpu::BlendState,
+    normals_target: bool,
+) -> Vec<Option<wgpu::ColorTargetState>> {
+    let target = |format| {
+        Some(wgpu::ColorTargetState {
+            format,
+            blend: Some(blend),
+            write_mask: wgpu::ColorWrites::ALL,
+        })
+    };
+    let mut targets = vec![target(format)];
+    if normals_target {
+        targets.push(target(NORMALS_FORMAT));
+    }
+    targets
+}
@@ -0,0 +1,177 @@
+use crate::blit::{
+    create_bind_group, create_bind_group_layout, create_sampler, create_target_view,
+};
+
+/// Format of the texture the fragment counts accumulate in. One channel is all it needs, and unlike
+/// the 32-bit formats, 16-bit floats can be blended. They count exactly up to 2048, far more
+/// overdraw than the heatmap tells apart.
+const COUNTS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+
+/// The overdraw heatmap: instead of drawing the scene normally, count how many fragments land on
+/// each pixel, then show the counts from blue (1) to red (lots). V toggles it.
+///
+/// Every fragment counts, even ones the depth test would have thrown away, because they still
+/// cost the GPU something to produce. The background and particles have their own pipelines and
+/// aren't counted.
+pub struct Overdraw {
+    pub enabled: bool,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    accumulate_pipeline: wgpu::RenderPipeline,
+    resolve_pipeline: wgpu::RenderPipeline,
+}
+
+impl Overdraw {
+    /// `scene_layout`, `buffers` and `primitive` have to be the same as the scene pipelines', so
+    /// the objects can be drawn with the same bind groups and vertex buffers. The heatmap gets
+    /// drawn onto a texture in `output_format`, usually the surface.
+    pub fn new(
+        device: &wgpu::Device,
+        scene_layout: &wgpu::PipelineLayout,
+        buffers: &[wgpu::VertexBufferLayout],
+        primitive: wgpu::PrimitiveState,
+        output_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let view = create_target_view(device, "overdraw_texture", COUNTS_FORMAT, width, height);
+        // The counts shouldn't be blended together when the render size isn't the window's.
+        let sampler = create_sampler(device, wgpu::FilterMode::Nearest, "overdraw_sampler");
+        let layout = create_bind_group_layout(device, "overdraw_bind_group_layout");
+        let bind_group = create_bind_group(device, &layout, &view, &sampler, "overdraw_bind_group");
+
+        let source =
+            crate::shader::resolve_includes("overdraw.wgsl", include_str!("overdraw.wgsl"), None)
+                .expect("overdraw.wgsl only includes built-in files");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Overdraw Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let accumulate_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overdraw Accumulate Pipeline"),
+            layout: Some(scene_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_accumulate",
+                buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_accumulate",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: COUNTS_FORMAT,
+                    // Add each fragment's 1 to what's already there.
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent::REPLACE,
+                    }),
+                    write_mask: wgpu::ColorWrites::RED,
+                })],
+            }),
+            primitive,
+            // No depth test, so hidden fragments get counted too.
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let resolve_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overdraw Resolve Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let resolve_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overdraw Resolve Pipeline"),
+            layout: Some(&resolve_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_resolve",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_resolve",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: output_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            enabled: false,
+            view,
+            sampler,
+            layout,
+            bind_group,
+            accumulate_pipeline,
+            resolve_pipeline,
+        }
+    }
+
+    /// Only needed when the render resolution changes, not every time the window does.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.view = create_target_view(device, "overdraw_texture", COUNTS_FORMAT, width, height);
+        self.bind_group = create_bind_group(
+            device,
+            &self.layout,
+            &self.view,
+            &self.sampler,
+            "overdraw_bind_group",
+        );
+    }
+
+    /// Start the pass which counts fragments, with the counts cleared and the pipeline set. The
+    /// caller binds the scene's bind groups and draws the objects, like in the normal scene pass.
+    pub fn begin_accumulate<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'a> {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overdraw Accumulate Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.accumulate_pipeline);
+        pass
+    }
+
+    /// Draw the counts onto `output` as a heatmap.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overdraw Resolve Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // The fullscreen triangle covers every pixel.
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.resolve_pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
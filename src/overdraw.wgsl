@@ -0,0 +1,69 @@
+// Overdraw heatmap. The accumulate pass draws the scene's objects with additive blending, adding 1
+// per fragment, so each pixel ends up counting how many fragments landed on it. The resolve pass
+// then turns those counts into colours on the surface.
+
+#include "common.wgsl"
+
+// Accumulate
+
+@vertex
+fn vs_accumulate(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> @builtin(position) vec4<f32> {
+    let instance_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    // Same transform as shader.wgsl, so the fragments land in the same places.
+    return camera.view_proj * instance_matrix * model_uniform.transform * vec4<f32>(model.position, 1.0);
+}
+
+@fragment
+fn fs_accumulate() -> @location(0) vec4<f32> {
+    // Only the red channel exists in the accumulation texture.
+    return vec4<f32>(1.0, 0.0, 0.0, 0.0);
+}
+
+// Resolve
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Same trick as blit.wgsl: one big triangle covering the whole screen, no vertex buffer.
+@vertex
+fn vs_resolve(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(xy * 2.0 - 1.0, 0.0, 1.0);
+    // Texture coordinates have y pointing down, but clip space has y pointing up.
+    out.uv = vec2<f32>(xy.x, 1.0 - xy.y);
+    return out;
+}
+
+@group(0) @binding(0)
+var t_counts: texture_2d<f32>;
+@group(0) @binding(1)
+var s_counts: sampler;
+
+// This many fragments on one pixel, or more, is as red as it gets.
+const MAX_OVERDRAW: f32 = 8.0;
+
+@fragment
+fn fs_resolve(in: VertexOutput) -> @location(0) vec4<f32> {
+    let count = textureSample(t_counts, s_counts, in.uv).r;
+    // Nothing drawn at all stays black, so it's easy to tell from drawn once (blue).
+    if count < 0.5 {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    // Blue for 1, through cyan, green and yellow, to red for MAX_OVERDRAW.
+    let t = clamp((count - 1.0) / (MAX_OVERDRAW - 1.0), 0.0, 1.0);
+    let r = clamp(4.0 * t - 2.0, 0.0, 1.0);
+    let g = clamp(4.0 * t, 0.0, 1.0) * clamp(4.0 - 4.0 * t, 0.0, 1.0);
+    let b = clamp(2.0 - 4.0 * t, 0.0, 1.0);
+    return vec4<f32>(r, g, b, 1.0);
+}
@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use wgpu::util::DeviceExt;
+
+use crate::uniform::Uniform;
+
+/// Must match @workgroup_size in particles.wgsl.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Clip-space units per second per second.
+const GRAVITY: f32 = 0.5;
+
+/// Corresponds to Particle in particles.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+}
+
+impl Particle {
+    /// The render pipeline reads the particle buffer as per-instance vertex data.
+    fn descriptor<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Particle>() as wgpu::BufferAddress,
+            // Every vertex of a quad reads the same particle, then it moves on to the next
+            // particle for the next quad.
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Corresponds to SimParams in particles.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SimParams {
+    dt: f32,
+    gravity: f32,
+    // Uniform buffers should be a multiple of 16 bytes, WebGL is picky about it.
+    _padding: [f32; 2],
+}
+
+/// Can this adapter run the compute shader which moves the particles?
+/// WebGL can't run compute shaders at all.
+pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+}
+
+/// Lots of little particles, falling under gravity. A compute shader moves them every frame, then
+/// they're drawn as one instanced draw call. The CPU never touches them after creating them.
+pub struct ParticleSystem {
+    num_particles: u32,
+    particle_buffer: wgpu::Buffer,
+    particle_bind_group: wgpu::BindGroup,
+    params: Uniform<SimParams>,
+    compute_pipeline: wgpu::ComputePipeline,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        device: &wgpu::Device,
+        num_particles: u32,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+        normals_target: bool,
+    ) -> Self {
+        let particles: Vec<_> = (0..num_particles).map(initial_particle).collect();
+        let particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Buffer"),
+            contents: bytemuck::cast_slice(&particles),
+            // STORAGE so the compute shader can write to it, VERTEX so the render pipeline can
+            // read it as instance data.
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+        });
+        let particle_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("particle_bind_group_layout"),
+            });
+        let particle_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &particle_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: particle_buffer.as_entire_binding(),
+            }],
+            label: Some("particle_bind_group"),
+        });
+        let params = Uniform::new(
+            device,
+            SimParams {
+                dt: 0.0,
+                gravity: GRAVITY,
+                _padding: [0.0; 2],
+            },
+            wgpu::ShaderStages::COMPUTE,
+            "particle_params",
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Particle Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("particles.wgsl").into()),
+        });
+
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Particle Compute Pipeline Layout"),
+                bind_group_layouts: &[&particle_bind_group_layout, &params.layout],
+                push_constant_ranges: &[],
+            });
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Particle Compute Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Particle Render Pipeline Layout"),
+                bind_group_layouts: &[],
+                push_constant_ranges: &[],
+            });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Particle Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Particle::descriptor()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &crate::normals::scene_targets(
+                    target_format,
+                    wgpu::BlendState::REPLACE,
+                    normals_target,
+                ),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                // Quads always face the camera, so there's nothing to cull.
+                cull_mode: None,
+                ..Default::default()
+            },
+            // The scene pass has a depth buffer, so every pipeline drawn in it must say so. Particles
+            // are flat on the screen, in front of everything, so they ignore it.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            // Particles are drawn in the same pass as the scene, so must match its sample count.
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        Self {
+            num_particles,
+            particle_buffer,
+            particle_bind_group,
+            params,
+            compute_pipeline,
+            render_pipeline,
+        }
+    }
+
+    /// Tell the compute shader how much time has passed.
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: Duration) {
+        // If the window was dragged or the app was paused, dt could be huge, and every particle
+        // would teleport. Cap it.
+        let dt = dt.as_secs_f32().min(0.1);
+        self.params.set(SimParams {
+            dt,
+            gravity: GRAVITY,
+            _padding: [0.0; 2],
+        });
+        self.params.flush(queue);
+    }
+
+    /// Move the particles. Has to be recorded before the render pass that draws them.
+    pub fn compute(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Particle Compute Pass"),
+        });
+        pass.set_pipeline(&self.compute_pipeline);
+        pass.set_bind_group(0, &self.particle_bind_group, &[]);
+        pass.set_bind_group(1, self.params.bind_group(), &[]);
+        pass.dispatch_workgroups(self.num_particles.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, self.particle_buffer.slice(..));
+        // 6 vertices per quad, one quad per particle.
+        render_pass.draw(0..6, 0..self.num_particles);
+    }
+}
+
+/// Spread the particles out over the screen, with a bit of sideways drift.
+/// Uses the golden ratio to scatter them evenly without needing a random number generator.
+fn initial_particle(i: u32) -> Particle {
+    // f64 so the fractional part stays accurate even with millions of particles.
+    let scatter = |step: f64| (i as f64 * step).fract() as f32;
+    Particle {
+        position: [
+            scatter(0.618_034) * 2.0 - 1.0,
+            scatter(0.754_877_7) * 2.0 - 1.0,
+        ],
+        velocity: [(scatter(0.569_840_3) - 0.5) * 0.2, 0.0],
+    }
+}
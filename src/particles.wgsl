@@ -0,0 +1,93 @@
+// Corresponds to Particle in the Rust code.
+struct Particle {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+};
+
+// Corresponds to SimParams in the Rust code.
+struct SimParams {
+    // Seconds since the last update.
+    dt: f32,
+    // How fast particles accelerate downwards, in clip-space units per second per second.
+    gravity: f32,
+};
+
+// Compute shader
+
+@group(0) @binding(0)
+var<storage, read_write> particles: array<Particle>;
+@group(1) @binding(0)
+var<uniform> params: SimParams;
+
+// Particles stop speeding up once they fall this fast, otherwise after a few wrap-arounds they'd
+// be a blur.
+const MAX_FALL_SPEED: f32 = 1.5;
+
+// Each invocation moves one particle. They run in groups of 64, so the Rust code dispatches
+// ceil(N / 64) workgroups.
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    // The last workgroup can overhang the end of the array.
+    if i >= arrayLength(&particles) {
+        return;
+    }
+    var p = particles[i];
+    p.velocity.y = max(p.velocity.y - params.gravity * params.dt, -MAX_FALL_SPEED);
+    p.position += p.velocity * params.dt;
+    // Wrap around when leaving the screen, so particles that fall off the bottom reappear at the
+    // top. Maps -1..1 to 0..1, wraps with fract, then maps back.
+    p.position = fract((p.position + 1.0) * 0.5) * 2.0 - 1.0;
+    particles[i] = p;
+}
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+// Half the width of each particle's quad, in clip space.
+const PARTICLE_SIZE: f32 = 0.006;
+
+// Each instance is one particle. Its quad is 6 vertices (2 triangles), generated from the vertex
+// index, so there's no vertex buffer, only the per-instance particle buffer.
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) position: vec2<f32>,
+    @location(1) velocity: vec2<f32>,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(position + corners[vertex_index] * PARTICLE_SIZE, 0.0, 1.0);
+    // Faster particles glow hotter.
+    let heat = clamp(length(velocity) / MAX_FALL_SPEED, 0.0, 1.0);
+    out.color = mix(vec3<f32>(1.0, 0.3, 0.05), vec3<f32>(1.0, 0.9, 0.6), heat);
+    return out;
+}
+
+// Fragment shader
+
+// The normal is for --normals-target.
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(in.color, 1.0);
+    // Particles are flat on the screen, so they face +Z, towards the default camera.
+    out.normal = vec4<f32>(0.5, 0.5, 1.0, 1.0);
+    return out;
+}
@@ -0,0 +1,201 @@
+use wgpu::util::DeviceExt;
+
+use crate::{
+    mesh::Mesh,
+    scatter::{Instance, InstanceRaw},
+    scene::{ModelUniforms, SceneObject},
+    texture::Texture,
+    uniform::Uniform,
+};
+
+/// Corresponds to SpriteUniform in point_sprites.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteUniform {
+    viewport: [f32; 2],
+    size: f32,
+    // Uniform structs are padded to a multiple of 16 bytes.
+    _padding: f32,
+}
+
+/// One point: where it is in its mesh, and which copy of the mesh it belongs to.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointInstance {
+    position: [f32; 3],
+    instance: InstanceRaw,
+}
+
+/// Every vertex of the mesh, once per instance.
+fn points(positions: &[[f32; 3]], instances: &[Instance]) -> Vec<PointInstance> {
+    instances
+        .iter()
+        .flat_map(|instance| {
+            let instance = instance.to_raw();
+            positions
+                .iter()
+                .map(move |&position| PointInstance { position, instance })
+        })
+        .collect()
+}
+
+/// Draws every vertex of an object as a round dot, --point-size pixels across, for looking at
+/// vertex data as a point cloud. wgpu's point lists only ever draw one pixel per point, which is
+/// barely visible.
+///
+/// Each point is an instance, and each instance is a 4-vertex triangle strip, which the vertex
+/// shader spreads out into a square facing the camera. It has no vertex buffer of its own, just
+/// the instance buffer.
+///
+/// It has its own pipeline layout, so it sets its own bind groups, which means it has to be the
+/// last thing drawn in the scene pass.
+pub struct PointSprites {
+    /// Where each of the mesh's vertices is, for rebuilding the points in `set_instances`.
+    positions: Vec<[f32; 3]>,
+    /// One per vertex per instance of the object.
+    instance_buffer: wgpu::Buffer,
+    num_points: u32,
+    /// Pixels across.
+    size: f32,
+    sprite: Uniform<SpriteUniform>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl PointSprites {
+    /// `mesh` and `instances` should be what `object` was made from. `size` is in pixels.
+    ///
+    /// Like the particles, the points are drawn in the scene pass, so they have to match the
+    /// scene's format, sample count and targets, and test depth the same way.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        mesh: &Mesh,
+        instances: &[Instance],
+        camera_layout: &wgpu::BindGroupLayout,
+        models: &ModelUniforms,
+        size: f32,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+        normals_target: bool,
+        depth_compare: wgpu::CompareFunction,
+    ) -> Self {
+        let positions: Vec<_> = mesh.vertices.iter().map(|vertex| vertex.position).collect();
+        let points = points(&positions, instances);
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("point_sprite_instance_buffer"),
+            contents: bytemuck::cast_slice(&points),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let sprite = Uniform::new(
+            device,
+            SpriteUniform {
+                viewport: [1.0, 1.0],
+                size,
+                _padding: 0.0,
+            },
+            wgpu::ShaderStages::VERTEX,
+            "point_sprite_uniform",
+        );
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Point Sprite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("point_sprites.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Point Sprite Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, &models.layout, &sprite.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Point Sprite Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<PointInstance>() as wgpu::BufferAddress,
+                    // A whole square per point.
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    // The instance's texture layer comes last, and isn't needed.
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x3,
+                        1 => Float32x4,
+                        2 => Float32x4,
+                        3 => Float32x4,
+                        4 => Float32x4,
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &crate::normals::scene_targets(
+                    target_format,
+                    wgpu::BlendState::REPLACE,
+                    normals_target,
+                ),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                // The square always faces the camera, but which way round its corners go depends
+                // on whether y is flipped, so don't cull either side.
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+        Self {
+            positions,
+            instance_buffer,
+            num_points: points.len() as u32,
+            size,
+            sprite,
+            pipeline,
+        }
+    }
+
+    /// The points' size is in pixels, so they need to know how many pixels the viewport has.
+    /// Call before drawing. Only uploads anything if it changed.
+    /// Follow the object's instances after `SceneObject::set_instances`. There have to be as
+    /// many as before.
+    pub fn set_instances(&self, queue: &wgpu::Queue, instances: &[Instance]) {
+        let points = points(&self.positions, instances);
+        debug_assert_eq!(points.len() as u32, self.num_points);
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&points));
+    }
+
+    pub fn set_viewport(&mut self, queue: &wgpu::Queue, width: f32, height: f32) {
+        self.sprite.set(SpriteUniform {
+            viewport: [width, height],
+            size: self.size,
+            _padding: 0.0,
+        });
+        self.sprite.flush(queue);
+    }
+
+    /// Draw `object`'s points. See the struct's docs for why this has to come last in the pass.
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        models: &'a ModelUniforms,
+        object: &SceneObject,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        object.bind_model(render_pass, models, 1);
+        render_pass.set_bind_group(2, self.sprite.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..4, 0..self.num_points);
+    }
+}
@@ -0,0 +1,85 @@
+// Point sprites: each point is drawn as a square a fixed number of pixels across, facing the
+// camera, because wgpu's own points are always exactly one pixel.
+
+// Same as in common.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    eye_position: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Same as in common.wgsl. The points follow their object around, spinning and all.
+struct ModelUniform {
+    transform: mat4x4<f32>,
+    tint: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> model_uniform: ModelUniform;
+
+// Corresponds to SpriteUniform in the Rust code.
+struct SpriteUniform {
+    // Size of the viewport, in pixels.
+    viewport: vec2<f32>,
+    // How many pixels across each point is.
+    size: f32,
+};
+@group(2) @binding(0)
+var<uniform> sprite: SpriteUniform;
+
+// One per point. Corresponds to PointInstance in the Rust code.
+struct PointInput {
+    @location(0) position: vec3<f32>,
+    // Which copy of the object the point belongs to. See InstanceRaw.
+    @location(1) model_matrix_0: vec4<f32>,
+    @location(2) model_matrix_1: vec4<f32>,
+    @location(3) model_matrix_2: vec4<f32>,
+    @location(4) model_matrix_3: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Where in the square this is, from -1 to 1 each way.
+    @location(0) corner: vec2<f32>,
+};
+
+// Drawn as a 4-vertex triangle strip per point (instance). The vertex index picks the corner.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, point: PointInput) -> VertexOutput {
+    let instance_matrix = mat4x4<f32>(
+        point.model_matrix_0,
+        point.model_matrix_1,
+        point.model_matrix_2,
+        point.model_matrix_3,
+    );
+    // (-1, -1), (1, -1), (-1, 1), (1, 1): a strip making a square.
+    let corner = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u)) * 2.0 - 1.0;
+    let center = camera.view_proj * instance_matrix * model_uniform.transform
+        * vec4<f32>(point.position, 1.0);
+    var out: VertexOutput;
+    // The viewport is 2 wide in normalized device coordinates, so a pixel is 2 / viewport. Clip
+    // space gets divided by w afterwards, so multiply by it to cancel that out, keeping the
+    // square the same size on screen however far away it is.
+    let offset = corner * sprite.size / sprite.viewport * center.w;
+    out.clip_position = vec4<f32>(center.xy + offset, center.zw);
+    out.corner = corner;
+    return out;
+}
+
+// The normal is for --normals-target. A sprite has no real surface, so it gets a zero normal.
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    // Round, rather than square.
+    if dot(in.corner, in.corner) > 1.0 {
+        discard;
+    }
+    var out: FragmentOutput;
+    out.color = vec4<f32>(1.0, 0.9, 0.3, 1.0);
+    out.normal = vec4<f32>(0.5, 0.5, 0.5, 0.0);
+    return out;
+}
@@ -0,0 +1,290 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::JoinHandle,
+};
+
+/// How many frames can be waiting to be read back at once. Mapping a buffer only completes once
+/// the GPU has finished the frame, so with only one buffer every frame would have to wait for
+/// the GPU to catch up. With a few, the CPU can keep going while earlier frames finish.
+const RING_SIZE: usize = 3;
+
+/// Copies frames off the surface and saves them as numbered PNGs (see --record), e.g. for turning
+/// into a video or GIF with `ffmpeg -i frame_%05d.png out.gif`.
+pub struct FrameRecorder {
+    /// How many frames to record in total.
+    frames: u32,
+    /// How many frames have been copied off the GPU so far.
+    captured: u32,
+    /// How many frames have been read back and handed to the writer thread.
+    saved: u32,
+    format: wgpu::TextureFormat,
+    slots: Vec<Slot>,
+    /// Map callbacks send their slot's index here once it's ready to read (or failed).
+    mapped_tx: Sender<(usize, Result<(), wgpu::BufferAsyncError>)>,
+    mapped_rx: Receiver<(usize, Result<(), wgpu::BufferAsyncError>)>,
+    /// PNG encoding is slow, so it happens on another thread. Dropping the sender ends it.
+    writer_tx: Option<Sender<(PathBuf, image::RgbaImage)>>,
+    writer: Option<JoinHandle<()>>,
+    dir: PathBuf,
+}
+
+/// One readback buffer, and which frame (if any) is in it.
+struct Slot {
+    buffer: Option<wgpu::Buffer>,
+    /// Size of the frame in the buffer. If the window resizes, the buffer gets recreated.
+    width: u32,
+    height: u32,
+    /// Bytes from one row to the next in the buffer. Copies need rows aligned to 256 bytes, so
+    /// this can be more than the frame's actual width.
+    padded_bytes_per_row: u32,
+    /// The frame number waiting in this buffer, or None if it's free.
+    frame: Option<u32>,
+    /// The copy's been recorded, but the buffer hasn't started mapping, because the copy hadn't
+    /// been submitted yet.
+    needs_map: bool,
+}
+
+/// Why recording can't start.
+#[derive(Debug)]
+pub enum RecordError {
+    UnsupportedFormat(wgpu::TextureFormat),
+    CreateDir(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => {
+                write!(
+                    f,
+                    "can't save frames in {format:?}, only 8-bit RGBA or BGRA"
+                )
+            }
+            Self::CreateDir(dir, e) => write!(f, "couldn't create {}: {e}", dir.display()),
+        }
+    }
+}
+
+impl FrameRecorder {
+    /// Record `frames` frames of a surface in `format` into `dir`, which gets created if need be.
+    pub fn new(
+        frames: u32,
+        dir: PathBuf,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, RecordError> {
+        if bytes_per_pixel(format).is_none() {
+            return Err(RecordError::UnsupportedFormat(format));
+        }
+        std::fs::create_dir_all(&dir).map_err(|e| RecordError::CreateDir(dir.clone(), e))?;
+        let (mapped_tx, mapped_rx) = mpsc::channel();
+        let (writer_tx, writer_rx) = mpsc::channel::<(PathBuf, image::RgbaImage)>();
+        let writer = std::thread::spawn(move || {
+            for (path, image) in writer_rx {
+                if let Err(e) = image.save(&path) {
+                    log::error!("Couldn't save {}: {e}", path.display());
+                }
+            }
+        });
+        let slots = (0..RING_SIZE)
+            .map(|_| Slot {
+                buffer: None,
+                width: 0,
+                height: 0,
+                padded_bytes_per_row: 0,
+                frame: None,
+                needs_map: false,
+            })
+            .collect();
+        Ok(Self {
+            frames,
+            captured: 0,
+            saved: 0,
+            format,
+            slots,
+            mapped_tx,
+            mapped_rx,
+            writer_tx: Some(writer_tx),
+            writer: Some(writer),
+            dir,
+        })
+    }
+
+    /// True once every frame has been copied off the GPU. Some may still be waiting to be saved.
+    pub fn is_done_capturing(&self) -> bool {
+        self.captured >= self.frames
+    }
+
+    /// Copy `texture` (the frame that's about to be presented) into a free readback buffer. The
+    /// copy goes in `encoder`, which must be submitted before calling `after_submit`.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        width: u32,
+        height: u32,
+    ) {
+        if self.is_done_capturing() {
+            return;
+        }
+        // Every buffer still has an unsaved frame in it, so wait for the oldest. This only
+        // happens if the GPU is more than RING_SIZE frames behind.
+        while self.slots.iter().all(|slot| slot.frame.is_some()) {
+            device.poll(wgpu::Maintain::Wait);
+            self.save_ready_frames();
+        }
+        let format = self.format;
+        let slot = self
+            .slots
+            .iter_mut()
+            .find(|slot| slot.frame.is_none())
+            .unwrap();
+        if slot.buffer.is_none() || (slot.width, slot.height) != (width, height) {
+            let unpadded = width * bytes_per_pixel(format).unwrap();
+            let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+            slot.padded_bytes_per_row = unpadded.div_ceil(align) * align;
+            slot.width = width;
+            slot.height = height;
+            slot.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Frame Readback Buffer"),
+                size: (slot.padded_bytes_per_row * height) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }));
+        }
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: slot.buffer.as_ref().unwrap(),
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(slot.padded_bytes_per_row),
+                    rows_per_image: std::num::NonZeroU32::new(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        slot.frame = Some(self.captured);
+        slot.needs_map = true;
+        self.captured += 1;
+    }
+
+    /// Start mapping the buffer `capture` just copied into, and save any frames which have
+    /// finished mapping since last time. Never waits for the GPU.
+    pub fn after_submit(&mut self, device: &wgpu::Device) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if !std::mem::take(&mut slot.needs_map) {
+                continue;
+            }
+            let tx = self.mapped_tx.clone();
+            if let Some(buffer) = &slot.buffer {
+                buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |result| {
+                        // The recorder might be gone by now, in which case nobody cares.
+                        let _ = tx.send((index, result));
+                    });
+            }
+        }
+        device.poll(wgpu::Maintain::Poll);
+        self.save_ready_frames();
+    }
+
+    /// Wait for every captured frame to be read back, then for the writer thread to save them.
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        while self.slots.iter().any(|slot| slot.frame.is_some()) {
+            device.poll(wgpu::Maintain::Wait);
+            if !self.save_ready_frames() {
+                // Nothing came back, so a map must have failed. Don't wait forever.
+                break;
+            }
+        }
+        // Closing the channel stops the writer once it's saved everything.
+        self.writer_tx = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+        log::info!("Recorded {} frame(s) to {}", self.saved, self.dir.display());
+    }
+
+    /// Hand every mapped buffer's frame to the writer thread, and free its slot. Returns whether
+    /// there were any.
+    fn save_ready_frames(&mut self) -> bool {
+        let mut any = false;
+        while let Ok((index, result)) = self.mapped_rx.try_recv() {
+            any = true;
+            let slot = &mut self.slots[index];
+            let (Some(buffer), Some(frame)) = (&slot.buffer, slot.frame.take()) else {
+                continue;
+            };
+            if let Err(e) = result {
+                log::error!("Couldn't read back recorded frame {frame}: {e}");
+                continue;
+            }
+            let image = {
+                let data = buffer.slice(..).get_mapped_range();
+                to_rgba_image(
+                    &data,
+                    slot.width,
+                    slot.height,
+                    slot.padded_bytes_per_row,
+                    self.format,
+                )
+            };
+            buffer.unmap();
+            let path = self.dir.join(format!("frame_{frame:05}.png"));
+            if let Some(writer_tx) = &self.writer_tx {
+                let _ = writer_tx.send((path, image));
+            }
+            self.saved += 1;
+            if self.saved == self.frames {
+                log::info!("Captured all {} frame(s), saving them", self.frames);
+            }
+        }
+        any
+    }
+}
+
+/// Bytes per pixel of the surface formats frames can be saved from.
+fn bytes_per_pixel(format: wgpu::TextureFormat) -> Option<u32> {
+    use wgpu::TextureFormat::*;
+    match format {
+        Rgba8Unorm | Rgba8UnormSrgb | Bgra8Unorm | Bgra8UnormSrgb => Some(4),
+        _ => None,
+    }
+}
+
+/// Strip the row padding, and swap BGRA to RGBA if need be. sRGB formats are already stored as
+/// sRGB, which is what PNGs expect, so the colours are left alone.
+fn to_rgba_image(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+) -> image::RgbaImage {
+    let is_bgra = matches!(
+        format,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    );
+    let row_bytes = (width * 4) as usize;
+    let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+    for row in data
+        .chunks(padded_bytes_per_row as usize)
+        .take(height as usize)
+    {
+        pixels.extend_from_slice(&row[..row_bytes]);
+    }
+    if is_bgra {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    image::RgbaImage::from_raw(width, height, pixels)
+        .expect("pixel buffer is exactly width * height * 4 bytes")
+}
@@ -0,0 +1,15 @@
+/// Whether this process is running under RenderDoc, i.e. whether RenderDoc has injected its
+/// library into it. None if there's no way to tell on this platform.
+///
+/// wgpu does the actual capturing (`Device::start_capture` and `stop_capture` call RenderDoc's
+/// StartFrameCapture and EndFrameCapture), but does nothing, silently, without RenderDoc. This
+/// is only so F12 can say why nothing happened.
+pub fn is_attached() -> Option<bool> {
+    if cfg!(target_os = "linux") {
+        // Every library loaded into the process is listed here.
+        let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+        Some(maps.contains("librenderdoc.so"))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,136 @@
+use cgmath::{Matrix4, Rad, Vector3};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Used when no --seed is given. Fixed rather than random, so the default demo looks the same
+/// every run, and screenshots can be compared.
+pub const DEFAULT_SEED: u64 = 0x5eed;
+
+/// One copy of the mesh, placed somewhere in the world.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instance {
+    pub position: Vector3<f32>,
+    /// Rotation around the Z axis, i.e. spinning in the plane of the screen.
+    pub rotation: Rad<f32>,
+    pub scale: f32,
+    /// Which layer of the array texture to draw this instance with.
+    pub texture_layer: u32,
+}
+
+impl Instance {
+    /// Leaves the mesh exactly where it is.
+    pub fn identity() -> Self {
+        Self {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Rad(0.0),
+            scale: 1.0,
+            texture_layer: 0,
+        }
+    }
+
+    /// Moves the mesh from where it is to where this instance is.
+    pub fn matrix(self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.position)
+            * Matrix4::from_angle_z(self.rotation)
+            * Matrix4::from_scale(self.scale)
+    }
+
+    pub fn to_raw(self) -> InstanceRaw {
+        InstanceRaw {
+            model: self.matrix().into(),
+            texture_layer: self.texture_layer,
+        }
+    }
+}
+
+/// What actually goes in the instance buffer. Shaders don't understand positions and rotations,
+/// so it's all baked into one matrix.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    model: [[f32; 4]; 4],
+    texture_layer: u32,
+}
+
+impl InstanceRaw {
+    pub fn descriptor<'a>() -> wgpu::VertexBufferLayout<'a> {
+        // A mat4 is too big for one vertex attribute, so it's passed as 4 vec4 columns.
+        // Locations start at 5, leaving room for more per-vertex attributes.
+        const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32x4,
+            9 => Uint32,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            // Move on to the next matrix once per instance, rather than once per vertex.
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Scatters instances randomly around the screen. The same seed always gives the same layout.
+#[derive(Clone, Debug)]
+pub struct ScatterBuilder {
+    count: usize,
+    seed: u64,
+    texture_layers: u32,
+}
+
+impl ScatterBuilder {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count,
+            seed: DEFAULT_SEED,
+            texture_layers: 1,
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Give each instance a random layer out of this many.
+    pub fn texture_layers(mut self, texture_layers: u32) -> Self {
+        self.texture_layers = texture_layers.max(1);
+        self
+    }
+
+    pub fn build(&self) -> Vec<Instance> {
+        // Seeded rather than seeded from entropy, so the layout is reproducible.
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        (0..self.count)
+            .map(|_| Instance {
+                position: Vector3::new(rng.gen_range(-0.9..0.9), rng.gen_range(-0.9..0.9), 0.0),
+                rotation: Rad(rng.gen_range(0.0..std::f32::consts::TAU)),
+                scale: rng.gen_range(0.15..0.35),
+                texture_layer: rng.gen_range(0..self.texture_layers),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The instance buffer's contents, as the GPU would get them.
+    fn instance_data(builder: &ScatterBuilder) -> Vec<u8> {
+        let raw: Vec<InstanceRaw> = builder.build().into_iter().map(Instance::to_raw).collect();
+        bytemuck::cast_slice(&raw).to_vec()
+    }
+
+    #[test]
+    fn same_seed_same_instances() {
+        let a = ScatterBuilder::new(50).seed(1234).texture_layers(4);
+        let b = ScatterBuilder::new(50).seed(1234).texture_layers(4);
+        assert_eq!(instance_data(&a), instance_data(&b));
+        // Building twice from the same builder doesn't carry on the random sequence either.
+        assert_eq!(instance_data(&a), instance_data(&a));
+        let c = ScatterBuilder::new(50).seed(1235).texture_layers(4);
+        assert_ne!(instance_data(&a), instance_data(&c));
+    }
+}
@@ -0,0 +1,644 @@
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::Camera,
+    frustum::Frustum,
+    mesh::{Mesh, VertexLayout},
+    scatter::{Instance, InstanceRaw},
+};
+
+/// How many objects `ModelUniforms` has room for. The buffer's allocated up front, so this has to
+/// be picked in advance. 64 slots of 256 bytes is only 16KiB.
+const MAX_SCENE_OBJECTS: u32 = 64;
+
+/// Colours the selected object's tint cycles through. Linear RGB, because it's multiplied with
+/// the texture in the shader.
+pub const PALETTE: [[f32; 3]; 8] = [
+    [1.0, 0.2, 0.2], // red
+    [0.2, 1.0, 0.2], // green
+    [0.2, 0.4, 1.0], // blue
+    [1.0, 1.0, 0.2], // yellow
+    [0.2, 1.0, 1.0], // cyan
+    [1.0, 0.2, 1.0], // magenta
+    [1.0, 0.5, 0.1], // orange
+    [0.5, 0.5, 0.5], // grey
+];
+
+/// Uniform holding the model matrix, i.e. where the object is and which way it's facing, plus a
+/// colour to multiply its texture by.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelUniform {
+    // cgmath's matrices aren't Pod, so store it as plain arrays.
+    transform: [[f32; 4]; 4],
+    /// RGBA, with the colour premultiplied by alpha, like the textures it's multiplied with. The
+    /// alpha only matters for transparent objects, opaque ones replace whatever's behind them
+    /// anyway.
+    tint: [f32; 4],
+}
+
+/// Every object's `ModelUniform`, in one buffer. Each object gets its own slot, and picks it at
+/// draw time with a dynamic offset. So there's one bind group for all of them, rather than one
+/// per object, and changing one object's slot can't affect the others.
+pub struct ModelUniforms {
+    buffer: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    /// Bytes from one slot to the next. Dynamic offsets have to be a multiple of the device's
+    /// `min_uniform_buffer_offset_alignment` (usually 256), which is more than a ModelUniform
+    /// needs, so there's padding between slots.
+    stride: u64,
+    /// How many slots have been handed out.
+    len: u32,
+    /// A copy of the buffer's contents, so changes to lots of objects can be sent to the GPU in
+    /// one write, by `flush`.
+    staging: Vec<u8>,
+    /// Whether `staging` has changed since the last flush.
+    dirty: bool,
+}
+
+impl ModelUniforms {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let size = std::mem::size_of::<ModelUniform>() as u64;
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = wgpu::util::align_to(size, alignment);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Model Uniforms"),
+            size: stride * MAX_SCENE_OBJECTS as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                // The vertex shader needs the transform to move the vertices, the fragment shader
+                // needs the tint.
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(size),
+                },
+                count: None,
+            }],
+            label: Some("Model Uniforms"),
+        });
+        // The binding is one slot wide. The dynamic offset slides it along the buffer.
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(size),
+                }),
+            }],
+            label: Some("Model Uniforms"),
+        });
+        Self {
+            buffer,
+            layout,
+            bind_group,
+            stride,
+            len: 0,
+            staging: Vec::new(),
+            dirty: false,
+        }
+    }
+
+    /// How many slots are in use. Slots are numbered from 0, so this is also the next one.
+    pub fn slots_used(&self) -> u32 {
+        self.len
+    }
+
+    fn allocate(&mut self) -> u32 {
+        assert!(
+            self.len < MAX_SCENE_OBJECTS,
+            "more than {MAX_SCENE_OBJECTS} scene objects, raise MAX_SCENE_OBJECTS"
+        );
+        self.len += 1;
+        self.staging
+            .resize(self.len as usize * self.stride as usize, 0);
+        self.len - 1
+    }
+
+    fn set(&mut self, slot: u32, value: ModelUniform) {
+        let start = self.offset(slot) as usize;
+        let bytes = bytemuck::bytes_of(&value);
+        self.staging[start..start + bytes.len()].copy_from_slice(bytes);
+        self.dirty = true;
+    }
+
+    /// Copy every change since the last flush to the GPU. Call once per frame, before rendering.
+    pub fn flush(&mut self, queue: &wgpu::Queue) {
+        if self.dirty {
+            queue.write_buffer(&self.buffer, 0, &self.staging);
+            self.dirty = false;
+        }
+    }
+
+    fn offset(&self, slot: u32) -> u32 {
+        (slot as u64 * self.stride) as u32
+    }
+}
+
+/// One mesh in the world, with its own GPU buffers and a slot in `ModelUniforms`.
+pub struct SceneObject {
+    label: String,
+    /// One per slot in the `VertexLayout` it was created with.
+    vertex_buffers: Vec<wgpu::Buffer>,
+    index_buffer: wgpu::Buffer,
+    /// 16-bit for meshes which came with small enough indices, see `Mesh::index_data`.
+    index_format: wgpu::IndexFormat,
+    num_indices: u32,
+    instance_buffer: wgpu::Buffer,
+    num_instances: u32,
+    /// Every instance, including culled ones. The instance buffer only has the visible ones.
+    instances: Vec<Instance>,
+    /// Which of `instances` are in the instance buffer, in order. All of them, until `cull` says
+    /// otherwise.
+    visible: Vec<usize>,
+    /// The centre and radius of a sphere around the mesh, in the mesh's own coordinates, for
+    /// `cull`. None if the mesh is empty.
+    bounding_sphere: Option<(Point3<f32>, f32)>,
+    /// Which of the `ModelUniforms` is this object's.
+    slot: u32,
+    /// Where the object is, before any spinning.
+    transform: Matrix4<f32>,
+    /// How far `spin` has turned it.
+    rotation: Rad<f32>,
+    /// The tint it was created with.
+    original_tint: [f32; 4],
+    tint: [f32; 4],
+    /// Where `next_palette_tint` is up to. None means the original tint.
+    palette_index: Option<usize>,
+    /// If true, `spin` turns the object around its own Z axis.
+    pub spins: bool,
+    /// If set, `draw` reads its parameters from this buffer instead of passing them directly.
+    indirect_buffer: Option<wgpu::Buffer>,
+    /// The mesh's bounding box, as lines, for `draw_bounding_box`. None if the mesh is empty.
+    bounding_box_buffer: Option<wgpu::Buffer>,
+    /// The mesh's triangles, unindexed, for `draw_wireframe`.
+    wireframe_buffer: wgpu::Buffer,
+    wireframe_vertices: u32,
+}
+
+impl SceneObject {
+    /// `vertex_layout` and `topology` have to match the pipeline's, see
+    /// `VertexLayout::buffer_layouts` and `Mesh::indices_for`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        models: &mut ModelUniforms,
+        label: &str,
+        mesh: &Mesh,
+        vertex_layout: VertexLayout,
+        topology: wgpu::PrimitiveTopology,
+        instances: &[Instance],
+        transform: Matrix4<f32>,
+        tint: [f32; 4],
+    ) -> Self {
+        let vertex_buffers = mesh
+            .vertex_data(vertex_layout)
+            .iter()
+            .enumerate()
+            .map(|(slot, contents)| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{label} Vertex Buffer {slot}")),
+                    contents,
+                    usage: wgpu::BufferUsages::VERTEX,
+                })
+            })
+            .collect();
+        let (indices, index_format) = mesh.index_data(topology);
+        let index_size = match index_format {
+            wgpu::IndexFormat::Uint16 => 2,
+            wgpu::IndexFormat::Uint32 => 4,
+        };
+        let num_indices = (indices.len() / index_size) as u32;
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Index Buffer")),
+            contents: &indices,
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let instance_data: Vec<InstanceRaw> = instances.iter().map(|i| i.to_raw()).collect();
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Instance Buffer")),
+            contents: bytemuck::cast_slice(&instance_data),
+            // COPY_DST so `set_instances` can move them around later, and `cull` can leave some
+            // out.
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        // Worked out now, while there's a mesh to work them out from. They never change.
+        let bounding_box = mesh.bounding_box();
+        let bounding_sphere = bounding_box.map(|bbox| (bbox.center(), bbox.radius()));
+        let bounding_box_buffer = bounding_box.map(|bbox| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{label} Bounding Box Buffer")),
+                contents: bytemuck::cast_slice(&bbox.edges()),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+        let triangle_positions = mesh.triangle_positions();
+        let wireframe_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Wireframe Buffer")),
+            contents: bytemuck::cast_slice(&triangle_positions),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let object = Self {
+            label: label.to_owned(),
+            vertex_buffers,
+            index_buffer,
+            index_format,
+            num_indices,
+            instance_buffer,
+            num_instances: instance_data.len() as u32,
+            instances: instances.to_vec(),
+            visible: (0..instances.len()).collect(),
+            bounding_sphere,
+            slot: models.allocate(),
+            transform,
+            rotation: Rad(0.0),
+            original_tint: tint,
+            tint,
+            palette_index: None,
+            spins: false,
+            indirect_buffer: None,
+            bounding_box_buffer,
+            wireframe_buffer,
+            wireframe_vertices: triangle_positions.len() as u32,
+        };
+        object.upload(models);
+        object
+    }
+
+    /// Move the instances somewhere else, e.g. after rerolling the scatter. There have to be as
+    /// many as the object was created with, because the instance buffer can't grow.
+    pub fn set_instances(&mut self, queue: &wgpu::Queue, instances: &[Instance]) {
+        debug_assert_eq!(instances.len() as u32, self.num_instances);
+        self.instances = instances.to_vec();
+        self.write_instances(queue, (0..instances.len()).collect());
+    }
+
+    /// Put only the instances `frustum` can see in the instance buffer, so `draw` skips the rest.
+    /// Instances are tested by a sphere around the mesh, moved to wherever the instance is.
+    /// Returns how many instances were culled.
+    ///
+    /// Call after the object's been spun for the frame, and again for every camera it's drawn
+    /// from.
+    pub fn cull(&mut self, queue: &wgpu::Queue, frustum: &Frustum) -> u32 {
+        let Some((center, radius)) = self.bounding_sphere else {
+            return 0;
+        };
+        let model = self.model_matrix();
+        let visible: Vec<usize> = (0..self.instances.len())
+            .filter(|&i| {
+                let matrix = self.instances[i].matrix() * model;
+                let world_center = Point3::from_homogeneous(matrix * center.to_homogeneous());
+                frustum.contains_sphere(world_center, radius * max_scale(matrix))
+            })
+            .collect();
+        // Most frames nothing comes into or goes out of view, so there's nothing to upload.
+        if visible != self.visible {
+            self.write_instances(queue, visible);
+        }
+        self.num_instances - self.visible.len() as u32
+    }
+
+    /// Compact the `visible` instances into the start of the instance buffer.
+    fn write_instances(&mut self, queue: &wgpu::Queue, visible: Vec<usize>) {
+        let instance_data: Vec<InstanceRaw> = visible
+            .iter()
+            .map(|&i| self.instances[i].to_raw())
+            .collect();
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(&instance_data),
+        );
+        self.visible = visible;
+        if let Some(indirect_buffer) = &self.indirect_buffer {
+            queue.write_buffer(indirect_buffer, 0, self.indirect_args().as_bytes());
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Stays the same for the object's whole life, so it can be used to identify it.
+    pub fn slot(&self) -> u32 {
+        self.slot
+    }
+
+    /// Draw with `draw_indexed_indirect` from now on. The GPU reads the same numbers `draw` would
+    /// have passed directly, from a buffer, so the result is exactly the same. The point is that a
+    /// compute shader could fill in that buffer instead, e.g. to cull instances on the GPU.
+    pub fn use_indirect_draw(&mut self, device: &wgpu::Device) {
+        self.indirect_buffer = Some(
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Indirect Draw Buffer"),
+                contents: self.indirect_args().as_bytes(),
+                // STORAGE too, so a compute shader could write the draw parameters later, and
+                // COPY_DST so `cull` can change the instance count.
+                usage: wgpu::BufferUsages::INDIRECT
+                    | wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST,
+            }),
+        );
+    }
+
+    /// What `draw` would pass to `draw_indexed`.
+    fn indirect_args(&self) -> wgpu::util::DrawIndexedIndirect {
+        wgpu::util::DrawIndexedIndirect {
+            vertex_count: self.num_indices,
+            instance_count: self.visible.len() as u32,
+            base_index: 0,
+            vertex_offset: 0,
+            base_instance: 0,
+        }
+    }
+
+    /// Turn the object to `angle` around its own centre.
+    pub fn spin(&mut self, models: &mut ModelUniforms, angle: Rad<f32>) {
+        self.rotation = angle;
+        self.upload(models);
+    }
+
+    /// Change the tint to the next colour in `PALETTE`. After the last one, go back to the tint
+    /// the object started with. Alpha is left alone, so transparent objects stay transparent.
+    /// Returns the new colour.
+    pub fn next_palette_tint(&mut self, models: &mut ModelUniforms) -> [f32; 4] {
+        self.palette_index = match self.palette_index {
+            None => Some(0),
+            Some(i) if i + 1 < PALETTE.len() => Some(i + 1),
+            Some(_) => None,
+        };
+        self.tint = match self.palette_index {
+            Some(i) => {
+                let [r, g, b] = PALETTE[i];
+                [r, g, b, self.original_tint[3]]
+            }
+            None => self.original_tint,
+        };
+        self.upload(models);
+        self.tint
+    }
+
+    /// Go back to the tint the object was created with.
+    pub fn reset_tint(&mut self, models: &mut ModelUniforms) {
+        self.palette_index = None;
+        self.tint = self.original_tint;
+        self.upload(models);
+    }
+
+    /// Copy the transform and tint into this object's slot. They reach the GPU at the next
+    /// `ModelUniforms::flush`.
+    fn upload(&self, models: &mut ModelUniforms) {
+        let [r, g, b, a] = self.tint;
+        models.set(
+            self.slot,
+            ModelUniform {
+                transform: self.model_matrix().into(),
+                tint: [r * a, g * a, b * a, a],
+            },
+        );
+    }
+
+    /// Where the object is, spun by however far it's been spun.
+    fn model_matrix(&self) -> Matrix4<f32> {
+        self.transform * Matrix4::from_angle_z(self.rotation)
+    }
+
+    /// How far in front of the camera the object's centre is, measured along the view direction.
+    pub fn view_depth(&self, camera: &Camera) -> f32 {
+        let centre = Point3::from_vec(self.transform.w.truncate());
+        (centre - camera.eye).dot(camera.forward())
+    }
+
+    /// Assumes the pipeline and every bind group except the model's are already set.
+    /// Bind this object's slot of `models` to `model_group`, for drawing it or anything that
+    /// should move with it.
+    pub fn bind_model<'a>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        models: &'a ModelUniforms,
+        model_group: u32,
+    ) {
+        render_pass.set_bind_group(model_group, &models.bind_group, &[models.offset(self.slot)]);
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        models: &'a ModelUniforms,
+        model_group: u32,
+    ) {
+        // Culled entirely. With indirect draws the GPU would skip it anyway, but this saves
+        // recording it.
+        if self.visible.is_empty() {
+            return;
+        }
+        self.bind_model(render_pass, models, model_group);
+        for (slot, vertex_buffer) in self.vertex_buffers.iter().enumerate() {
+            render_pass.set_vertex_buffer(slot as u32, vertex_buffer.slice(..));
+        }
+        // The instance data goes in the slot after the vertex data.
+        render_pass.set_vertex_buffer(
+            self.vertex_buffers.len() as u32,
+            self.instance_buffer.slice(..),
+        );
+        render_pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+        match &self.indirect_buffer {
+            Some(indirect_buffer) => render_pass.draw_indexed_indirect(indirect_buffer, 0),
+            None => render_pass.draw_indexed(0..self.num_indices, 0, 0..self.visible.len() as u32),
+        }
+    }
+
+    /// Outline the mesh's bounding box, in the same place as `draw` would draw the mesh. Expects
+    /// `BoundingBoxes`'s pipeline to be set.
+    pub fn draw_bounding_box<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        models: &'a ModelUniforms,
+        model_group: u32,
+    ) {
+        let Some(bounding_box_buffer) = &self.bounding_box_buffer else {
+            return;
+        };
+        self.bind_model(render_pass, models, model_group);
+        render_pass.set_vertex_buffer(0, bounding_box_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        // Two vertices for each of the 12 edges.
+        render_pass.draw(0..24, 0..self.visible.len() as u32);
+    }
+
+    /// Draw the mesh's triangle edges over it, in the same place as `draw` would draw the mesh.
+    /// Expects `Wireframe`'s pipeline to be set.
+    pub fn draw_wireframe<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        models: &'a ModelUniforms,
+        model_group: u32,
+    ) {
+        if self.visible.is_empty() || self.wireframe_vertices == 0 {
+            return;
+        }
+        self.bind_model(render_pass, models, model_group);
+        render_pass.set_vertex_buffer(0, self.wireframe_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.draw(0..self.wireframe_vertices, 0..self.visible.len() as u32);
+    }
+}
+
+/// How much `matrix` stretches things, in whichever direction it stretches them most, so a sphere
+/// moved by it is sure to fit inside a sphere this much bigger.
+fn max_scale(matrix: Matrix4<f32>) -> f32 {
+    [matrix.x, matrix.y, matrix.z]
+        .map(|axis| axis.truncate().magnitude())
+        .into_iter()
+        .fold(0.0, f32::max)
+}
+
+/// Sort transparent objects so the furthest from the camera is drawn first. Blending mixes each
+/// object with whatever's already been drawn behind it, so anything behind has to be drawn first.
+/// Depth testing can't help, because transparent objects don't write depth (otherwise they'd hide
+/// each other completely).
+pub fn sort_back_to_front(objects: &mut [SceneObject], camera: &Camera) {
+    objects.sort_by(|a, b| b.view_depth(camera).total_cmp(&a.view_depth(camera)));
+}
+
+/// A long strip of floor below the origin, running from just behind the default camera to twice
+/// `zfar` away. With the usual depth clipping it stops dead at the far plane. With unclipped depth
+/// it carries on to the horizon.
+pub fn far_strip(
+    device: &wgpu::Device,
+    models: &mut ModelUniforms,
+    vertex_layout: VertexLayout,
+    topology: wgpu::PrimitiveTopology,
+    zfar: f32,
+) -> SceneObject {
+    let length = zfar * 2.0 + 4.0;
+    // The quad faces +Z, so lay it flat, facing up, then stretch it out along -Z.
+    let transform = Matrix4::from_translation(Vector3::new(0.0, -1.0, 4.0 - length / 2.0))
+        * Matrix4::from_angle_x(Deg(-90.0))
+        * Matrix4::from_nonuniform_scale(2.0, length, 1.0);
+    SceneObject::new(
+        device,
+        models,
+        "Far Strip",
+        &Mesh::quad(),
+        vertex_layout,
+        topology,
+        &[Instance::identity()],
+        transform,
+        [1.0; 4],
+    )
+}
+
+/// How big the --ground plane is, and how many squares it's split into along each side.
+const GROUND_SIZE: f32 = 6.0;
+const GROUND_SUBDIVISIONS: u32 = 12;
+
+/// A big grey floor at y = 0, for --ground, so models have something to stand on (see
+/// `Mesh::place_on_floor`) and cast shadows onto.
+pub fn ground(
+    device: &wgpu::Device,
+    models: &mut ModelUniforms,
+    vertex_layout: VertexLayout,
+    topology: wgpu::PrimitiveTopology,
+) -> SceneObject {
+    SceneObject::new(
+        device,
+        models,
+        "Ground",
+        &Mesh::plane(GROUND_SIZE, GROUND_SUBDIVISIONS),
+        vertex_layout,
+        topology,
+        &[Instance::identity()],
+        Matrix4::identity(),
+        [0.6, 0.6, 0.6, 1.0],
+    )
+}
+
+/// A cube sitting on a patch of ground below the origin, off to the side, for --shadows. The
+/// light's over the default camera's shoulder, so shadows fall on the ground behind things, away
+/// from the camera.
+pub fn shadow_demo(
+    device: &wgpu::Device,
+    models: &mut ModelUniforms,
+    vertex_layout: VertexLayout,
+    topology: wgpu::PrimitiveTopology,
+) -> Vec<SceneObject> {
+    const GROUND_Y: f32 = -1.0;
+    const CUBE_SIZE: f32 = 0.6;
+    // The quad faces +Z, so lay it flat, facing up.
+    let ground = Matrix4::from_translation(Vector3::new(0.0, GROUND_Y, 0.0))
+        * Matrix4::from_angle_x(Deg(-90.0))
+        * Matrix4::from_scale(4.0);
+    let cube = Matrix4::from_translation(Vector3::new(-1.0, GROUND_Y + CUBE_SIZE / 2.0, 0.8))
+        * Matrix4::from_scale(CUBE_SIZE);
+    vec![
+        SceneObject::new(
+            device,
+            models,
+            "Ground",
+            &Mesh::quad(),
+            vertex_layout,
+            topology,
+            &[Instance::identity()],
+            ground,
+            [0.8, 0.8, 0.8, 1.0],
+        ),
+        SceneObject::new(
+            device,
+            models,
+            "Cube",
+            &Mesh::cube(),
+            vertex_layout,
+            topology,
+            &[Instance::identity()],
+            cube,
+            [1.0; 4],
+        ),
+    ]
+}
+
+/// Two overlapping, half-see-through coloured quads, one in front of the other, to show off
+/// transparency sorting. Orbit around them and they should still blend correctly.
+pub fn transparent_quads(
+    device: &wgpu::Device,
+    models: &mut ModelUniforms,
+    vertex_layout: VertexLayout,
+    topology: wgpu::PrimitiveTopology,
+) -> Vec<SceneObject> {
+    let quad = Mesh::quad();
+    let place =
+        |x, z| Matrix4::from_translation(Vector3::new(x, 0.1, z)) * Matrix4::from_scale(1.2);
+    vec![
+        SceneObject::new(
+            device,
+            models,
+            "Red Quad",
+            &quad,
+            vertex_layout,
+            topology,
+            &[Instance::identity()],
+            place(-0.3, 0.4),
+            [1.0, 0.2, 0.2, 0.5],
+        ),
+        SceneObject::new(
+            device,
+            models,
+            "Blue Quad",
+            &quad,
+            vertex_layout,
+            topology,
+            &[Instance::identity()],
+            place(0.3, -0.4),
+            [0.2, 0.4, 1.0, 0.5],
+        ),
+    ]
+}
@@ -0,0 +1,554 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Entry points used when a shader doesn't say otherwise.
+const DEFAULT_VS_ENTRY: &str = "vs_main";
+const DEFAULT_FS_ENTRY: &str = "fs_main";
+
+/// Entry points for sample-rate shading, which a shader can have as well as its usual ones. See
+/// shader.wgsl's.
+const PER_SAMPLE_VS_ENTRY: &str = "vs_per_sample";
+const PER_SAMPLE_FS_ENTRY: &str = "fs_per_sample";
+
+/// Files built into the binary which any shader can `#include` by name, even ones loaded from
+/// --shader-dir. A file with the same name next to the shader wins.
+const BUILT_IN_INCLUDES: &[(&str, &str)] = &[("common.wgsl", include_str!("common.wgsl"))];
+
+/// A compiled shader module, plus the names of the functions the pipeline should call.
+pub struct Shader {
+    /// What to call the shader when showing it to the user, e.g. in the title bar. Defaults to
+    /// the filename without its extension.
+    pub name: String,
+    pub module: wgpu::ShaderModule,
+    pub vs_entry: String,
+    pub fs_entry: String,
+    /// The shader's `vs_per_sample` and `fs_per_sample`, if it has both. Used instead of the
+    /// usual entry points while sample-rate shading is on.
+    pub per_sample_entries: Option<(String, String)>,
+}
+
+impl Shader {
+    /// Compile WGSL source. `name` is only used for labels and error messages, e.g. a filename.
+    ///
+    /// The entry points default to `vs_main` and `fs_main`. Shaders which use other names can say
+    /// so in comments at the very top of the file, before any code:
+    ///
+    /// ```wgsl
+    /// // vs_entry: vertex
+    /// // fs_entry: fragment
+    /// ```
+    ///
+    /// Lines like `#include "common.wgsl"` are replaced with that file's contents first (see
+    /// `resolve_includes`), looking in `dir` and then `BUILT_IN_INCLUDES`. Then placeholders like
+    /// `{{MAX_LIGHTS}}` are replaced with their values from `constants` (see
+    /// `substitute_constants`). Line numbers in errors count lines of the combined source.
+    ///
+    /// If `dump_info` is set, also validates the shader with naga and logs what it found.
+    pub async fn from_wgsl(
+        device: &wgpu::Device,
+        name: &str,
+        source: &str,
+        dir: Option<&Path>,
+        constants: &HashMap<String, String>,
+        dump_info: bool,
+    ) -> Result<Self, ShaderError> {
+        let (vs_entry, fs_entry) = entry_points_from_front_matter(source);
+        let source = &resolve_includes(name, source, dir)?;
+        let source = &substitute_constants(name, source, constants)?;
+
+        // wgpu would panic on a missing entry point when building the pipeline, with an error
+        // which doesn't say which file was wrong. Check up front instead.
+        let parsed = naga::front::wgsl::parse_str(source).map_err(|e| ShaderError::Parse {
+            file: name.to_owned(),
+            message: e.emit_to_string_with_path(source, name),
+        })?;
+        if dump_info {
+            dump_shader_info(name, source, &parsed)?;
+        }
+        let has_entry = |stage, name: &str| {
+            parsed
+                .entry_points
+                .iter()
+                .any(|ep| ep.stage == stage && ep.name == name)
+        };
+        for (stage, entry) in [
+            (naga::ShaderStage::Vertex, &vs_entry),
+            (naga::ShaderStage::Fragment, &fs_entry),
+        ] {
+            if !has_entry(stage, entry) {
+                return Err(ShaderError::MissingEntryPoint {
+                    file: name.to_owned(),
+                    stage,
+                    entry: entry.clone(),
+                });
+            }
+        }
+
+        let per_sample_entries = (has_entry(naga::ShaderStage::Vertex, PER_SAMPLE_VS_ENTRY)
+            && has_entry(naga::ShaderStage::Fragment, PER_SAMPLE_FS_ENTRY))
+        .then(|| {
+            (
+                PER_SAMPLE_VS_ENTRY.to_owned(),
+                PER_SAMPLE_FS_ENTRY.to_owned(),
+            )
+        });
+
+        // Catch validation errors instead of letting wgpu's default handler panic.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(name),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        if let Some(e) = device.pop_error_scope().await {
+            return Err(ShaderError::Invalid {
+                file: name.to_owned(),
+                message: e.to_string(),
+            });
+        }
+
+        let short_name = Path::new(name).file_stem().map_or_else(
+            || name.to_owned(),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+        Ok(Self {
+            name: short_name,
+            module,
+            vs_entry,
+            fs_entry,
+            per_sample_entries,
+        })
+    }
+
+    /// Read and compile a WGSL file. Its includes are relative to the directory it's in.
+    pub async fn from_file(
+        device: &wgpu::Device,
+        path: &Path,
+        constants: &HashMap<String, String>,
+        dump_info: bool,
+    ) -> Result<Self, ShaderError> {
+        let name = path.display().to_string();
+        let source = std::fs::read_to_string(path).map_err(|e| ShaderError::Read {
+            file: name.clone(),
+            message: e.to_string(),
+        })?;
+        Self::from_wgsl(device, &name, &source, path.parent(), constants, dump_info).await
+    }
+
+    /// Load every `.wgsl` file in `dir`, in filename order. Files which fail to load are logged
+    /// and skipped, so one broken shader doesn't stop the others.
+    pub async fn load_dir(
+        device: &wgpu::Device,
+        dir: &Path,
+        constants: &HashMap<String, String>,
+        dump_info: bool,
+    ) -> Vec<Self> {
+        let mut paths: Vec<_> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "wgsl"))
+                .collect(),
+            Err(e) => {
+                log::error!("Couldn't read shader directory {}: {e}", dir.display());
+                return Vec::new();
+            }
+        };
+        paths.sort();
+
+        let mut shaders = Vec::with_capacity(paths.len());
+        for path in paths {
+            match Self::from_file(device, &path, constants, dump_info).await {
+                Ok(shader) => {
+                    log::info!("Loaded shader {}", path.display());
+                    shaders.push(shader);
+                }
+                Err(e) => log::error!("Skipping shader: {e}"),
+            }
+        }
+        shaders
+    }
+}
+
+/// Log the size and entry points of a parsed shader, then validate it with naga. wgpu validates it
+/// too, but naga's own errors point at the exact line and column.
+fn dump_shader_info(name: &str, source: &str, module: &naga::Module) -> Result<(), ShaderError> {
+    log::info!(
+        "{name}: {} bytes, {} lines of WGSL",
+        source.len(),
+        source.lines().count()
+    );
+    for entry_point in &module.entry_points {
+        log::info!(
+            "{name}: {:?} entry point {:?}",
+            entry_point.stage,
+            entry_point.name
+        );
+    }
+    // Capabilities::all() because this only checks the shader itself. Whether the device
+    // supports what it uses is wgpu's problem.
+    let mut validator = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    );
+    match validator.validate(module) {
+        Ok(_) => {
+            log::info!("{name}: passed naga validation");
+            Ok(())
+        }
+        Err(e) => Err(ShaderError::Validation {
+            file: name.to_owned(),
+            message: e.emit_to_string_with_path(source, name),
+        }),
+    }
+}
+
+/// Replace every `#include "file"` line in `source` with the contents of that file, which can
+/// have includes of its own. Paths are relative to `dir`, the including file's directory, and
+/// fall back to `BUILT_IN_INCLUDES`. Built-in files can only include other built-in files.
+pub fn resolve_includes(
+    name: &str,
+    source: &str,
+    dir: Option<&Path>,
+) -> Result<String, ShaderError> {
+    let mut combined = String::with_capacity(source.len());
+    let mut stack = vec![name.to_owned()];
+    expand_includes(name, source, dir, &mut stack, &mut combined)?;
+    Ok(combined)
+}
+
+/// `resolve_includes`, for one file. `stack` is the chain of files being included, starting
+/// with the shader itself, to catch files which end up including themselves.
+fn expand_includes(
+    name: &str,
+    source: &str,
+    dir: Option<&Path>,
+    stack: &mut Vec<String>,
+    combined: &mut String,
+) -> Result<(), ShaderError> {
+    for line in source.lines() {
+        let Some(rest) = line.trim().strip_prefix("#include") else {
+            combined.push_str(line);
+            combined.push('\n');
+            continue;
+        };
+        let include_error = |include: &str, reason: String| ShaderError::Include {
+            file: name.to_owned(),
+            include: include.to_owned(),
+            reason,
+        };
+        let include = rest
+            .trim()
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+            .ok_or_else(|| include_error(rest.trim(), "expected #include \"file\"".to_owned()))?;
+        let (included_name, included_source, included_dir) =
+            find_include(include, dir).map_err(|reason| include_error(include, reason))?;
+        if stack.contains(&included_name) {
+            stack.push(included_name);
+            return Err(ShaderError::IncludeCycle {
+                chain: std::mem::take(stack),
+            });
+        }
+        stack.push(included_name.clone());
+        expand_includes(
+            &included_name,
+            &included_source,
+            included_dir.as_deref(),
+            stack,
+            combined,
+        )?;
+        stack.pop();
+    }
+    Ok(())
+}
+
+/// Find an included file, returning its name (unique to that file, for spotting cycles), its
+/// source, and the directory its own includes are relative to.
+fn find_include(
+    include: &str,
+    dir: Option<&Path>,
+) -> Result<(String, String, Option<PathBuf>), String> {
+    if let Some(dir) = dir {
+        let path = dir.join(include);
+        if path.is_file() {
+            // The same file can be reached by different relative paths, so compare real paths.
+            let path = std::fs::canonicalize(&path).unwrap_or(path);
+            let source = std::fs::read_to_string(&path)
+                .map_err(|e| format!("couldn't read {}: {e}", path.display()))?;
+            let parent = path.parent().map(Path::to_path_buf);
+            return Ok((path.display().to_string(), source, parent));
+        }
+    }
+    match BUILT_IN_INCLUDES.iter().find(|(name, _)| *name == include) {
+        Some((name, source)) => Ok(((*name).to_owned(), (*source).to_owned(), None)),
+        None => Err(match dir {
+            Some(dir) => format!("it isn't in {} or built in", dir.display()),
+            None => "it isn't built in".to_owned(),
+        }),
+    }
+}
+
+/// Replace every `{{NAME}}` in `source` with `constants["NAME"]`, e.g. `{{MAX_LIGHTS}}` with
+/// `4`, so one file can be compiled with different values baked in. The values are pasted in as
+/// they are, so they can be any WGSL, not just numbers. Spaces inside the braces are ignored.
+///
+/// Every placeholder has to have a value, otherwise the shader would fail to parse with a much
+/// less helpful error.
+pub fn substitute_constants(
+    name: &str,
+    source: &str,
+    constants: &HashMap<String, String>,
+) -> Result<String, ShaderError> {
+    let mut substituted = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        substituted.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let constant_error = |constant: &str, reason: &str| ShaderError::Constant {
+            file: name.to_owned(),
+            // Of the combined source, like naga's errors.
+            line: source[..source.len() - rest.len() + start]
+                .matches('\n')
+                .count()
+                + 1,
+            constant: constant.to_owned(),
+            reason: reason.to_owned(),
+        };
+        let Some(end) = after.find("}}").filter(|end| !after[..*end].contains('\n')) else {
+            let line_rest = after.lines().next().unwrap_or_default().trim();
+            return Err(constant_error(
+                line_rest,
+                "there's no }} to close it on the same line",
+            ));
+        };
+        let constant = after[..end].trim();
+        let value = constants.get(constant).ok_or_else(|| {
+            constant_error(constant, "it isn't defined (pass --define NAME=value)")
+        })?;
+        substituted.push_str(value);
+        rest = &after[end + 2..];
+    }
+    substituted.push_str(rest);
+    Ok(substituted)
+}
+
+/// Look for `// vs_entry: name` and `// fs_entry: name` in the comments at the top of the file.
+fn entry_points_from_front_matter(source: &str) -> (String, String) {
+    let mut vs_entry = DEFAULT_VS_ENTRY.to_owned();
+    let mut fs_entry = DEFAULT_FS_ENTRY.to_owned();
+    let front_matter = source
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("//"));
+    for line in front_matter {
+        let comment = line.trim_start_matches('/').trim();
+        if let Some((key, value)) = comment.split_once(':') {
+            match key.trim() {
+                "vs_entry" => vs_entry = value.trim().to_owned(),
+                "fs_entry" => fs_entry = value.trim().to_owned(),
+                _ => {}
+            }
+        }
+    }
+    (vs_entry, fs_entry)
+}
+
+/// Why a shader couldn't be loaded. Always says which file was at fault.
+#[derive(Debug)]
+pub enum ShaderError {
+    /// The file couldn't be read at all, e.g. because it doesn't exist.
+    Read { file: String, message: String },
+    /// Not valid WGSL syntax.
+    Parse { file: String, message: String },
+    /// The front matter (or the default) names an entry point the shader doesn't have.
+    MissingEntryPoint {
+        file: String,
+        stage: naga::ShaderStage,
+        entry: String,
+    },
+    /// Parsed, but naga's validator found a problem, e.g. a type error. Only checked with
+    /// --dump-shader-info, otherwise wgpu finds these instead (see Invalid).
+    Validation { file: String, message: String },
+    /// Parsed, but wgpu rejected it, e.g. because of a type error.
+    Invalid { file: String, message: String },
+    /// An `#include` line couldn't be resolved, e.g. because the file doesn't exist.
+    Include {
+        file: String,
+        include: String,
+        reason: String,
+    },
+    /// A file ends up including itself. The chain starts with the shader and ends with the file
+    /// included twice.
+    IncludeCycle { chain: Vec<String> },
+    /// A `{{NAME}}` placeholder couldn't be filled in, usually because there's no value for it.
+    Constant {
+        file: String,
+        line: usize,
+        constant: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { file, message } => write!(f, "couldn't read {file}: {message}"),
+            Self::Parse { file, message } => write!(f, "{file} isn't valid WGSL:\n{message}"),
+            Self::MissingEntryPoint { file, stage, entry } => write!(
+                f,
+                "{file} has no {stage:?} entry point called {entry:?} (set a different one with \
+                 a `// vs_entry: name` or `// fs_entry: name` comment at the top of the file)"
+            ),
+            Self::Validation { file, message } => write!(f, "{file} failed validation:\n{message}"),
+            Self::Invalid { file, message } => write!(f, "{file} was rejected by wgpu: {message}"),
+            Self::Include {
+                file,
+                include,
+                reason,
+            } => write!(f, "{file} can't include {include:?}: {reason}"),
+            Self::IncludeCycle { chain } => {
+                write!(f, "shader includes itself: {}", chain.join(" -> "))
+            }
+            Self::Constant {
+                file,
+                line,
+                constant,
+                reason,
+            } => write!(
+                f,
+                "{file} line {line} has a placeholder {{{{{constant}}}}} which can't be filled in: \
+                 {reason}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory of shader files for one test, named after it so tests running at the
+    /// same time don't share one.
+    fn shader_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shader-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            std::fs::write(dir.join(name), source).unwrap();
+        }
+        dir
+    }
+
+    fn compiles(source: &str) -> bool {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .is_ok()
+    }
+
+    #[test]
+    fn built_in_include_resolves_and_compiles() {
+        let source = resolve_includes("shader.wgsl", include_str!("shader.wgsl"), None).unwrap();
+        assert!(!source.lines().any(|line| line.starts_with("#include")));
+        assert!(source.contains(include_str!("common.wgsl")));
+        assert!(compiles(&source));
+    }
+
+    #[test]
+    fn include_from_the_shader_dir_resolves_and_compiles() {
+        let dir = shader_dir(
+            "resolves",
+            &[(
+                "double.wgsl",
+                "fn double(x: f32) -> f32 {\n    return x * 2.0;\n}\n",
+            )],
+        );
+        let source = resolve_includes(
+            "main.wgsl",
+            "#include \"double.wgsl\"\nfn four() -> f32 {\n    return double(2.0);\n}\n",
+            Some(&dir),
+        )
+        .unwrap();
+        assert!(source.starts_with("fn double"));
+        assert!(compiles(&source));
+    }
+
+    #[test]
+    fn include_cycle_is_detected() {
+        let dir = shader_dir(
+            "cycle",
+            &[
+                ("a.wgsl", "#include \"b.wgsl\"\n"),
+                ("b.wgsl", "#include \"a.wgsl\"\n"),
+            ],
+        );
+        match resolve_includes("main.wgsl", "#include \"a.wgsl\"\n", Some(&dir)) {
+            Err(ShaderError::IncludeCycle { chain }) => {
+                // main -> a -> b -> a, with a's full path both times.
+                assert_eq!(chain.len(), 4);
+                assert_eq!(chain[1], chain[3]);
+                assert!(chain[1].ends_with("a.wgsl"));
+            }
+            other => panic!("expected an include cycle, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn missing_include_is_an_error() {
+        let dir = shader_dir("missing", &[]);
+        match resolve_includes("main.wgsl", "#include \"nowhere.wgsl\"\n", Some(&dir)) {
+            Err(ShaderError::Include { file, include, .. }) => {
+                assert_eq!(file, "main.wgsl");
+                assert_eq!(include, "nowhere.wgsl");
+            }
+            other => panic!("expected a missing include, got {other:?}"),
+        }
+    }
+
+    fn constants(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn placeholders_are_substituted() {
+        let source = "const MAX_LIGHTS: u32 = {{MAX_LIGHTS}}u;\nconst SCALE = {{ SCALE }};\n";
+        let substituted = substitute_constants(
+            "lights.wgsl",
+            source,
+            &constants(&[("MAX_LIGHTS", "4"), ("SCALE", "vec2(1.0, 2.0)")]),
+        )
+        .unwrap();
+        assert_eq!(
+            substituted,
+            "const MAX_LIGHTS: u32 = 4u;\nconst SCALE = vec2(1.0, 2.0);\n"
+        );
+    }
+
+    #[test]
+    fn unresolved_placeholder_is_an_error() {
+        let source = "const A = {{A}};\nconst B = {{B}};\n";
+        match substitute_constants("lights.wgsl", source, &constants(&[("A", "1")])) {
+            Err(ShaderError::Constant {
+                file,
+                line,
+                constant,
+                ..
+            }) => {
+                assert_eq!(file, "lights.wgsl");
+                assert_eq!(line, 2);
+                assert_eq!(constant, "B");
+            }
+            other => panic!("expected an unresolved placeholder, got {other:?}"),
+        }
+    }
+}
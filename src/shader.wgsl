@@ -1,35 +1,123 @@
 // Vertex shader
 
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-};
+#include "common.wgsl"
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    // Integers can't be interpolated between vertices, so every fragment gets the first vertex's.
+    @location(1) @interpolate(flat) texture_layer: u32,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_position: vec3<f32>,
+    @location(4) color: vec3<f32>,
 };
 
 @vertex
 fn vs_main(
     model: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
+    return transform(model, instance);
+}
+
+// Entry points can't call each other, so the work's in here, for vs_per_sample too.
+fn transform(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let instance_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.texture_layer = instance.texture_layer;
+    out.color = model.color;
+    // Spin the model around its own centre first, then move it to where this instance goes, then
+    // see where that ends up on screen.
+    let world_position = instance_matrix * model_uniform.transform * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    // w = 0, because normals are directions, so moving the model mustn't move them. This is only
+    // right for uniform scaling, which is all the demo does.
+    out.world_normal = (instance_matrix * model_uniform.transform * vec4<f32>(model.normal, 0.0)).xyz;
     return out;
 }
 
 // Fragment shader
 
-// Corresponds to the BindGroupDescriptor in the Rust code.
-// These are 'uniforms'.
-@group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
-@group(0)@binding(1)
-var s_diffuse: sampler;
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    return shade(in);
+}
+
+fn shade(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    // The vertex colours are interpolated across each triangle, so they blend over the texture.
+    let base = textureSample(t_diffuse, s_diffuse, in.tex_coords, i32(in.texture_layer))
+        * vec4<f32>(in.color, 1.0) * model_uniform.tint;
+    // Interpolating between unit vectors makes them shorter, so normalize again.
+    let normal = normalize(in.world_normal);
+    // Blinn-Phong. Ambient light reaches everything a little, so faces turned away from the
+    // light aren't pure black.
+    let ambient = light.ambient_strength;
+    // Lambert: surfaces facing the light get all of it, ones edge-on or facing away get none.
+    // Only where the light reaches, though.
+    let in_light = shadow(in.world_position);
+    let diffuse = max(dot(normal, light.direction), 0.0) * light.intensity * in_light;
+    // Highlights where the surface would reflect the light straight at the camera, i.e. where
+    // the normal lines up with the halfway point between the light and the camera.
+    let view_direction = normalize(camera.eye_position.xyz - in.world_position);
+    let half_direction = normalize(view_direction + light.direction);
+    let specular = pow(max(dot(normal, half_direction), 0.0), light.shininess)
+        * light.specular_strength * light.intensity * in_light;
+    // The colour is premultiplied by alpha, so scaling it leaves it premultiplied. The highlight
+    // is white, and needs premultiplying too.
+    out.color = vec4<f32>(base.rgb * (ambient + diffuse) + vec3<f32>(specular * base.a), base.a);
+    out.normal = vec4<f32>(normal * 0.5 + 0.5, 1.0);
+    return out;
+}
+
+// Sample-rate shading (Z, with MSAA). The same as vs_main and fs_main, except every interpolated
+// value is worked out at each of the pixel's samples rather than at its centre, which makes the
+// fragment shader run once per sample. MSAA on its own only smooths the edges of triangles. This
+// smooths the insides too, e.g. a texture's sharp details, for the price of running the fragment
+// shader several times per pixel.
+//
+// (`centroid` instead of `sample` is the cheap relative: still once per pixel, but interpolated
+// at a point the triangle actually covers, so textures don't bleed past the edges of triangles.)
+struct PerSampleOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(perspective, sample) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) texture_layer: u32,
+    @location(2) @interpolate(perspective, sample) world_normal: vec3<f32>,
+    @location(3) @interpolate(perspective, sample) world_position: vec3<f32>,
+    @location(4) @interpolate(perspective, sample) color: vec3<f32>,
+};
+
+@vertex
+fn vs_per_sample(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> PerSampleOutput {
+    let out = transform(model, instance);
+    return PerSampleOutput(
+        out.clip_position,
+        out.tex_coords,
+        out.texture_layer,
+        out.world_normal,
+        out.world_position,
+        out.color,
+    );
+}
 
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+fn fs_per_sample(in: PerSampleOutput) -> FragmentOutput {
+    return shade(VertexOutput(
+        in.clip_position,
+        in.tex_coords,
+        in.texture_layer,
+        in.world_normal,
+        in.world_position,
+        in.color,
+    ));
 }
@@ -0,0 +1,132 @@
+use crate::{
+    scene::{ModelUniforms, SceneObject},
+    texture::Texture,
+};
+
+/// Width and height of the shadow map in texels, with --shadows. Bigger gives sharper shadow
+/// edges, but costs memory and fill rate.
+const SHADOW_MAP_SIZE: u32 = 2048;
+
+/// The shadow map is always bound, because the scene shaders expect it, so without --shadows it's
+/// this small and never drawn into, just cleared to the far plane, so nothing's in shadow.
+const DISABLED_SIZE: u32 = 1;
+
+/// Which bind groups the shadow pipeline's layout has the light and the model in. Not the same as
+/// the scene pipelines', because the shadow pass doesn't bind any textures.
+const LIGHT_GROUP: u32 = 0;
+const MODEL_GROUP: u32 = 1;
+
+/// A depth texture holding how far the light reaches in each direction, i.e. the depth of the
+/// nearest thing to the light, drawn from the light's point of view (see `Light::view_proj`).
+/// The scene shaders sample it with a comparison sampler: a fragment further from the light than
+/// what's in the map has something between it and the light, so it's in shadow.
+///
+/// Drawn in its own pass before the scene, with the opaque objects. Transparent ones don't cast
+/// shadows.
+pub struct ShadowMap {
+    /// Depth32Float, with a LessEqual comparison sampler.
+    pub texture: Texture,
+    /// Whether there's anything to draw into the map, i.e. --shadows.
+    enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    /// `vertex_buffers` and `primitive` have to match the objects', like the scene pipelines'.
+    pub fn new(
+        device: &wgpu::Device,
+        enabled: bool,
+        light_layout: &wgpu::BindGroupLayout,
+        models: &ModelUniforms,
+        vertex_buffers: &[wgpu::VertexBufferLayout],
+        primitive: wgpu::PrimitiveState,
+    ) -> Self {
+        let size = if enabled {
+            SHADOW_MAP_SIZE
+        } else {
+            DISABLED_SIZE
+        };
+        let texture = Texture::create_depth_texture(device, size, size, 1, "shadow_map");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            // LIGHT_GROUP, then MODEL_GROUP.
+            bind_group_layouts: &[light_layout, &models.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: vertex_buffers,
+            },
+            // Only depth is needed, so there's no fragment shader at all.
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                // The pentagon and the quads are one-sided, but should still cast shadows from
+                // behind.
+                cull_mode: None,
+                unclipped_depth: false,
+                ..primitive
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // Push the depths a little further from the light, more so on surfaces at a
+                // steep angle to it. Otherwise surfaces shadow themselves in stripes ("shadow
+                // acne"), because the map's texels don't line up with the screen's pixels.
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Self {
+            texture,
+            enabled,
+            pipeline,
+        }
+    }
+
+    /// Draw `objects` into the shadow map, as seen from the light. Without --shadows, just clear
+    /// it.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        light_bind_group: &wgpu::BindGroup,
+        models: &ModelUniforms,
+        objects: &[SceneObject],
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    // The far plane, i.e. nothing in the way of the light.
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        if !self.enabled {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(LIGHT_GROUP, light_bind_group, &[]);
+        for object in objects {
+            object.draw(&mut pass, models, MODEL_GROUP);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+// Shadow pass: draws the scene's depth as seen from the light, into the shadow map. There's no
+// fragment shader, because only depth is needed.
+
+// Same as in common.wgsl, apart from the group.
+struct LightUniform {
+    view_proj: mat4x4<f32>,
+    direction: vec3<f32>,
+    intensity: f32,
+    ambient_strength: f32,
+    specular_strength: f32,
+    shininess: f32,
+};
+@group(0) @binding(0)
+var<uniform> light: LightUniform;
+
+// Same as in common.wgsl, apart from the group.
+struct ModelUniform {
+    transform: mat4x4<f32>,
+    tint: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> model_uniform: ModelUniform;
+
+// Only the position. The other vertex attributes are in the buffers too, but aren't needed.
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+// Same as in common.wgsl.
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+};
+
+// The same transform as shader.wgsl, except with the light's view instead of the camera's.
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let instance_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    return light.view_proj * instance_matrix * model_uniform.transform
+        * vec4<f32>(model.position, 1.0);
+}
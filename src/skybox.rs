@@ -0,0 +1,205 @@
+use std::path::Path;
+
+use anyhow::Result;
+use cgmath::{Matrix4, Point3, SquareMatrix};
+
+use crate::{camera::Camera, texture::Texture, uniform::Uniform};
+
+/// The cube's faces, in the order cube textures want them. --skybox-dir looks for files with these
+/// names.
+const FACE_NAMES: [&str; 6] = ["px.png", "nx.png", "py.png", "ny.png", "pz.png", "nz.png"];
+
+/// Small test faces: sky above, ground below, and a coloured square on each side so it's easy to
+/// tell which way is which. +X is red, -X cyan, +Z blue and -Z (straight ahead) yellow.
+const BUILT_IN_FACES: [(&[u8], &str); 6] = [
+    (include_bytes!("../assets/skybox/px.png"), "px.png"),
+    (include_bytes!("../assets/skybox/nx.png"), "nx.png"),
+    (include_bytes!("../assets/skybox/py.png"), "py.png"),
+    (include_bytes!("../assets/skybox/ny.png"), "ny.png"),
+    (include_bytes!("../assets/skybox/pz.png"), "pz.png"),
+    (include_bytes!("../assets/skybox/nz.png"), "nz.png"),
+];
+
+/// Corresponds to SkyUniform in skybox.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+    /// Moves points from clip space back into world space, as if the eye were at the origin.
+    inv_view_proj: [[f32; 4]; 4],
+}
+
+impl From<&Camera> for SkyUniform {
+    fn from(camera: &Camera) -> Self {
+        // The sky is infinitely far away, so moving the camera never gets it any closer. Only
+        // which way the camera faces matters, so leave the eye at the origin.
+        let view = Matrix4::look_to_rh(Point3::new(0.0, 0.0, 0.0), camera.forward(), camera.up);
+        let view_proj = camera.build_projection_matrix() * view;
+        Self {
+            // Only a degenerate camera (e.g. zero aspect ratio) has no inverse.
+            inv_view_proj: view_proj.invert().unwrap_or(Matrix4::identity()).into(),
+        }
+    }
+}
+
+/// An environment cubemap drawn behind the scene. Like `Background`, it's a fullscreen triangle
+/// drawn first in the scene pass, but each pixel looks up the cubemap in the direction the camera
+/// sees through it, so the sky turns with the camera. Y toggles it.
+pub struct Skybox {
+    pipeline: wgpu::RenderPipeline,
+    sky: Uniform<SkyUniform>,
+    texture_bind_group: wgpu::BindGroup,
+    // Never read, but the bind group refers to it, so keep it around.
+    #[allow(dead_code)]
+    texture: Texture,
+    /// If false, nothing is drawn, and the background shows instead.
+    pub enabled: bool,
+}
+
+impl Skybox {
+    /// `faces` are in the same order as FACE_NAMES. `srgb` is the same as for
+    /// `Texture::array_from_images`. The pipeline is drawn in the scene pass, so it has to match
+    /// the scene's format, sample count and targets.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: &[image::DynamicImage],
+        srgb: bool,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+        normals_target: bool,
+    ) -> Result<Self> {
+        let texture = Texture::cube_from_images(device, queue, faces, "skybox_texture", srgb)?;
+        let sky = Uniform::new(
+            device,
+            SkyUniform::from(&Camera::new(1.0)),
+            wgpu::ShaderStages::FRAGMENT,
+            "sky_uniform",
+        );
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        // Looked up by direction, rather than by texture coordinates.
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("skybox_bind_group_layout"),
+        });
+        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("skybox_bind_group"),
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Skybox Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("skybox.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[&sky.layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &crate::normals::scene_targets(
+                    target_format,
+                    wgpu::BlendState::REPLACE,
+                    normals_target,
+                ),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // Same as the background: behind everything, so it mustn't write depth.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+        Ok(Self {
+            pipeline,
+            sky,
+            texture_bind_group,
+            texture,
+            enabled: false,
+        })
+    }
+
+    /// Point the sky the same way as `camera`. Call this whenever the camera turns.
+    pub fn set_camera(&mut self, queue: &wgpu::Queue, camera: &Camera) {
+        self.sky.set(SkyUniform::from(camera));
+        self.sky.flush(queue);
+    }
+
+    /// Draw the sky, if it's enabled. It covers the whole viewport, so call this before drawing
+    /// anything else in the pass.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.enabled {
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, self.sky.bind_group(), &[]);
+            render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+/// The built-in test faces, or the ones in `dir` (see FACE_NAMES) if given. Faces which can't be
+/// read or decoded are logged and replaced with a checkerboard, like the scene's textures.
+pub fn load_faces(dir: Option<&Path>) -> Vec<image::DynamicImage> {
+    let Some(dir) = dir else {
+        return crate::texture::decode_images(&BUILT_IN_FACES);
+    };
+    let files: Vec<_> = FACE_NAMES
+        .iter()
+        .map(|name| {
+            let path = dir.join(name);
+            let bytes = std::fs::read(&path).unwrap_or_else(|e| {
+                log::warn!("Couldn't read {} ({e})", path.display());
+                Vec::new()
+            });
+            (bytes, path.display().to_string())
+        })
+        .collect();
+    let files: Vec<_> = files
+        .iter()
+        .map(|(bytes, name)| (bytes.as_slice(), name.as_str()))
+        .collect();
+    crate::texture::decode_images(&files)
+}
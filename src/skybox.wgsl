@@ -0,0 +1,49 @@
+// An environment cubemap, drawn behind the scene. Each pixel works out which way the camera is
+// looking through it, and shows whatever the cubemap has in that direction.
+
+// Corresponds to SkyUniform in the Rust code.
+struct SkyUniform {
+    inv_view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> sky: SkyUniform;
+
+@group(1) @binding(0)
+var t_sky: texture_cube<f32>;
+@group(1) @binding(1)
+var s_sky: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // The pixel's x and y in clip space, from -1 to 1.
+    @location(0) clip_xy: vec2<f32>,
+};
+
+// Same trick as background.wgsl: one big triangle covering the whole screen, no vertex buffer.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(xy * 2.0 - 1.0, 1.0, 1.0);
+    out.clip_xy = xy * 2.0 - 1.0;
+    return out;
+}
+
+// The normal is for --normals-target. Like the background, the sky isn't a surface.
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    // Any depth between the near and far planes will do: the eye is at the origin, so every
+    // point under this pixel is in the same direction from it.
+    let world = sky.inv_view_proj * vec4<f32>(in.clip_xy, 0.5, 1.0);
+    let direction = world.xyz / world.w;
+    var out: FragmentOutput;
+    out.color = textureSample(t_sky, s_sky, direction);
+    // Same as the normals target's clear value, i.e. a zero normal.
+    out.normal = vec4<f32>(0.5, 0.5, 0.5, 0.0);
+    return out;
+}
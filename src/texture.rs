@@ -1,31 +1,72 @@
 use anyhow::Result;
 use image::GenericImageView;
 
+/// Size of the checkerboard used in place of an image that couldn't be decoded, and of its
+/// squares, in pixels.
+const FALLBACK_SIZE: u32 = 256;
+const FALLBACK_CHECK_SIZE: u32 = 32;
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
+    // The default, clamp-to-edge sampler. The scene makes its own samplers so it can cycle
+    // through address modes, but the skybox uses this one.
     pub sampler: wgpu::Sampler,
 }
 
 impl Texture {
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    // The scene uses `array_from_images` now, but these are still the way to load a single image.
+    #[allow(dead_code)]
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: &[u8],
         label: &str,
+        premultiply: bool,
     ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, Some(label))
+        let img = match image::load_from_memory(bytes) {
+            Ok(img) => img,
+            Err(e) => {
+                log::warn!("Couldn't decode {label} ({e}), using a checkerboard instead");
+                return Self::checkerboard(device, queue, FALLBACK_SIZE, FALLBACK_CHECK_SIZE);
+            }
+        };
+        Self::from_image(device, queue, &img, Some(label), premultiply)
     }
 
+    /// A `size` x `size` magenta and black checkerboard, with squares `check_size` pixels across.
+    /// Hard to miss, so it's used in place of images which couldn't be loaded.
+    #[allow(dead_code)]
+    pub fn checkerboard(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: u32,
+        check_size: u32,
+    ) -> Result<Self> {
+        let img = image::DynamicImage::ImageRgba8(checkerboard_image(size, check_size));
+        // It's fully opaque, so premultiplying wouldn't change anything.
+        Self::from_image(device, queue, &img, Some("checkerboard"), false)
+    }
+
+    /// If `premultiply` is true, the colour channels get multiplied by alpha before uploading,
+    /// for drawing with `BlendState::PREMULTIPLIED_ALPHA_BLENDING`.
+    #[allow(dead_code)]
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
+        premultiply: bool,
     ) -> Result<Self> {
-        let rgba = img.to_rgba8();
+        let mut rgba = img.to_rgba8();
+        if premultiply {
+            premultiply_alpha(&mut rgba, true);
+        }
         let (width, height) = img.dimensions();
+        check_size(device, width, height, label.unwrap_or("texture"))?;
+        let mip_level_count = mip_level_count(width, height);
 
         let size = wgpu::Extent3d {
             width,
@@ -35,7 +76,7 @@ impl Texture {
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             // Most images are stored using sRGB so we need to reflect that here.
@@ -53,39 +94,499 @@ impl Texture {
             view_formats: &[],
         });
 
-        queue.write_texture(
-            // Where should wgpu copy the data to?
-            wgpu::ImageCopyTexture {
-                aspect: wgpu::TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            // Data to copy
-            &rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * width), // 4 for (R, G, B and A)?
-                rows_per_image: std::num::NonZeroU32::new(height),
-            },
+        write_mips(queue, &texture, &rgba, 0, mip_level_count);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = create_sampler(device, wgpu::AddressMode::ClampToEdge, true);
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// Put several images into the layers of one array texture. Every layer of an array texture
+    /// is the same size, so images which don't match the first one get resized to fit.
+    ///
+    /// `srgb` should say whether the surface is sRGB. If it is, sampling converts the texels to
+    /// linear and the surface converts them back. If it isn't, nothing would convert them back, so
+    /// the texels are left as they are, otherwise everything comes out too dark.
+    ///
+    /// `premultiply` is the same as for `from_image`.
+    pub fn array_from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[image::DynamicImage],
+        label: &str,
+        srgb: bool,
+        premultiply: bool,
+    ) -> Result<Self> {
+        let Some(first) = images.first() else {
+            anyhow::bail!("{label} has no layers");
+        };
+        let (width, height) = first.dimensions();
+        check_size(device, width, height, label)?;
+        let mip_level_count = mip_level_count(width, height);
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: images.len() as u32,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
             size,
+            mip_level_count,
+            sample_count: 1,
+            // Still 2D. The layers are stacked up in depth_or_array_layers.
+            dimension: wgpu::TextureDimension::D2,
+            format: if srgb {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            },
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, img) in images.iter().enumerate() {
+            let mut rgba = if img.dimensions() == (width, height) {
+                img.to_rgba8()
+            } else {
+                img.resize_exact(width, height, image::imageops::FilterType::Triangle)
+                    .to_rgba8()
+            };
+            if premultiply {
+                premultiply_alpha(&mut rgba, srgb);
+            }
+            write_mips(queue, &texture, &rgba, layer as u32, mip_level_count);
+        }
+
+        // Shaders have to see it as an array, otherwise they'd only get one layer.
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = create_sampler(device, wgpu::AddressMode::ClampToEdge, true);
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// Make a cube texture from six square faces, in the order +X, -X, +Y, -Y, +Z, -Z. Sampling it
+    /// takes a direction instead of texture coordinates, and returns whatever's that way, so it's
+    /// good for things which surround the scene, like the sky.
+    ///
+    /// A cube texture is stored as a 2D array with six layers. Only the view is different.
+    pub fn cube_from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: &[image::DynamicImage],
+        label: &str,
+        srgb: bool,
+    ) -> Result<Self> {
+        anyhow::ensure!(
+            faces.len() == 6,
+            "{label} needs 6 faces, but it has {}",
+            faces.len()
         );
+        let (width, height) = faces[0].dimensions();
+        anyhow::ensure!(
+            width == height,
+            "{label}'s faces have to be square, but they're {width}x{height}"
+        );
+        // The faces are fully opaque, so premultiplying wouldn't change anything.
+        let array = Self::array_from_images(device, queue, faces, label, srgb, false)?;
+        let view = array.texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        Ok(Self { view, ..array })
+    }
 
+    /// Depth textures store how far away the closest thing drawn so far is, for every pixel.
+    /// Things behind it get skipped. It has to be the same size as the colour texture, and have the
+    /// same sample count, so recreate it whenever those change.
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            // Rendered to by the depth test. TEXTURE_BINDING so it could be read back in a shader.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Only useful if a shader ever samples the depth texture, e.g. for shadows.
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
             mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
             ..Default::default()
         });
-
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
+        }
+    }
+}
+
+/// How many mip levels a full chain has, down to 1x1. Each level is half the size of the one
+/// before, rounded down, so this works for sizes which aren't powers of two too, e.g. 300x200 goes
+/// 300x200, 150x100, 75x50, 37x25, 18x12, 9x6, 4x3, 2x1, 1x1: 9 levels.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// The size of mip level `mip_level` of a `width` x `height` texture. Never 0, even for the last
+/// levels of a non-square texture.
+fn mip_size(width: u32, height: u32, mip_level: u32) -> (u32, u32) {
+    ((width >> mip_level).max(1), (height >> mip_level).max(1))
+}
+
+/// How many bytes `write_mips` says each row of a `width` pixel wide level takes: 4 per pixel, for
+/// R, G, B and A, with no padding (see `write_mips`).
+fn unpadded_bytes_per_row(width: u32) -> u32 {
+    4 * width
+}
+
+/// Textures can't be empty, or bigger than the device allows.
+fn check_size(device: &wgpu::Device, width: u32, height: u32, label: &str) -> Result<()> {
+    let max = device.limits().max_texture_dimension_2d;
+    anyhow::ensure!(
+        width > 0 && height > 0,
+        "{label} is empty ({width}x{height})"
+    );
+    anyhow::ensure!(
+        width <= max && height <= max,
+        "{label} is {width}x{height}, but this device's textures can be at most {max}x{max}"
+    );
+    Ok(())
+}
+
+/// Upload `rgba` to mip level 0 of `layer`, then shrink it for each level after that.
+///
+/// Rows are tightly packed, 4 bytes per pixel, even when that's not a multiple of 256 (e.g. 17
+/// pixels is 68 bytes). Only buffer-to-texture copies need rows padded to
+/// `COPY_BYTES_PER_ROW_ALIGNMENT`. `write_texture` does any padding itself, so there's no need
+/// for a padded staging copy.
+fn write_mips(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    rgba: &image::RgbaImage,
+    layer: u32,
+    mip_level_count: u32,
+) {
+    let (width, height) = rgba.dimensions();
+    for mip_level in 0..mip_level_count {
+        let (mip_width, mip_height) = mip_size(width, height, mip_level);
+        let resized;
+        let pixels = if mip_level == 0 {
+            rgba
+        } else {
+            resized = image::imageops::resize(
+                rgba,
+                mip_width,
+                mip_height,
+                image::imageops::FilterType::Triangle,
+            );
+            &resized
+        };
+        queue.write_texture(
+            // Where should wgpu copy the data to?
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level,
+                // The z coordinate picks the layer.
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer,
+                },
+            },
+            // Data to copy
+            pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(unpadded_bytes_per_row(mip_width)),
+                rows_per_image: std::num::NonZeroU32::new(mip_height),
+            },
+            wgpu::Extent3d {
+                width: mip_width,
+                height: mip_height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// Decode a list of (file contents, filename) pairs, e.g. from `include_bytes!`. Anything which
+/// can't be decoded is logged and replaced with a checkerboard, so it's obvious on screen.
+pub fn decode_images(files: &[(&[u8], &str)]) -> Vec<image::DynamicImage> {
+    files
+        .iter()
+        .map(|(bytes, name)| {
+            image::load_from_memory(bytes).unwrap_or_else(|e| {
+                log::warn!("Couldn't decode {name} ({e}), using a checkerboard instead");
+                image::DynamicImage::ImageRgba8(checkerboard_image(
+                    FALLBACK_SIZE,
+                    FALLBACK_CHECK_SIZE,
+                ))
+            })
         })
+        .collect()
+}
+
+/// Squares alternate between magenta and black, starting with magenta in the top-left corner.
+pub fn checkerboard_image(size: u32, check_size: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(size, size, |x, y| checkerboard_pixel(x, y, check_size))
+}
+
+/// The colour of `checkerboard_image`'s pixel at `x`, `y`.
+fn checkerboard_pixel(x: u32, y: u32, check_size: u32) -> image::Rgba<u8> {
+    const MAGENTA: image::Rgba<u8> = image::Rgba([255, 0, 255, 255]);
+    const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+    // A check size of 0 would divide by zero.
+    let check_size = check_size.max(1);
+    if (x / check_size + y / check_size).is_multiple_of(2) {
+        MAGENTA
+    } else {
+        BLACK
+    }
+}
+
+/// Multiply every pixel's colour by its alpha. Filtering and blending straight (not premultiplied)
+/// alpha mixes in the colour of fully transparent texels, which is usually black, so see-through
+/// edges get dark halos. Premultiplied texels have no colour where they're transparent, so
+/// there's nothing to mix in.
+///
+/// If `srgb` is true the texture is sRGB, so the multiplication happens in linear space, which is
+/// where the GPU will blend it.
+pub fn premultiply_alpha(rgba: &mut image::RgbaImage, srgb: bool) {
+    for pixel in rgba.pixels_mut() {
+        pixel.0 = premultiply_pixel(pixel.0, srgb);
+    }
+}
+
+/// One pixel of `premultiply_alpha`.
+fn premultiply_pixel([r, g, b, a]: [u8; 4], srgb: bool) -> [u8; 4] {
+    if a == u8::MAX {
+        return [r, g, b, a];
+    }
+    let alpha = a as f32 / 255.0;
+    let rgb = [r, g, b].map(|c| c as f32 / 255.0);
+    let premultiplied = if srgb {
+        crate::color::linear_to_srgb(crate::color::srgb_to_linear(rgb).map(|c| c * alpha))
+    } else {
+        rgb.map(|c| c * alpha)
+    };
+    let [r, g, b] = premultiplied.map(|c| (c * 255.0).round() as u8);
+    [r, g, b, a]
+}
+
+/// The address mode says what to do with texture coordinates outside 0..1: clamp them, repeat
+/// the texture (tiling it), or repeat it mirrored.
+/// Samplers are immutable, so each address mode needs its own sampler.
+///
+/// `filtering` should be false if the sampler is bound as `SamplerBindingType::NonFiltering` (see
+/// `sample_binding_types`), because wgpu won't bind a sampler with linear filters there.
+pub fn create_sampler(
+    device: &wgpu::Device,
+    address_mode: wgpu::AddressMode,
+    filtering: bool,
+) -> wgpu::Sampler {
+    let linear = if filtering {
+        wgpu::FilterMode::Linear
+    } else {
+        wgpu::FilterMode::Nearest
+    };
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        address_mode_w: address_mode,
+        mag_filter: linear,
+        min_filter: wgpu::FilterMode::Nearest,
+        // Blend between the two nearest mip levels, so there's no visible seam where one
+        // switches to the next.
+        mipmap_filter: linear,
+        ..Default::default()
+    })
+}
+
+/// How a texture of `format` has to be declared in a bind group layout, and how its sampler has
+/// to be, given what the adapter supports for that format (from
+/// `adapter.get_texture_format_features`). The two have to agree, or wgpu rejects the bind group.
+///
+/// Filtering is only allowed on formats the adapter can filter. Some can't be on some adapters,
+/// e.g. `Rgba32Float`, and integer and depth formats never can. For those, this logs a warning
+/// and picks `NonFiltering`, which needs a sampler from `create_sampler` with `filtering` off.
+pub fn sample_binding_types(
+    label: &str,
+    format: wgpu::TextureFormat,
+    features: wgpu::TextureFormatFeatures,
+) -> (wgpu::TextureSampleType, wgpu::SamplerBindingType) {
+    let filterable = features
+        .flags
+        .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE);
+    match format.describe().sample_type {
+        wgpu::TextureSampleType::Float { .. } if filterable => (
+            wgpu::TextureSampleType::Float { filterable: true },
+            wgpu::SamplerBindingType::Filtering,
+        ),
+        wgpu::TextureSampleType::Float { .. } => {
+            log::warn!(
+                "This adapter can't filter {}, so {label} will be sampled without filtering",
+                crate::draw::format_name(format)
+            );
+            (
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::SamplerBindingType::NonFiltering,
+            )
+        }
+        // Shaders have to read these with matching types (e.g. texture_2d<u32>), not f32.
+        sample_type => {
+            log::warn!(
+                "{} can't be filtered, so {label} will be sampled without filtering",
+                crate::draw::format_name(format)
+            );
+            (sample_type, wgpu::SamplerBindingType::NonFiltering)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn premultiplies_a_half_transparent_pixel() {
+        // 128/255 alpha halves each channel, near enough.
+        assert_eq!(
+            premultiply_pixel([255, 100, 0, 128], false),
+            [128, 50, 0, 128]
+        );
+        // In linear space, white at half alpha is about 0.5, which is about 188 in sRGB.
+        assert_eq!(
+            premultiply_pixel([255, 255, 255, 128], true),
+            [188, 188, 188, 128]
+        );
+    }
+
+    #[test]
+    fn premultiplying_leaves_opaque_pixels_and_blanks_transparent_ones() {
+        assert_eq!(
+            premultiply_pixel([12, 34, 56, 255], true),
+            [12, 34, 56, 255]
+        );
+        assert_eq!(premultiply_pixel([12, 34, 56, 0], true), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn checkerboard_alternates_colours() {
+        let magenta = image::Rgba([255, 0, 255, 255]);
+        let black = image::Rgba([0, 0, 0, 255]);
+        let img = checkerboard_image(8, 2);
+        for (x, y, &pixel) in img.enumerate_pixels() {
+            let expected = if (x / 2 + y / 2) % 2 == 0 {
+                magenta
+            } else {
+                black
+            };
+            assert_eq!(pixel, expected, "pixel {x}, {y}");
+        }
+        // Each square is 2 pixels across, so these are all in the top-left square, and the next
+        // ones along and down are the other colour.
+        assert_eq!(checkerboard_pixel(1, 1, 2), magenta);
+        assert_eq!(checkerboard_pixel(2, 0, 2), black);
+        assert_eq!(checkerboard_pixel(0, 2, 2), black);
+        assert_eq!(checkerboard_pixel(2, 2, 2), magenta);
+    }
+
+    #[test]
+    fn checkerboard_with_no_check_size_alternates_every_pixel() {
+        assert_ne!(checkerboard_pixel(0, 0, 0), checkerboard_pixel(1, 0, 0));
+    }
+
+    #[test]
+    fn mip_chain_for_odd_sizes() {
+        // 33 (the bigger side) halves to 16, 8, 4, 2, 1.
+        assert_eq!(mip_level_count(17, 33), 6);
+        assert_eq!(mip_size(17, 33, 1), (8, 16));
+        // The narrow side bottoms out at 1 before the tall side does.
+        assert_eq!(mip_size(17, 33, 5), (1, 1));
+        assert_eq!(mip_size(17, 33, 4), (1, 2));
+
+        assert_eq!(mip_level_count(300, 200), 9);
+        assert_eq!(mip_size(300, 200, 3), (37, 25));
+        assert_eq!(mip_size(300, 200, 7), (2, 1));
+        assert_eq!(mip_size(300, 200, 8), (1, 1));
+    }
+
+    #[test]
+    fn rows_are_not_padded_to_the_copy_alignment() {
+        // write_texture pads rows itself, so neither of these is rounded up to 256.
+        assert_eq!(unpadded_bytes_per_row(17), 68);
+        assert_eq!(unpadded_bytes_per_row(300), 1200);
+        assert_ne!(68 % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT, 0);
+    }
+
+    fn features(flags: wgpu::TextureFormatFeatureFlags) -> wgpu::TextureFormatFeatures {
+        wgpu::TextureFormatFeatures {
+            allowed_usages: wgpu::TextureUsages::TEXTURE_BINDING,
+            flags,
+        }
+    }
+
+    #[test]
+    fn unfilterable_rgba32float_samples_without_filtering() {
+        assert_eq!(
+            sample_binding_types(
+                "test",
+                wgpu::TextureFormat::Rgba32Float,
+                features(wgpu::TextureFormatFeatureFlags::empty()),
+            ),
+            (
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::SamplerBindingType::NonFiltering,
+            )
+        );
+    }
+
+    #[test]
+    fn filterable_rgba32float_samples_with_filtering() {
+        assert_eq!(
+            sample_binding_types(
+                "test",
+                wgpu::TextureFormat::Rgba32Float,
+                features(wgpu::TextureFormatFeatureFlags::FILTERABLE),
+            ),
+            (
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::SamplerBindingType::Filtering,
+            )
+        );
     }
 }
@@ -0,0 +1,63 @@
+// Fullscreen resolve pass: reads the HDR scene texture and squashes it into the 0..1 range
+// the surface can display, then optionally filters the colours, e.g. to simulate colour
+// blindness.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// No vertex buffer! Vertex indices 0, 1, 2 become one big triangle which covers the whole screen:
+// (-1, -1), (3, -1) and (-1, 3). The bits outside the screen get clipped.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let xy = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(xy * 2.0 - 1.0, 0.0, 1.0);
+    // Texture coordinates have y pointing down, but clip space has y pointing up.
+    out.uv = vec2<f32>(xy.x, 1.0 - xy.y);
+    return out;
+}
+
+@group(0) @binding(0)
+var t_hdr: texture_2d<f32>;
+@group(0) @binding(1)
+var s_hdr: sampler;
+
+// Corresponds to TonemapUniform in the Rust code.
+struct TonemapUniform {
+    // Multiplies every linear colour after tonemapping. The identity matrix without a filter.
+    color_filter: mat3x3<f32>,
+    // 0 is Reinhard, 1 is ACES.
+    mode: u32,
+};
+@group(1) @binding(0)
+var<uniform> tonemap: TonemapUniform;
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (color + vec3<f32>(1.0));
+}
+
+// Krzysztof Narkowicz's curve fit of the ACES filmic tonemapper.
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let hdr = textureSample(t_hdr, s_hdr, in.uv);
+    var sdr: vec3<f32>;
+    if tonemap.mode == 1u {
+        sdr = aces(hdr.rgb);
+    } else {
+        sdr = reinhard(hdr.rgb);
+    }
+    // The matrices can push colours slightly outside 0..1.
+    let filtered = clamp(tonemap.color_filter * sdr, vec3<f32>(0.0), vec3<f32>(1.0));
+    return vec4<f32>(filtered, hdr.a);
+}
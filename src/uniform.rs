@@ -0,0 +1,176 @@
+use wgpu::util::DeviceExt;
+
+/// A value that lives in a GPU uniform buffer, plus the bind group that exposes it to shaders.
+/// Each uniform gets its own bind group with a single entry at binding 0.
+///
+/// `set` only changes the copy on the CPU, and marks it dirty if it's different. `flush` copies it
+/// to the GPU, but only if it's dirty, so uniforms which haven't changed cost nothing.
+///
+/// Optionally there's a ring of several buffers, each with its own bind group, and each upload
+/// writes to the next one. Then a write never lands in the buffer the GPU might still be reading
+/// for the previous frame, so the driver never has to wait for it (or copy it) first. Whether
+/// that's a win depends on the backend, so it's off by default. Try it with --uniform-ring and
+/// compare the --bench-frames numbers.
+pub struct Uniform<T> {
+    value: T,
+    /// Always at least one. The ring, if there's more than one.
+    buffers: Vec<wgpu::Buffer>,
+    /// One per buffer, in the same order.
+    bind_groups: Vec<wgpu::BindGroup>,
+    /// Which buffer holds the latest value, and whether there's a newer one to upload.
+    uploads: Uploads,
+    pub layout: wgpu::BindGroupLayout,
+}
+
+impl<T: bytemuck::Pod> Uniform<T> {
+    /// A uniform with just the one buffer.
+    pub fn new(
+        device: &wgpu::Device,
+        value: T,
+        visibility: wgpu::ShaderStages,
+        label: &str,
+    ) -> Self {
+        Self::with_ring(device, value, visibility, label, 1)
+    }
+
+    /// A uniform with a ring of `ring` buffers (at least one). Only worth it for uniforms that
+    /// change every frame. Each `flush` that uploads uses the next buffer, so flush at most once
+    /// a frame, or it comes round to the buffer in use sooner. The ring should be at least as
+    /// long as the number of frames in flight.
+    pub fn with_ring(
+        device: &wgpu::Device,
+        value: T,
+        visibility: wgpu::ShaderStages,
+        label: &str,
+        ring: usize,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    // Would be true if we used one buffer for many objects, and picked which one
+                    // to read with an offset at draw time.
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some(label),
+        });
+        // Every buffer starts off with the value, so it doesn't matter which one's bound first.
+        let buffers: Vec<_> = (0..ring.max(1))
+            .map(|_| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::bytes_of(&value),
+                    // COPY_DST so we can overwrite it with queue.write_buffer every frame.
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                })
+            })
+            .collect();
+        let bind_groups = buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                    label: Some(label),
+                })
+            })
+            .collect();
+        Self {
+            value,
+            uploads: Uploads::new(buffers.len()),
+            buffers,
+            bind_groups,
+            layout,
+        }
+    }
+
+    /// Store the new value. Nothing goes to the GPU until `flush`, and only if the value is
+    /// actually different.
+    pub fn set(&mut self, value: T) {
+        if bytemuck::bytes_of(&value) != bytemuck::bytes_of(&self.value) {
+            self.value = value;
+            self.uploads.dirty = true;
+        }
+    }
+
+    /// Copy the value to the GPU (into the next buffer in the ring, if there is one), if it's
+    /// changed since the last flush. The copy doesn't happen immediately, it's queued up and runs
+    /// before the next submit.
+    pub fn flush(&mut self, queue: &wgpu::Queue) {
+        if let Some(buffer) = self.uploads.next() {
+            queue.write_buffer(&self.buffers[buffer], 0, bytemuck::bytes_of(&self.value));
+        }
+    }
+
+    /// The bind group for the buffer with the latest value. With a ring, that changes on every
+    /// `flush` that uploads, so get it again each time it's bound.
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_groups[self.uploads.current]
+    }
+}
+
+/// The bookkeeping half of `Uniform::flush`, apart from the GPU.
+struct Uploads {
+    /// Index of the buffer holding the latest value.
+    current: usize,
+    /// How many buffers are in the ring.
+    ring: usize,
+    /// Whether the value has changed since it was last copied to the GPU.
+    dirty: bool,
+}
+
+impl Uploads {
+    fn new(ring: usize) -> Self {
+        Self {
+            current: 0,
+            ring,
+            dirty: false,
+        }
+    }
+
+    /// Which buffer to upload the value to, if it's dirty. That buffer becomes the current one.
+    fn next(&mut self) -> Option<usize> {
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        self.current = (self.current + 1) % self.ring;
+        Some(self.current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flushing_a_clean_uniform_uploads_nothing() {
+        let mut uploads = Uploads::new(3);
+        assert_eq!(uploads.next(), None);
+        assert_eq!(uploads.current, 0);
+
+        uploads.dirty = true;
+        assert_eq!(uploads.next(), Some(1));
+        // Flushed, so clean again, and the ring stays where it is.
+        assert_eq!(uploads.next(), None);
+        assert_eq!(uploads.current, 1);
+    }
+
+    #[test]
+    fn uploads_go_round_the_ring() {
+        let mut uploads = Uploads::new(2);
+        let mut next = || {
+            uploads.dirty = true;
+            uploads.next()
+        };
+        assert_eq!([next(), next(), next()], [Some(1), Some(0), Some(1)]);
+    }
+}
@@ -0,0 +1,49 @@
+// Debug view: shows each fragment's texture coordinates as a colour, u in red and v in green,
+// instead of lighting and texturing it. A mesh with sensible UVs goes black in the corner of its
+// texture where (0, 0) is, and yellow where (1, 1) is.
+
+#include "common.wgsl"
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Same transform as shader.wgsl, so the debug view lines up with the normal one.
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let instance_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.clip_position = camera.view_proj * instance_matrix * model_uniform.transform
+        * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+fn uv_output(uv: vec2<f32>) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(uv, 0.0, 1.0);
+    // Same as the normals target's clear value, i.e. a zero normal. Normals aren't the point here.
+    out.normal = vec4<f32>(0.5, 0.5, 0.5, 0.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    return uv_output(in.tex_coords);
+}
+
+// With v flipped, for checking whether a mesh's UVs are upside down compared to what the textures
+// expect (v pointing up, as in OpenGL, rather than down).
+@fragment
+fn fs_flipped(in: VertexOutput) -> FragmentOutput {
+    return uv_output(vec2<f32>(in.tex_coords.x, 1.0 - in.tex_coords.y));
+}
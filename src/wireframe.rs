@@ -0,0 +1,239 @@
+use crate::{
+    scatter::InstanceRaw,
+    scene::{ModelUniforms, SceneObject},
+    texture::Texture,
+    uniform::Uniform,
+};
+
+/// How many pixels wide the edges start out, and how far F7 and F8 can take them.
+const DEFAULT_THICKNESS: f32 = 1.0;
+const MIN_THICKNESS: f32 = 0.5;
+const MAX_THICKNESS: f32 = 8.0;
+/// How much each press of F7 or F8 changes the thickness by, in pixels.
+const THICKNESS_STEP: f32 = 0.5;
+
+/// Near-black, so the edges show up against the textures. Premultiplied, like the scene's
+/// colours.
+const EDGE_COLOR: [f32; 4] = [0.02, 0.02, 0.02, 1.0];
+
+/// Corresponds to WireframeUniform in wireframe.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct WireframeUniform {
+    color: [f32; 4],
+    thickness: f32,
+    // Uniform structs are padded to a multiple of 16 bytes.
+    _padding: [f32; 3],
+}
+
+/// Every object's triangle edges, blended over the scene, for seeing how finely meshes are
+/// divided up. F6 toggles it, F7 and F8 make the edges thinner and thicker.
+///
+/// Unlike `PolygonMode::Line`, this needs no optional feature, so it works everywhere. Each
+/// object is drawn again over itself, from an unindexed copy of its mesh, so the shader can tell
+/// which corner of its triangle each vertex is (see `Mesh::triangle_positions`). Fragments near
+/// an edge get the edge colour, the rest are left as they were.
+///
+/// Like the point sprites, it has its own pipeline layout and sets its own bind groups, so it has
+/// to come after everything which uses the scene's.
+pub struct Wireframe {
+    pub enabled: bool,
+    /// Pixels.
+    thickness: f32,
+    uniform: Uniform<WireframeUniform>,
+    /// Kept for `rebuild_pipeline`.
+    shader: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Wireframe {
+    /// Drawn in the scene pass over the objects, so it has to match the scene's format, sample
+    /// count and targets, and `primitive` should be the scene's, so the same faces are culled.
+    /// `depth_compare` is the scene's too.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        models: &ModelUniforms,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+        normals_target: bool,
+        primitive: wgpu::PrimitiveState,
+        depth_compare: wgpu::CompareFunction,
+    ) -> Self {
+        let uniform = Uniform::new(
+            device,
+            WireframeUniform {
+                color: EDGE_COLOR,
+                thickness: DEFAULT_THICKNESS,
+                _padding: [0.0; 3],
+            },
+            wgpu::ShaderStages::FRAGMENT,
+            "wireframe_uniform",
+        );
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Wireframe Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("wireframe.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Wireframe Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, &models.layout, &uniform.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(
+            device,
+            &shader,
+            &layout,
+            target_format,
+            sample_count,
+            normals_target,
+            primitive,
+            depth_compare,
+        );
+        Self {
+            enabled: false,
+            thickness: DEFAULT_THICKNESS,
+            uniform,
+            shader,
+            layout,
+            pipeline,
+        }
+    }
+
+    /// Recreate the pipeline after the scene's have changed, so it keeps matching them. See `new`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn rebuild_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+        normals_target: bool,
+        primitive: wgpu::PrimitiveState,
+        depth_compare: wgpu::CompareFunction,
+    ) {
+        self.pipeline = create_pipeline(
+            device,
+            &self.shader,
+            &self.layout,
+            target_format,
+            sample_count,
+            normals_target,
+            primitive,
+            depth_compare,
+        );
+    }
+
+    /// Make the edges `steps` steps thicker, or thinner if it's negative, within limits. Returns
+    /// the new thickness in pixels.
+    pub fn adjust_thickness(&mut self, queue: &wgpu::Queue, steps: f32) -> f32 {
+        self.set_thickness(queue, self.thickness + steps * THICKNESS_STEP);
+        self.thickness
+    }
+
+    /// Turn it off, and go back to the default thickness.
+    pub fn reset(&mut self, queue: &wgpu::Queue) {
+        self.enabled = false;
+        self.set_thickness(queue, DEFAULT_THICKNESS);
+    }
+
+    fn set_thickness(&mut self, queue: &wgpu::Queue, thickness: f32) {
+        self.thickness = thickness.clamp(MIN_THICKNESS, MAX_THICKNESS);
+        self.uniform.set(WireframeUniform {
+            color: EDGE_COLOR,
+            thickness: self.thickness,
+            _padding: [0.0; 3],
+        });
+        self.uniform.flush(queue);
+    }
+
+    /// Draw the edges of `objects`' triangles, if it's enabled. See the struct's docs for why this
+    /// has to come late in the pass.
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        objects: impl IntoIterator<Item = &'a SceneObject>,
+        models: &'a ModelUniforms,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, self.uniform.bind_group(), &[]);
+        for object in objects {
+            object.draw_wireframe(render_pass, models, 1);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+    target_format: wgpu::TextureFormat,
+    sample_count: u32,
+    normals_target: bool,
+    primitive: wgpu::PrimitiveState,
+    depth_compare: wgpu::CompareFunction,
+) -> wgpu::RenderPipeline {
+    // The surface is already in the depth buffer, so the edges have to pass where they're
+    // exactly as deep as it, and are nudged towards the camera in case the two vertex shaders
+    // round differently. Which way that is depends on whether Z is reversed.
+    let (depth_compare, towards_camera) = match depth_compare {
+        wgpu::CompareFunction::Greater | wgpu::CompareFunction::GreaterEqual => {
+            (wgpu::CompareFunction::GreaterEqual, 1.0)
+        }
+        _ => (wgpu::CompareFunction::LessEqual, -1.0),
+    };
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Wireframe Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                },
+                InstanceRaw::descriptor(),
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &crate::normals::scene_targets(
+                target_format,
+                wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+                normals_target,
+            ),
+        }),
+        primitive: wgpu::PrimitiveState {
+            // Always triangles, whatever --topology the scene's drawn with, because the
+            // shader needs all three corners.
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            ..primitive
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState {
+                constant: 2 * towards_camera as i32,
+                slope_scale: towards_camera,
+                clamp: 0.0,
+            },
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
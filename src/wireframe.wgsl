@@ -0,0 +1,92 @@
+// Wireframe overlay: draws each triangle's edges over the already-drawn scene, by working out how
+// close each fragment is to the edge of its triangle. Needs no optional feature, unlike
+// PolygonMode::Line.
+
+// Same as in common.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    eye_position: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Same as in common.wgsl. The edges follow their object around, spinning and all.
+struct ModelUniform {
+    transform: mat4x4<f32>,
+    tint: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> model_uniform: ModelUniform;
+
+// Corresponds to WireframeUniform in the Rust code.
+struct WireframeUniform {
+    // Premultiplied by alpha.
+    color: vec4<f32>,
+    // How many pixels wide the edges are.
+    thickness: f32,
+};
+@group(2) @binding(0)
+var<uniform> wireframe: WireframeUniform;
+
+// Same as InstanceInput in common.wgsl, without the texture layer.
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // 1 at this vertex, 0 at the other two. Interpolated, it's how far the fragment is from each
+    // edge: a component reaches 0 along the edge opposite its vertex.
+    @location(0) barycentric: vec3<f32>,
+};
+
+// The triangles aren't indexed (see Mesh::triangle_positions), so every three vertices are a
+// triangle of their own, and the vertex index says which corner this is.
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) position: vec3<f32>,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let instance_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    var out: VertexOutput;
+    let corner = vertex_index % 3u;
+    out.barycentric = vec3<f32>(f32(corner == 0u), f32(corner == 1u), f32(corner == 2u));
+    // Worked out in the same order as shader.wgsl, so the depths match the surface's as closely
+    // as possible.
+    let world_position = instance_matrix * model_uniform.transform * vec4<f32>(position, 1.0);
+    out.clip_position = camera.view_proj * world_position;
+    return out;
+}
+
+// Two outputs, for two colour targets, like FragmentOutput in common.wgsl.
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    // How much each coordinate changes from one pixel to the next, so the thickness is in pixels
+    // however big the triangle is on screen. Smoothed over a pixel, so the edges are antialiased.
+    let per_pixel = fwidth(in.barycentric);
+    let distance = smoothstep(
+        per_pixel * (wireframe.thickness - 0.5),
+        per_pixel * (wireframe.thickness + 0.5),
+        in.barycentric,
+    );
+    let coverage = 1.0 - min(min(distance.x, distance.y), distance.z);
+    var out: FragmentOutput;
+    out.color = wireframe.color * coverage;
+    // Blended like the colour, so all zeros leaves the surface's normal as it was.
+    out.normal = vec4<f32>(0.0);
+    return out;
+}